@@ -0,0 +1,51 @@
+//! Compares parsing and reading attributes as strings with classifying them by `typed_iter`, to
+//! check that values are only classified when they're iterated.
+//! Run with `cargo bench --bench typed_iter`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use logfmt_nostd::{Log, Value};
+
+/// The lines parsed by every iteration
+const LINES: [&str; 3] = [
+    "request done method=GET path=/index.html status=200 duration=12ms cached=true",
+    "at=info user=\"alice smith\" ratio=0.75 retries=3 region=eu-west-1 missing=null",
+    "level=warn msg=\"slow query\" ms=1203 rows=5000 table=users sampled=false",
+];
+
+/// Runs the closure for every line for at least a second, returning the average time per line
+fn measure(mut run: impl FnMut(&str)) -> Duration {
+    let start = Instant::now();
+    let mut lines = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        for line in LINES {
+            run(black_box(line));
+        }
+        lines += LINES.len() as u32;
+    }
+    start.elapsed() / lines
+}
+
+fn main() {
+    let parse = measure(|line| {
+        black_box(Log::parse(line).unwrap());
+    });
+    let attributes = measure(|line| {
+        let log = Log::parse(line).unwrap();
+        for attribute in log.attributes() {
+            black_box(attribute);
+        }
+    });
+    let typed = measure(|line| {
+        let log = Log::parse(line).unwrap();
+        for (key, value) in log.typed_iter() {
+            black_box((key, matches!(value, Value::Int(_) | Value::Float(_))));
+        }
+    });
+    println!("parse:                {parse:?}/line");
+    println!("parse + attributes(): {attributes:?}/line");
+    println!("parse + typed_iter(): {typed:?}/line");
+}
@@ -0,0 +1,223 @@
+//! Parsing log messages into memory of a custom allocator, like an arena that's reset at once
+
+use alloc::vec::Vec;
+use core::alloc::Allocator;
+
+use crate::{DEFAULT_MAX_ATTRIBUTES, Token, UnclosedString, keys_match, tokens};
+
+/// A log message parsed like [`Log::parse`](crate::Log::parse), with the message and list of
+/// attributes allocated by the allocator
+#[derive(Debug, Clone)]
+pub struct AllocLog<'input, A: Allocator> {
+    /// The message as UTF-8
+    message: Vec<u8, A>,
+    attributes: Vec<(&'input str, &'input str), A>,
+    message_is_fallback: bool,
+}
+
+impl<'input, A: Allocator> AllocLog<'input, A> {
+    /// Return the message
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message).expect("the message is built from strings")
+    }
+
+    /// Check whether the message is the full input, because no message was found
+    pub const fn message_is_fallback(&self) -> bool {
+        self.message_is_fallback
+    }
+
+    /// Return the keys and values of the attributes
+    pub fn attributes(&self) -> &[(&'input str, &'input str)] {
+        &self.attributes
+    }
+
+    /// Return the value of the attribute with the key, ignoring surrounding quotes
+    pub fn get(&self, key: &str) -> Option<&'input str> {
+        self.attributes
+            .iter()
+            .find(|(existing, _)| keys_match(existing, key))
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Appends the word to the message, separated by a space
+fn push_word<A: Allocator>(message: &mut Vec<u8, A>, word: &[&str]) {
+    if !message.is_empty() {
+        message.push(b' ');
+    }
+    for part in word {
+        message.extend_from_slice(part.as_bytes());
+    }
+}
+
+impl crate::Log<'_> {
+    /// Parse a log message with the default options like [`Log::parse`](crate::Log::parse),
+    /// allocating the message and list of attributes with the allocator instead of the global
+    /// allocator. Keys and values are borrowed from the input.
+    pub fn parse_in_alloc<A: Allocator + Clone>(
+        s: &str,
+        alloc: A,
+    ) -> Result<AllocLog<'_, A>, UnclosedString> {
+        let mut message = Vec::new_in(alloc.clone());
+        let mut attributes: Vec<(&str, &str), A> = Vec::new_in(alloc);
+        let mut message_property_found = false;
+        let mut token_found = false;
+        for token in tokens(s) {
+            token_found = true;
+            match token? {
+                Token::Word(word) => {
+                    if !message_property_found {
+                        push_word(&mut message, &[word]);
+                    }
+                }
+
+                // Replace the message if this attribute is the message
+                Token::Attribute(key, value)
+                    if ["msg", "message"].contains(&crate::unquote(key)) =>
+                {
+                    message.clear();
+                    message.extend_from_slice(value.as_bytes());
+                    message_property_found = true;
+                }
+                Token::Attribute(key, value) => {
+                    let full = attributes.len() >= DEFAULT_MAX_ATTRIBUTES;
+                    match attributes
+                        .iter_mut()
+                        .find(|(existing, _)| keys_match(existing, key))
+                    {
+                        Some((_, existing)) => *existing = value,
+                        None if !full => {
+                            attributes.push((key, value));
+                        }
+                        None => {
+                            if !message_property_found {
+                                push_word(&mut message, &[key, "=", value]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Use the input without byte order mark and line endings as message, if no message was
+        // found and it isn't blank
+        let message_is_fallback = message.is_empty() && token_found;
+        if message_is_fallback {
+            let content = s.strip_prefix('\u{FEFF}').unwrap_or(s);
+            message.extend_from_slice(content.trim_end_matches(['\r', '\n']).as_bytes());
+        }
+        Ok(AllocLog {
+            message,
+            attributes,
+            message_is_fallback,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use alloc::vec::Vec;
+    use core::{
+        alloc::{AllocError, Allocator, GlobalAlloc, Layout},
+        cell::{Cell, UnsafeCell},
+        ptr::NonNull,
+    };
+    use std::alloc::System;
+
+    use crate::{Log, UnclosedString};
+
+    std::thread_local! {
+        /// The number of allocations by the global allocator on this thread
+        static GLOBAL_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// The system allocator, counting allocations per thread
+    struct CountingGlobal;
+
+    unsafe impl GlobalAlloc for CountingGlobal {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = GLOBAL_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingGlobal = CountingGlobal;
+
+    /// An arena handing out memory of a fixed buffer, which is only freed at once
+    struct Bump {
+        memory: UnsafeCell<[u8; 4096]>,
+        used: Cell<usize>,
+        allocations: Cell<usize>,
+    }
+
+    unsafe impl Allocator for &Bump {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let base = self.memory.get().cast::<u8>();
+            let start = (base as usize + self.used.get()).next_multiple_of(layout.align());
+            let offset = start - base as usize;
+            let end = offset.checked_add(layout.size()).ok_or(AllocError)?;
+            if end > 4096 {
+                return Err(AllocError);
+            }
+            self.used.set(end);
+            self.allocations.set(self.allocations.get() + 1);
+            let ptr = NonNull::new(base.wrapping_add(offset)).ok_or(AllocError)?;
+            Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+        }
+
+        unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {}
+    }
+
+    #[test]
+    fn parse_in_alloc() {
+        let lines = [
+            "level=info request done",
+            "msg=\"disk full\" path=/var level=error path=/tmp",
+            "a=1 b=2",
+            "\u{FEFF}k=v\r\n",
+            "",
+            "bad=\"x\"y word",
+        ];
+        for line in lines {
+            let bump = Bump {
+                memory: UnsafeCell::new([0; 4096]),
+                used: Cell::new(0),
+                allocations: Cell::new(0),
+            };
+            let expected = Log::parse(line).unwrap();
+            let expected_attributes: Vec<_> = expected.iter().collect();
+
+            let before = GLOBAL_ALLOCATIONS.with(Cell::get);
+            let log = Log::parse_in_alloc(line, &bump).unwrap();
+            assert_eq!(GLOBAL_ALLOCATIONS.with(Cell::get), before, "{line:?}");
+            assert!(bump.allocations.get() > 0 || line.is_empty());
+
+            assert_eq!(log.message(), expected.message(), "{line:?}");
+            assert_eq!(log.message_is_fallback(), expected.message_is_fallback());
+            assert_eq!(log.attributes(), expected_attributes);
+        }
+
+        // The attributes past the limit are part of the message, like with the global allocator
+        let line: Vec<_> = (0..30).map(|index| alloc::format!("k{index}=v")).collect();
+        let line = line.join(" ");
+        let bump = Bump {
+            memory: UnsafeCell::new([0; 4096]),
+            used: Cell::new(0),
+            allocations: Cell::new(0),
+        };
+        let log = Log::parse_in_alloc(&line, &bump).unwrap();
+        assert_eq!(log.message(), Log::parse(&line).unwrap().message());
+        assert_eq!(log.get("k3"), Some("v"));
+        assert_eq!(
+            Log::parse_in_alloc("a=\"open", &bump).err(),
+            Some(UnclosedString)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Batches of logfmt lines behind a versioned header, to read serialized logs back after the
+//! serialization rules changed.
+//! The header is `LFMT`, followed by the format version and flags as two hexadecimal digits each
+//! and a newline, like `LFMT0100\n`.
+
+use core::fmt::{self, Display, Write};
+
+use crate::{EncodeError, EncodeOptions, Log, ParseLines, parse_lines};
+
+/// The bytes every batch starts with
+pub const MAGIC: &str = "LFMT";
+
+/// The version of the serialization rules written by [`Writer`], bumped when they change
+pub const VERSION: u8 = 1;
+
+/// The flag set when the lines are written with [`EncodeOptions::canonical`]
+pub const FLAG_CANONICAL: u8 = 1;
+
+/// The length of the header in bytes
+const HEADER_LEN: usize = MAGIC.len() + 5;
+
+/// The reason reading the header of a batch failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum HeaderError {
+    /// The input doesn't start with [`MAGIC`]
+    WrongMagic,
+
+    /// The input ends before the end of the header
+    Truncated,
+
+    /// The version or flags aren't hexadecimal digits, or aren't followed by a newline
+    Malformed,
+
+    /// The version is newer than [`VERSION`]
+    UnsupportedVersion(u8),
+
+    /// The flags contain flags that aren't known in the version
+    UnknownFlags(u8),
+}
+
+impl Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongMagic => f.write_str("not a logfmt batch"),
+            Self::Truncated => f.write_str("truncated batch header"),
+            Self::Malformed => f.write_str("malformed batch header"),
+            Self::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "unsupported batch version {version}, expected at most {VERSION}"
+                )
+            }
+            Self::UnknownFlags(flags) => write!(f, "unknown batch flags {flags:#04x}"),
+        }
+    }
+}
+
+impl core::error::Error for HeaderError {}
+
+/// Writes log messages as logfmt lines behind the header of the current version
+#[derive(Debug)]
+pub struct Writer<W: Write> {
+    writer: W,
+    flags: u8,
+    header_written: bool,
+}
+
+impl<W: Write> Writer<W> {
+    /// Create a writer writing to the writer
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            flags: 0,
+            header_written: false,
+        }
+    }
+
+    /// Write the lines with [`EncodeOptions::canonical`] and set [`FLAG_CANONICAL`]
+    #[must_use]
+    pub const fn canonical(mut self) -> Self {
+        self.flags |= FLAG_CANONICAL;
+        self
+    }
+
+    /// Writes the header, if it wasn't written yet
+    fn write_header(&mut self) -> fmt::Result {
+        if !self.header_written {
+            writeln!(self.writer, "{MAGIC}{VERSION:02x}{:02x}", self.flags)?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    /// Write the log message as line, after the header if it's the first
+    pub fn push(&mut self, log: &Log<'_>) -> Result<&mut Self, EncodeError> {
+        self.write_header()?;
+        if self.flags & FLAG_CANONICAL == 0 {
+            log.write_logfmt(&mut self.writer)?;
+        } else {
+            log.write_logfmt_with_options(
+                &mut self.writer,
+                &EncodeOptions {
+                    canonical: true,
+                    ..EncodeOptions::default()
+                },
+            )?;
+        }
+        self.writer.write_char('\n')?;
+        Ok(self)
+    }
+
+    /// Write the header if no log messages were written, returning the writer
+    pub fn finish(mut self) -> Result<W, fmt::Error> {
+        self.write_header()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the log messages of a batch with a supported version, like [`parse_lines`]
+#[derive(Debug, Clone)]
+pub struct Reader<'input> {
+    version: u8,
+    flags: u8,
+    lines: ParseLines<'input>,
+}
+
+impl<'input> Reader<'input> {
+    /// Create a reader for the batch, returning an error if the header isn't supported.
+    /// The lines are numbered from the line after the header, starting at 1.
+    pub fn new(input: &'input str) -> Result<Self, HeaderError> {
+        let magic_len = input.len().min(MAGIC.len());
+        if input.as_bytes()[..magic_len] != MAGIC.as_bytes()[..magic_len] {
+            return Err(HeaderError::WrongMagic);
+        }
+        let header = input.get(..HEADER_LEN).ok_or(HeaderError::Truncated)?;
+        let hex = |range| {
+            header
+                .get(range)
+                .filter(|digits: &&str| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or(HeaderError::Malformed)
+        };
+        let version = hex(MAGIC.len()..MAGIC.len() + 2)?;
+        let flags = hex(MAGIC.len() + 2..MAGIC.len() + 4)?;
+        if !header.ends_with('\n') {
+            return Err(HeaderError::Malformed);
+        }
+        if version == 0 || version > VERSION {
+            return Err(HeaderError::UnsupportedVersion(version));
+        }
+        if flags & !FLAG_CANONICAL != 0 {
+            return Err(HeaderError::UnknownFlags(flags));
+        }
+        Ok(Self {
+            version,
+            flags,
+            lines: parse_lines(&input[HEADER_LEN..]),
+        })
+    }
+
+    /// Return the version of the batch
+    pub const fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Check whether the lines were written with [`EncodeOptions::canonical`]
+    pub const fn canonical(&self) -> bool {
+        self.flags & FLAG_CANONICAL != 0
+    }
+}
+
+impl<'input> Iterator for Reader<'input> {
+    type Item = <ParseLines<'input> as Iterator>::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    use crate::Log;
+
+    use super::{HeaderError, Reader, Writer};
+
+    #[test]
+    fn round_trip() {
+        let lines = ["msg=\"disk full\" path=/var", "level=info code=7"];
+        let mut writer = Writer::new(String::new());
+        for line in lines {
+            writer.push(&Log::parse(line).unwrap()).unwrap();
+        }
+        let batch = writer.finish().unwrap();
+        assert_eq!(
+            batch,
+            "LFMT0100\nmsg=\"disk full\" path=/var\nlevel=info code=7\n"
+        );
+        let reader = Reader::new(&batch).unwrap();
+        assert_eq!((reader.version(), reader.canonical()), (1, false));
+        let logs: Vec<_> = reader.map(|(number, log)| (number, log.unwrap())).collect();
+        assert_eq!(
+            logs,
+            [
+                (1, Log::parse(lines[0]).unwrap()),
+                (2, Log::parse(lines[1]).unwrap())
+            ]
+        );
+
+        let mut writer = Writer::new(String::new()).canonical();
+        writer.push(&Log::parse("b=2 a=\"1\"").unwrap()).unwrap();
+        let batch = writer.finish().unwrap();
+        assert_eq!(batch, "LFMT0101\na=1 b=2\n");
+        assert!(Reader::new(&batch).unwrap().canonical());
+        let empty = Writer::new(String::new()).finish().unwrap();
+        assert_eq!(Reader::new(&empty).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn header_errors() {
+        let error = |input| Reader::new(input).err();
+        assert_eq!(error("a=1\n"), Some(HeaderError::WrongMagic));
+        assert_eq!(error("LFMX0100\n"), Some(HeaderError::WrongMagic));
+        assert_eq!(error("LFM"), Some(HeaderError::Truncated));
+        assert_eq!(error("LFMT010"), Some(HeaderError::Truncated));
+        assert_eq!(error("LFMT01zz\n"), Some(HeaderError::Malformed));
+        assert_eq!(error("LFMT0100 a=1"), Some(HeaderError::Malformed));
+        assert_eq!(
+            error("LFMT0200\na=1\n"),
+            Some(HeaderError::UnsupportedVersion(2))
+        );
+        assert_eq!(error("LFMT0180\n"), Some(HeaderError::UnknownFlags(0x80)));
+        assert_eq!(
+            HeaderError::UnsupportedVersion(2).to_string(),
+            "unsupported batch version 2, expected at most 1"
+        );
+    }
+}
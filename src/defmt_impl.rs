@@ -0,0 +1,50 @@
+//! defmt support, enabled by the `defmt` feature
+
+use core::convert::Infallible;
+
+use defmt::{Format, Formatter};
+
+use crate::Log;
+
+impl Format for Log<'_> {
+    /// Writes the log message as logfmt line, like [`Log::write_logfmt`], without allocating
+    fn format(&self, f: Formatter<'_>) {
+        let Ok(()) = self.logfmt_chunks::<Infallible>(&mut |chunk| {
+            defmt::write!(f, "{=str}", chunk);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use defmt::Format;
+
+    use crate::{
+        AutoParseError, BufferTooSmall, EncodeError, ErrorKind, InvalidKey, JsonError,
+        JsonErrorKind, Level, Log, ParseError, TemplateError, TemplateErrorKind, UnclosedString,
+        UnescapeError, UnknownLevel,
+    };
+
+    /// Only checks the bound, formatting needs a defmt logger
+    const fn assert_format<T: Format>() {}
+
+    #[test]
+    fn implemented() {
+        assert_format::<Log<'_>>();
+        assert_format::<Level>();
+        assert_format::<UnclosedString>();
+        assert_format::<ErrorKind>();
+        assert_format::<ParseError>();
+        assert_format::<InvalidKey>();
+        assert_format::<UnescapeError>();
+        assert_format::<UnknownLevel>();
+        assert_format::<JsonErrorKind>();
+        assert_format::<JsonError>();
+        assert_format::<AutoParseError>();
+        assert_format::<EncodeError>();
+        assert_format::<BufferTooSmall>();
+        assert_format::<TemplateErrorKind>();
+        assert_format::<TemplateError>();
+    }
+}
@@ -0,0 +1,207 @@
+//! Differential and round-trip tests against lines from other logfmt implementations.
+//! The corpus in `fixtures/differential.json` holds lines from the test suite of Go's
+//! `go-logfmt` and from Heroku router logs, with the message and attributes both agree on.
+//! Bare words are keys without value in `go-logfmt`, here they're part of the message.
+//! Lines parsed differently have a `divergence` describing it, with the result expected here,
+//! and lines parsed with [`DuplicatePolicy::KeepAll`] have `keep_all` set.
+//! The inputs in `fixtures/go_logfmt.json` are copied from the tests of `go-logfmt`, with the
+//! pairs it decodes, and are parsed with [`Dialect::GoLogfmt`].
+
+use alloc::{borrow::Cow, format, string::String, vec::Vec};
+
+use proptest::{
+    collection::vec,
+    sample::select,
+    strategy::Strategy,
+    test_runner::{Config, TestRunner},
+};
+use serde_json::Value;
+
+use crate::{
+    Dialect, DuplicatePolicy, Encoder, Log, ParseError, ParseOptions, decode_quoted, unescape,
+};
+
+/// The decoded message, if it isn't empty or the fallback message, and the decoded attributes
+type Extracted = (Option<String>, Vec<(String, String)>);
+
+/// A line of the corpus with the expected message and decoded attributes, or `None` if parsing
+/// fails
+struct Case {
+    source: String,
+    line: String,
+    keep_all: bool,
+    expected: Option<Extracted>,
+}
+
+/// Loads the cases of the corpus
+fn corpus() -> Vec<Case> {
+    let corpus: Value = serde_json::from_str(include_str!("../fixtures/differential.json"))
+        .expect("the corpus should be valid JSON");
+    let string = |value: &Value| String::from(value.as_str().expect("expected a string"));
+    corpus
+        .as_array()
+        .expect("the corpus should be an array")
+        .iter()
+        .map(|case| Case {
+            source: string(&case["source"]),
+            line: string(&case["line"]),
+            keep_all: case["keep_all"] == Value::Bool(true),
+            expected: (case["error"] != Value::Bool(true)).then(|| {
+                let message = case["message"].as_str().map(String::from);
+                let attributes = case["attributes"]
+                    .as_array()
+                    .expect("expected the attributes")
+                    .iter()
+                    .map(|pair| (string(&pair[0]), string(&pair[1])))
+                    .collect();
+                (message, attributes)
+            }),
+        })
+        .collect()
+}
+
+/// Returns the message and attributes compared with the corpus
+fn extract(log: &Log<'_>) -> Extracted {
+    let message = (!log.message_is_fallback() && !log.message().is_empty())
+        .then(|| decode_quoted(log.message()).into());
+    let attributes = log
+        .iter()
+        .map(|(key, value)| {
+            let value = unescape(value).unwrap_or(Cow::Borrowed(value));
+            (String::from(key), value.into_owned())
+        })
+        .collect();
+    (message, attributes)
+}
+
+/// A strategy for strings of up to `max_len` of the characters
+pub(crate) fn string_of(chars: &'static [char], max_len: usize) -> impl Strategy<Value = String> {
+    vec(select(chars), 0..=max_len).prop_map(String::from_iter)
+}
+
+#[test]
+fn corpus_agrees() {
+    let corpus = corpus();
+    assert!(corpus.len() >= 15);
+    for case in corpus {
+        let options = ParseOptions {
+            duplicates: if case.keep_all {
+                DuplicatePolicy::KeepAll
+            } else {
+                DuplicatePolicy::default()
+            },
+            ..ParseOptions::default()
+        };
+        let parsed = Log::parse_with(&case.line, &options).ok();
+        assert_eq!(
+            parsed.as_ref().map(extract),
+            case.expected,
+            "{} line {:?}",
+            case.source,
+            case.line
+        );
+
+        // Writing the parsed lines again keeps the attributes
+        if let Some(log) = parsed {
+            let line = log.to_logfmt();
+            assert_eq!(
+                extract(&Log::parse_with(&line, &options).unwrap()).1,
+                extract(&log).1,
+                "{line}"
+            );
+        }
+    }
+}
+
+#[test]
+fn go_dialect_agrees() {
+    let corpus: Value = serde_json::from_str(include_str!("../fixtures/go_logfmt.json"))
+        .expect("the corpus should be valid JSON");
+    let options = ParseOptions::dialect(Dialect::GoLogfmt);
+    for case in corpus.as_array().expect("the corpus should be an array") {
+        let input = case["input"].as_str().expect("expected the input");
+        let pairs = input
+            .lines()
+            .map(|line| {
+                let log = Log::parse_with(line, &options)?;
+                Ok(log
+                    .iter()
+                    .map(|(key, value)| (String::from(key), String::from(value)))
+                    .collect::<Vec<_>>())
+            })
+            .collect::<Result<Vec<_>, ParseError>>()
+            .map(|lines| lines.concat());
+        let expected = case["pairs"].as_array().map(|pairs| {
+            pairs
+                .iter()
+                .map(|pair| {
+                    let string = |index: usize| String::from(pair[index].as_str().unwrap());
+                    (string(0), string(1))
+                })
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(pairs.ok(), expected, "{input:?}");
+    }
+
+    // There are no length or count limits
+    let line: Vec<_> = (0..30)
+        .map(|index| format!("k{index}={}", "v".repeat(200)))
+        .collect();
+    let line = format!("{}={} {}", "k".repeat(60), "v".repeat(200), line.join(" "));
+    assert_eq!(
+        Log::parse_with(&line, &options).unwrap().attribute_count(),
+        31
+    );
+
+    // The lenient dialect stays the default
+    let log = Log::parse_with("y msg=hi", &ParseOptions::dialect(Dialect::Lenient)).unwrap();
+    assert_eq!((log.message(), log.attribute_count()), ("hi", 0));
+}
+
+#[test]
+fn encoded_attributes_round_trip() {
+    const KEY_CHARS: [char; 8] = ['a', 'z', 'K', '0', '_', '.', '-', 'ß'];
+    const VALUE_CHARS: [char; 16] = [
+        'a', 'Z', '9', ' ', '"', '\'', '=', '\\', '\n', '\t', '\r', '\u{1}', '\u{7f}', 'é', '€',
+        '😀',
+    ];
+    // Keep the keys unique by ending them with their index
+    let pairs = vec(
+        (string_of(&KEY_CHARS, 6), string_of(&VALUE_CHARS, 12)),
+        0..6,
+    )
+    .prop_map(|pairs| {
+        pairs
+            .into_iter()
+            .enumerate()
+            .map(|(index, (mut key, value))| {
+                key.push(char::from(b'0' + index as u8));
+                (key, value)
+            })
+            .collect::<Vec<_>>()
+    });
+    let strategy = (pairs, string_of(&VALUE_CHARS, 12));
+    let mut runner = TestRunner::new(Config::with_cases(1000));
+    let result = runner.run(&strategy, |(pairs, mut message)| {
+        // Messages surrounded by double quotes are taken as quoted already
+        while message.len() > 1 && message.starts_with('"') && message.ends_with('"') {
+            message.pop();
+        }
+
+        let mut encoder = Encoder::new(String::new());
+        let mut builder = Log::build();
+        for (key, value) in &pairs {
+            encoder.push(key, value).unwrap();
+            builder = builder.attr(key, value);
+        }
+        let line = encoder.finish();
+        assert_eq!(extract(&Log::parse(&line).unwrap()).1, pairs, "{line:?}");
+
+        let line = builder.msg(&message).finish_to_string().unwrap();
+        let log = Log::parse(&line).unwrap();
+        let expected_message = (!message.is_empty()).then(|| message.clone());
+        assert_eq!(extract(&log), (expected_message, pairs), "{line:?}");
+        Ok(())
+    });
+    result.unwrap();
+}
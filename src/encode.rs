@@ -0,0 +1,1002 @@
+//! Writing log messages as logfmt and other formats
+
+use alloc::{borrow::Cow, format, string::String, vec::Vec};
+use core::fmt::{self, Display, Write as _};
+
+use crate::{
+    BufferTooSmall, Level, Log, Timestamp, Token, TypedValue, Value,
+    parse::contains_unescaped,
+    tokens, unescape, unquote,
+    value::{LEVEL_KEYS, TIMESTAMP_KEYS, civil_from_days},
+};
+
+pub use crate::encoder::{EncodeError, Encoder, EncoderFactory};
+
+/// Checks whether the string is surrounded by double quotes, without unescaped quotes inside
+pub(crate) fn is_quoted(s: &str) -> bool {
+    s.len() > 1
+        && s.starts_with('"')
+        && s.ends_with('"')
+        && !contains_unescaped(&s[1..s.len() - 1], '"')
+}
+
+/// Writes to a byte buffer, counting the required length once the buffer is full
+struct SliceWriter<'buf> {
+    buf: &'buf mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Strings are only written if every earlier string fit, so the buffer is valid UTF-8
+        if let Some(target) = self.buf.get_mut(self.len..self.len + s.len()) {
+            target.copy_from_slice(s.as_bytes());
+        }
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+/// Checks whether a key, value, or message can't be parsed back unquoted, because it contains
+/// whitespace, quotes, `=`, backslashes, or control characters.
+/// Shared by the encoder and every logfmt writer of logs.
+pub(crate) fn value_needs_quotes(s: &str) -> bool {
+    s.contains(|ch: char| {
+        ch.is_whitespace() || ch.is_control() || matches!(ch, '"' | '\'' | '=' | '\\')
+    })
+}
+
+/// Checks whether a key, value, or message [`value_needs_quotes`] and isn't quoted already
+fn needs_quotes(s: &str) -> bool {
+    !is_quoted(s) && value_needs_quotes(s)
+}
+
+/// Checks whether the message consists of words separated by single spaces, without control
+/// characters, so it's parsed back unchanged when it's written as free text
+fn is_words(message: &str) -> bool {
+    let mut end = 0;
+    for token in tokens(message) {
+        let Ok(Token::Word(word)) = token else {
+            return false;
+        };
+        let start = word.as_ptr() as usize - message.as_ptr() as usize;
+        if start != end && (start != end + 1 || end == 0 || message.as_bytes()[end] != b' ') {
+            return false;
+        }
+        end = start + word.len();
+    }
+    end == message.len() && !message.contains(char::is_control)
+}
+
+/// Writes a key, value, or message, quoting it if [`needs_quotes`], like [`quoted_chunks`]
+fn write_quoted<W: fmt::Write + ?Sized>(f: &mut W, s: &str) -> fmt::Result {
+    quoted_chunks(s, &mut |chunk| f.write_str(chunk))
+}
+
+/// Writes the string surrounded by quotes, escaping quotes, backslashes, and control characters
+pub(crate) fn write_with_quotes<W: fmt::Write + ?Sized>(f: &mut W, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    escaped_chunks(s, true, &mut |chunk| f.write_str(chunk))?;
+    f.write_char('"')
+}
+
+/// Passes a key, value, or message to `write` in chunks, quoting it if [`needs_quotes`].
+/// Control characters are escaped, also in strings that are quoted already.
+/// Shared by every logfmt writer, independent of the formatting machinery.
+fn quoted_chunks<E>(s: &str, write: &mut impl FnMut(&str) -> Result<(), E>) -> Result<(), E> {
+    let (contents, escape_quotes) = if is_quoted(s) {
+        (&s[1..s.len() - 1], false)
+    } else if needs_quotes(s) {
+        (s, true)
+    } else {
+        return write(s);
+    };
+    write("\"")?;
+    escaped_chunks(contents, escape_quotes, write)?;
+    write("\"")
+}
+
+/// Passes the string to `write` in chunks, escaping control characters and optionally quotes and
+/// backslashes
+fn escaped_chunks<E>(
+    s: &str,
+    escape_quotes: bool,
+    write: &mut impl FnMut(&str) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut rest = s;
+    while let Some(index) =
+        rest.find(|ch: char| ch.is_control() || (escape_quotes && matches!(ch, '"' | '\\')))
+    {
+        write(&rest[..index])?;
+        let ch = rest[index..].chars().next().unwrap_or_default();
+        write(escape_char(ch, &mut [0; 8]))?;
+        rest = &rest[index + ch.len_utf8()..];
+    }
+    write(rest)
+}
+
+/// Returns the escape sequence of a quote, backslash, or control character, using `\n`, `\t`,
+/// `\r`, or `\u{XXXX}` for control characters
+fn escape_char(ch: char, buffer: &mut [u8; 8]) -> &str {
+    match ch {
+        '"' => "\\\"",
+        '\\' => "\\\\",
+        '\n' => "\\n",
+        '\t' => "\\t",
+        '\r' => "\\r",
+        _ => {
+            // Control characters are at most U+009F, so four hexadecimal digits are enough
+            let code = u32::from(ch);
+            *buffer = *b"\\u{0000}";
+            for (index, shift) in [12, 8, 4, 0].into_iter().enumerate() {
+                buffer[3 + index] = b"0123456789abcdef"[((code >> shift) & 0xf) as usize];
+            }
+            core::str::from_utf8(buffer).unwrap_or_default()
+        }
+    }
+}
+
+/// Writes the value, cutting the contents to at most `max_len` bytes followed by `…` if it's
+/// longer
+fn write_truncated<W: fmt::Write + ?Sized>(
+    f: &mut W,
+    value: &str,
+    max_len: Option<usize>,
+    canonical: bool,
+) -> fmt::Result {
+    let contents = decode_quoted(value);
+    let cut;
+    let contents = match max_len.filter(|max| contents.len() > *max) {
+        Some(max) => {
+            let end = (0..=max)
+                .rev()
+                .find(|end| contents.is_char_boundary(*end))
+                .unwrap_or(0);
+            cut = format!("{}…", &contents[..end]);
+            cut.as_str()
+        }
+        None if !canonical => return write_quoted(f, value),
+        None => contents.as_ref(),
+    };
+    if canonical {
+        // Quote the contents only when needed, regardless of how the value was quoted
+        crate::encoder::write_value(f, contents)
+    } else {
+        write_quoted(f, contents)
+    }
+}
+
+impl Display for Log<'_> {
+    /// Writes the log message as logfmt line, like [`Log::write_logfmt`]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_logfmt(f)
+    }
+}
+
+impl Log<'_> {
+    /// Writes the log message as logfmt line, with the message as `msg` attribute unless it's the
+    /// fallback message. Messages of several words separated by single spaces are written as is
+    /// before the attributes instead, so they're parsed back the same.
+    /// Keys and values containing whitespace, quotes, `=`, backslashes, or control characters are
+    /// quoted like [`Encoder::push`] does, unless they're quoted already. Control characters are
+    /// escaped as `\n`, `\t`, `\r`, or `\u{XXXX}`.
+    pub fn write_logfmt<W: fmt::Write + ?Sized>(&self, f: &mut W) -> fmt::Result {
+        self.logfmt_chunks(&mut |chunk| f.write_str(chunk))
+    }
+
+    /// Passes the logfmt line of [`Log::write_logfmt`] to `write` in chunks
+    pub(crate) fn logfmt_chunks<E>(
+        &self,
+        write: &mut impl FnMut(&str) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut separator = "";
+        if !self.message.is_empty() && !self.message_is_fallback {
+            // Quoting words would make them one quoted value when they're parsed again
+            if needs_quotes(&self.message) && is_words(&self.message) {
+                write(&self.message)?;
+            } else {
+                write("msg=")?;
+                quoted_chunks(&self.message, write)?;
+            }
+            separator = " ";
+        }
+        for (key, value) in self {
+            write(separator)?;
+            quoted_chunks(key, write)?;
+            write("=")?;
+            quoted_chunks(value, write)?;
+            separator = " ";
+        }
+        Ok(())
+    }
+
+    /// Writes the log message as logfmt line, like [`Log::write_logfmt`], using the options
+    pub fn write_logfmt_with_options<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        options: &EncodeOptions,
+    ) -> Result<(), EncodeError> {
+        // Check everything before writing, to not write part of the line
+        if options.reject_control_chars {
+            let message = (!self.message_is_fallback).then_some(self.message.as_ref());
+            if message
+                .into_iter()
+                .chain(self.iter().flat_map(|(key, value)| [key, value]))
+                .any(|s| s.contains(char::is_control))
+            {
+                return Err(EncodeError::ControlCharacter);
+            }
+        }
+
+        let mut line = String::new();
+        let mut separator = "";
+        if !self.message.is_empty() && !self.message_is_fallback {
+            line.push_str("msg=");
+            if options.canonical {
+                write_truncated(&mut line, &self.message, None, true)?;
+            } else {
+                write_quoted(&mut line, &self.message)?;
+            }
+            separator = " ";
+        }
+        let message_end = line.len();
+        let mut attributes: Vec<_> = self.iter().collect();
+        if options.canonical {
+            // Attributes with the same key are ordered by value, which is also written decoded
+            attributes.sort_by_cached_key(|&(key, value)| (unquote(key), decode_quoted(value)));
+        }
+        let mut attribute_ends = Vec::with_capacity(attributes.len());
+        for (key, value) in attributes {
+            line.push_str(separator);
+            if options.canonical {
+                crate::encoder::write_value(&mut line, unquote(key))?;
+            } else {
+                write_quoted(&mut line, key)?;
+            }
+            line.push('=');
+            write_truncated(&mut line, value, options.max_value_len, options.canonical)?;
+            attribute_ends.push(line.len());
+            separator = " ";
+        }
+
+        // Make room for ` crc=` and the checksum
+        let max_line_len = match options.max_line_len {
+            Some(max) if options.trailer_crc32 => Some(max.saturating_sub(CRC_KEY.len() + 10)),
+            max => max,
+        };
+        if let Some(max) = max_line_len.filter(|max| line.len() > *max) {
+            // Keep as many attributes as fit together with the marker
+            const MARKER: &str = "truncated=true";
+            let end = attribute_ends
+                .into_iter()
+                .rev()
+                .find(|end| end + 1 + MARKER.len() <= max)
+                .unwrap_or(message_end);
+            line.truncate(end);
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(MARKER);
+        }
+        if options.trailer_crc32 {
+            let crc = crc32(line.as_bytes());
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            write!(line, "{CRC_KEY}={crc:08x}")?;
+        }
+        Ok(f.write_str(&line)?)
+    }
+
+    /// Check the `crc` attribute against the CRC-32 of the original line before it, like written
+    /// with [`EncodeOptions::trailer_crc32`].
+    /// Returns `None` without `crc` attribute, and `Some(false)` if the line doesn't end with it.
+    pub fn verify_crc(&self, original_line: &str) -> Option<bool> {
+        let crc = self.get(CRC_KEY)?;
+        let line = original_line.trim_end_matches(['\r', '\n']);
+        let Some(rest) = line
+            .strip_suffix(crc)
+            .and_then(|rest| rest.strip_suffix('='))
+            .and_then(|rest| rest.strip_suffix(CRC_KEY))
+            .filter(|rest| rest.is_empty() || rest.ends_with(' '))
+        else {
+            return Some(false);
+        };
+        let covered = rest.strip_suffix(' ').unwrap_or(rest);
+        let expected = u32::from_str_radix(crc, 16).ok().filter(|_| crc.len() == 8);
+        Some(expected == Some(crc32(covered.as_bytes())))
+    }
+
+    /// Returns the log message as logfmt line, like [`Log::write_logfmt_with_options`]
+    pub fn to_logfmt_with_options(&self, options: &EncodeOptions) -> Result<String, EncodeError> {
+        let mut line = String::new();
+        self.write_logfmt_with_options(&mut line, options)?;
+        Ok(line)
+    }
+
+    /// Returns the log message as canonical logfmt line, with [`EncodeOptions::canonical`]
+    pub fn to_logfmt_canonical(&self) -> String {
+        self.to_logfmt_with_options(&EncodeOptions {
+            canonical: true,
+            ..EncodeOptions::default()
+        })
+        .expect("writing to a string can't fail")
+    }
+
+    /// Writes the log message as logfmt line in the style, skipping the fallback message.
+    /// Keys and values are quoted like [`Log::write_logfmt`].
+    pub fn write_logfmt_with<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        style: EncodeStyle,
+    ) -> fmt::Result {
+        if self.message.is_empty() || self.message_is_fallback {
+            return self.write_attributes_only(f, " ");
+        }
+        let prose = decode_quoted(&self.message);
+        if style == EncodeStyle::Prose && is_words(&prose) {
+            f.write_str(&prose)?;
+        } else if is_quoted(&self.message) {
+            f.write_str("msg=")?;
+            write_quoted(f, &self.message)?;
+        } else {
+            f.write_str("msg=")?;
+            write_with_quotes(f, &self.message)?;
+        }
+        if self.has_attributes() {
+            f.write_char(' ')?;
+        }
+        self.write_attributes_only(f, " ")
+    }
+
+    /// Writes the message without surrounding quotes and with escape sequences of quoted messages
+    /// decoded, writing nothing for the fallback message
+    pub fn write_message_only<W: fmt::Write + ?Sized>(&self, f: &mut W) -> fmt::Result {
+        if self.message_is_fallback {
+            return Ok(());
+        }
+        f.write_str(&decode_quoted(&self.message))
+    }
+
+    /// Writes the attributes separated by the separator, without the message.
+    /// Keys and values are quoted like [`Log::write_logfmt`].
+    pub fn write_attributes_only<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        separator: &str,
+    ) -> fmt::Result {
+        for (index, (key, value)) in self.iter().enumerate() {
+            if index > 0 {
+                f.write_str(separator)?;
+            }
+            write_quoted(f, key)?;
+            f.write_char('=')?;
+            write_quoted(f, value)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the log message as logfmt line, like [`Log::write_logfmt`]
+    pub fn to_logfmt(&self) -> String {
+        let mut line = String::new();
+        self.write_logfmt(&mut line)
+            .expect("writing to a string can't fail");
+        line
+    }
+
+    /// Writes the log message as logfmt line into the buffer, like [`Log::write_logfmt`],
+    /// returning the number of bytes written.
+    /// Nothing is written past the end of the buffer, the contents of the buffer are unspecified
+    /// if it's too small.
+    pub fn write_logfmt_to_slice(&self, buf: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        let mut writer = SliceWriter { buf, len: 0 };
+        self.write_logfmt(&mut writer)
+            .expect("writing to a slice writer can't fail");
+        if writer.len > writer.buf.len() {
+            return Err(BufferTooSmall {
+                required: writer.len,
+            });
+        }
+        Ok(writer.len)
+    }
+
+    /// Returns the input of [`Log::parse_lossless`] byte for byte, or the log message as logfmt
+    /// line like [`Log::to_logfmt`] if it wasn't parsed losslessly or has been changed since
+    pub fn to_original(&self) -> Cow<'_, str> {
+        match &self.original {
+            Some(original) => Cow::Borrowed(original),
+            None => Cow::Owned(self.to_logfmt()),
+        }
+    }
+
+    /// Returns the log message as logfmt line in the style, like [`Log::write_logfmt_with`]
+    pub fn to_logfmt_with(&self, style: EncodeStyle) -> String {
+        let mut line = String::new();
+        self.write_logfmt_with(&mut line, style)
+            .expect("writing to a string can't fail");
+        line
+    }
+
+    /// Writes the log message as JSON object with string values, like [`Log::write_json_with`]
+    pub fn write_json<W: fmt::Write + ?Sized>(&self, f: &mut W) -> fmt::Result {
+        self.write_json_with(f, JsonValues::Strings)
+    }
+
+    /// Writes the log message as JSON object, with the message as `msg` entry unless it's empty
+    /// or the fallback message.
+    /// Keys and values are written without surrounding quotes and with escape sequences
+    /// decoded, the output only contains ASCII characters.
+    pub fn write_json_with<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        values: JsonValues,
+    ) -> fmt::Result {
+        f.write_char('{')?;
+        let mut separator = "";
+        if !self.message.is_empty() && !self.message_is_fallback {
+            f.write_str("\"msg\":")?;
+            write_json_string(f, &decode_quoted(&self.message))?;
+            separator = ",";
+        }
+        for (key, value) in self {
+            f.write_str(separator)?;
+            write_json_string(f, &decode_quoted(key))?;
+            f.write_char(':')?;
+            match (values, TypedValue::new(value).value()) {
+                (JsonValues::Typed, Value::Int(int)) => write!(f, "{int}")?,
+                (JsonValues::Typed, Value::Float(float)) if float.is_finite() => {
+                    write!(f, "{float}")?;
+                }
+                (JsonValues::Typed, Value::Bool(bool)) => write!(f, "{bool}")?,
+                (JsonValues::Typed, Value::Null) => f.write_str("null")?,
+                _ => write_json_string(f, &decode_quoted(value))?,
+            }
+            separator = ",";
+        }
+        f.write_char('}')
+    }
+
+    /// Returns the log message as JSON object with string values, like [`Log::write_json_with`]
+    pub fn to_json(&self) -> String {
+        self.to_json_with(JsonValues::Strings)
+    }
+
+    /// Returns the log message as JSON object, like [`Log::write_json_with`]
+    pub fn to_json_with(&self, values: JsonValues) -> String {
+        let mut json = String::new();
+        self.write_json_with(&mut json, values)
+            .expect("writing to a string can't fail");
+        json
+    }
+
+    /// Writes the header row of [`Log::write_csv_row`], without line ending
+    pub fn write_csv_header<W: fmt::Write + ?Sized>(
+        f: &mut W,
+        columns: &[&str],
+        delimiter: char,
+    ) -> fmt::Result {
+        write_csv_fields(f, columns.iter().copied().map(Cow::Borrowed), delimiter)
+    }
+
+    /// Writes the values of the columns as CSV row, without line ending.
+    /// The `message` column contains the message, other columns the value of the first attribute
+    /// with the key without surrounding quotes, or nothing if it doesn't exist.
+    /// Fields containing the delimiter, quotes, or line breaks are quoted as described in RFC 4180.
+    pub fn write_csv_row<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        columns: &[&str],
+        delimiter: char,
+    ) -> fmt::Result {
+        let fields = columns.iter().map(|column| match *column {
+            "message" if self.message_is_fallback => Cow::Borrowed(""),
+            "message" => decode_quoted(&self.message),
+            _ => self.get(column).map_or(Cow::Borrowed(""), decode_quoted),
+        });
+        write_csv_fields(f, fields, delimiter)
+    }
+
+    /// Writes the log message as GELF 1.1 JSON object for Graylog.
+    /// The message is the `short_message`, the level and timestamp are written as numeric
+    /// `level` and `timestamp`, and the other attributes are additional fields prefixed with `_`.
+    /// Characters other than ASCII alphanumerics, `_`, `.`, and `-` in keys are replaced with
+    /// `_`, and the reserved `id` key is written as `_id_`. Integers and floating point numbers
+    /// are written as numbers, other values as strings.
+    pub fn write_gelf<W: fmt::Write + ?Sized>(&self, f: &mut W, host: &str) -> fmt::Result {
+        f.write_str("{\"version\":\"1.1\",\"host\":")?;
+        write_json_string(f, host)?;
+        f.write_str(",\"short_message\":")?;
+        write_json_string(f, &decode_quoted(&self.message))?;
+
+        // Only skip the attributes that were used as timestamp and level
+        let timestamp = self.timestamp_parsed();
+        let timestamp_key = timestamp.and(
+            TIMESTAMP_KEYS
+                .into_iter()
+                .find(|key| self.contains_key(key)),
+        );
+        if let Some(timestamp) = timestamp {
+            f.write_str(",\"timestamp\":")?;
+            write_seconds(f, timestamp)?;
+        }
+        let level = self.level();
+        let level_key = level.and(LEVEL_KEYS.into_iter().find(|key| self.contains_key(key)));
+        if let Some(level) = level {
+            write!(f, ",\"level\":{}", level.syslog_severity())?;
+        }
+
+        for (key, value) in self {
+            if [timestamp_key, level_key].contains(&Some(unquote(key))) {
+                continue;
+            }
+            f.write_str(",\"_")?;
+            let key = decode_quoted(key);
+            if key == "id" {
+                f.write_str("id_")?;
+            } else {
+                for ch in key.chars() {
+                    let valid = ch.is_ascii_alphanumeric() || matches!(ch, '_' | '.' | '-');
+                    f.write_char(if valid { ch } else { '_' })?;
+                }
+            }
+            f.write_str("\":")?;
+            match TypedValue::new(value).value() {
+                Value::Int(int) => write!(f, "{int}")?,
+                Value::Float(float) if float.is_finite() => write!(f, "{float}")?,
+                _ => write_json_string(f, &decode_quoted(value))?,
+            }
+        }
+        f.write_char('}')
+    }
+
+    /// Returns the log message as GELF 1.1 JSON object, like [`Log::write_gelf`]
+    pub fn to_gelf(&self, host: &str) -> String {
+        let mut gelf = String::new();
+        self.write_gelf(&mut gelf, host)
+            .expect("writing to a string can't fail");
+        gelf
+    }
+
+    /// Writes the attributes as RFC 5424 structured data element with the SD-ID, like
+    /// `[id@32473 duration="10" foo="bar"]`.
+    /// Keys are truncated to 32 characters, with characters that aren't allowed in parameter
+    /// names replaced with `_`. Values are written without surrounding quotes, escaping `\`,
+    /// `"`, and `]`.
+    pub fn write_syslog_sd<W: fmt::Write + ?Sized>(&self, f: &mut W, sd_id: &str) -> fmt::Result {
+        write!(f, "[{sd_id}")?;
+        for (key, value) in self {
+            f.write_char(' ')?;
+            for ch in decode_quoted(key).chars().take(32) {
+                let valid = ch.is_ascii_graphic() && !matches!(ch, '=' | ']' | '"');
+                f.write_char(if valid { ch } else { '_' })?;
+            }
+            f.write_str("=\"")?;
+            for ch in decode_quoted(value).chars() {
+                if matches!(ch, '\\' | '"' | ']') {
+                    f.write_char('\\')?;
+                }
+                f.write_char(ch)?;
+            }
+            f.write_char('"')?;
+        }
+        f.write_char(']')
+    }
+
+    /// Returns the attributes as RFC 5424 structured data element, like [`Log::write_syslog_sd`]
+    pub fn to_syslog_sd(&self, sd_id: &str) -> String {
+        let mut sd = String::new();
+        self.write_syslog_sd(&mut sd, sd_id)
+            .expect("writing to a string can't fail");
+        sd
+    }
+
+    /// Writes the log message as RFC 5424 syslog message,
+    /// `<PRI>1 TIMESTAMP HOST APP - - [SD] MSG`.
+    /// The priority combines the facility with the severity of the level, or informational if the
+    /// level isn't known. The timestamp is written in UTC with microseconds, or `-` if it's
+    /// missing. The attributes are written like [`Log::write_syslog_sd`].
+    pub fn write_syslog<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        options: &SyslogOptions<'_>,
+    ) -> fmt::Result {
+        let severity = self.level().unwrap_or(Level::Info).syslog_severity();
+        write!(
+            f,
+            "<{}>1 ",
+            u16::from(options.facility) * 8 + u16::from(severity)
+        )?;
+        match self.timestamp_parsed() {
+            Some(timestamp) => write_syslog_timestamp(f, timestamp)?,
+            None => f.write_char('-')?,
+        }
+        for field in [options.hostname, options.app_name] {
+            write!(f, " {}", if field.is_empty() { "-" } else { field })?;
+        }
+        f.write_str(" - - ")?;
+        self.write_syslog_sd(f, options.sd_id)?;
+        if !self.message.is_empty() && !self.message_is_fallback {
+            write!(f, " {}", decode_quoted(&self.message))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the log message as RFC 5424 syslog message, like [`Log::write_syslog`]
+    pub fn to_syslog(&self, options: &SyslogOptions<'_>) -> String {
+        let mut syslog = String::new();
+        self.write_syslog(&mut syslog, options)
+            .expect("writing to a string can't fail");
+        syslog
+    }
+
+    /// Writes the message followed by the attributes with ANSI colors: the message is red for
+    /// errors and yellow for warnings, and keys are dimmed.
+    /// Quoted messages are written without quotes and the fallback message is skipped, keys and
+    /// values are quoted like [`Log::write_logfmt`].
+    pub fn format_colored<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        scheme: &ColorScheme<'_>,
+    ) -> fmt::Result {
+        let message_color = match self.level() {
+            Some(Level::Error | Level::Fatal) => scheme.error,
+            Some(Level::Warn) => scheme.warn,
+            _ => "",
+        };
+        let mut separator = "";
+        if !self.message.is_empty() && !self.message_is_fallback {
+            scheme.paint(f, message_color, |f| {
+                f.write_str(&decode_quoted(&self.message))
+            })?;
+            separator = " ";
+        }
+        for (key, value) in self {
+            f.write_str(separator)?;
+            scheme.paint(f, scheme.key, |f| {
+                write_quoted(f, key)?;
+                f.write_char('=')
+            })?;
+            write_quoted(f, value)?;
+            separator = " ";
+        }
+        Ok(())
+    }
+
+    /// Writes the message on the first line followed by every attribute on an indented line,
+    /// like [`Log::format_pretty_with`] with [`DEFAULT_WRAP_COLUMN`]
+    pub fn format_pretty<W: fmt::Write + ?Sized>(&self, f: &mut W) -> fmt::Result {
+        self.format_pretty_with(f, DEFAULT_WRAP_COLUMN)
+    }
+
+    /// Writes the message on the first line followed by every attribute on an indented line,
+    /// with the keys padded to the longest key, leaving the first line empty for the fallback
+    /// message.
+    /// Quotes are removed and values are wrapped at whitespace to fit before the column, or
+    /// split if a word doesn't fit.
+    pub fn format_pretty_with<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        column: usize,
+    ) -> fmt::Result {
+        if !self.message_is_fallback {
+            f.write_str(&decode_quoted(&self.message))?;
+        }
+        let key_width = self
+            .keys()
+            .map(|key| decode_quoted(key).chars().count())
+            .max()
+            .unwrap_or(0);
+        // The indentation, key, and ` = ` come before every line of the value
+        let indent = 2 + key_width + 3;
+        let value_width = column.saturating_sub(indent).max(1);
+        for (key, value) in self {
+            write!(f, "\n  {:<key_width$} = ", decode_quoted(key))?;
+            let value = decode_quoted(value);
+            let mut rest = value.as_ref();
+            while rest.chars().count() > value_width {
+                // Break at the last whitespace that fits, or split the word at the width
+                let limit = rest
+                    .char_indices()
+                    .nth(value_width)
+                    .map_or(rest.len(), |(index, _)| index);
+                let line_end = if rest[limit..].starts_with(char::is_whitespace) {
+                    Some(limit)
+                } else {
+                    rest[..limit].rfind(char::is_whitespace)
+                };
+                let (line, next) = match line_end {
+                    Some(index) if index > 0 => {
+                        let space_len = rest[index..].chars().next().map_or(1, char::len_utf8);
+                        (&rest[..index], &rest[index + space_len..])
+                    }
+                    _ => rest.split_at(limit),
+                };
+                write!(f, "{line}\n{:indent$}", "")?;
+                rest = next;
+            }
+            f.write_str(rest)?;
+        }
+        Ok(())
+    }
+}
+
+/// How attribute values are written as JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonValues {
+    /// Write every value as string
+    #[default]
+    Strings,
+
+    /// Write unquoted integers, floating point numbers, booleans, and null as JSON values
+    Typed,
+}
+
+/// Options for writing log messages with [`Log::write_logfmt_with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EncodeOptions {
+    /// Return an error for messages, keys, and values containing control characters, instead of
+    /// escaping them
+    pub reject_control_chars: bool,
+
+    /// The maximum length of values in bytes, without surrounding quotes and escape sequences.
+    /// Longer values are cut at a character boundary and followed by `…`.
+    pub max_value_len: Option<usize>,
+
+    /// The maximum length of the line in bytes.
+    /// Attributes at the end of longer lines are dropped and replaced by `truncated=true`, the
+    /// message is always kept.
+    pub max_line_len: Option<usize>,
+
+    /// Sort the attributes by their keys without surrounding quotes and then by their decoded
+    /// values, and quote the message, keys, and values only when needed, so equal attributes in
+    /// any order are written the same
+    pub canonical: bool,
+
+    /// Append `crc=` and the CRC-32 of the bytes before it as 8 lowercase hexadecimal digits,
+    /// checked by [`Log::verify_crc`]. The trailer counts towards the maximum line length.
+    pub trailer_crc32: bool,
+}
+
+/// The key of the CRC-32 trailer of [`EncodeOptions::trailer_crc32`]
+const CRC_KEY: &str = "crc";
+
+/// Returns the CRC-32 (IEEE) of the bytes
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(u32::MAX, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+/// How [`Log::write_logfmt_with`] writes the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncodeStyle {
+    /// Write the message as free text before the attributes.
+    /// Messages that would be parsed differently, because a word contains `=` or quotes or the
+    /// words aren't separated by single spaces, are written like [`EncodeStyle::Canonical`].
+    #[default]
+    Prose,
+
+    /// Write the message as quoted `msg` attribute before the attributes
+    Canonical,
+}
+
+/// How lists are written by [`Encoder::list`](crate::Encoder::list) and read by [`Log::get_list`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListStyle {
+    /// An attribute with the key per item, like `tag=a tag=b`
+    Repeat,
+
+    /// One attribute with the items separated by the delimiter, like `tags=a,b`.
+    /// Delimiters and backslashes in items are escaped by a backslash, an empty value is an
+    /// empty list.
+    Delimited(char),
+}
+
+/// Joins the items with the delimiter, escaping delimiters and backslashes in items
+pub(crate) fn join_list(items: impl IntoIterator<Item = impl Display>, delimiter: char) -> String {
+    let mut list = String::new();
+    for (index, item) in items.into_iter().enumerate() {
+        if index > 0 {
+            list.push(delimiter);
+        }
+        for ch in format!("{item}").chars() {
+            if ch == delimiter || ch == '\\' {
+                list.push('\\');
+            }
+            list.push(ch);
+        }
+    }
+    list
+}
+
+/// Splits the list on delimiters that aren't escaped by a backslash, removing the escapes
+pub(crate) fn split_list(list: &str, delimiter: char) -> Vec<Cow<'static, str>> {
+    if list.is_empty() {
+        return Vec::new();
+    }
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = list.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => item.extend(chars.next()),
+            _ if ch == delimiter => items.push(Cow::Owned(core::mem::take(&mut item))),
+            _ => item.push(ch),
+        }
+    }
+    items.push(Cow::Owned(item));
+    items
+}
+
+/// The header fields of syslog messages written by [`Log::write_syslog`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyslogOptions<'options> {
+    /// The facility code, combined with the severity of the level into the priority
+    pub facility: u8,
+
+    /// The host name, `-` if it's empty
+    pub hostname: &'options str,
+
+    /// The application name, `-` if it's empty
+    pub app_name: &'options str,
+
+    /// The SD-ID of the structured data element containing the attributes
+    pub sd_id: &'options str,
+}
+
+impl Default for SyslogOptions<'_> {
+    fn default() -> Self {
+        Self {
+            facility: 1,
+            hostname: "",
+            app_name: "",
+            sd_id: "logfmt@32473",
+        }
+    }
+}
+
+/// The default column at which [`Log::format_pretty`] wraps values
+pub const DEFAULT_WRAP_COLUMN: usize = 80;
+
+/// The ANSI escape sequences used by [`Log::format_colored`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorScheme<'scheme> {
+    /// Whether escape sequences are written, disable for output that isn't a terminal
+    pub enabled: bool,
+
+    /// The color of the message of errors
+    pub error: &'scheme str,
+
+    /// The color of the message of warnings
+    pub warn: &'scheme str,
+
+    /// The style of the keys
+    pub key: &'scheme str,
+}
+
+impl ColorScheme<'_> {
+    /// The escape sequence resetting the color and style
+    const RESET: &'static str = "\x1b[0m";
+
+    /// Writes with the color, unless colors are disabled or the color is empty
+    fn paint<W: fmt::Write + ?Sized>(
+        &self,
+        f: &mut W,
+        color: &str,
+        write: impl FnOnce(&mut W) -> fmt::Result,
+    ) -> fmt::Result {
+        if !self.enabled || color.is_empty() {
+            return write(f);
+        }
+        f.write_str(color)?;
+        write(f)?;
+        f.write_str(Self::RESET)
+    }
+}
+
+impl Default for ColorScheme<'_> {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            error: "\x1b[31m",
+            warn: "\x1b[33m",
+            key: "\x1b[2m",
+        }
+    }
+}
+
+/// Removes surrounding quotes and decodes escape sequences of quoted strings.
+/// Strings with invalid escape sequences are only unquoted.
+pub(crate) fn decode_quoted(s: &str) -> Cow<'_, str> {
+    if is_quoted(s) {
+        unescape(s).unwrap_or(Cow::Borrowed(unquote(s)))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// Writes the fields separated by the delimiter, quoting fields containing the delimiter, quotes,
+/// or line breaks and doubling their quotes
+fn write_csv_fields<'a, W: fmt::Write + ?Sized>(
+    f: &mut W,
+    fields: impl Iterator<Item = Cow<'a, str>>,
+    delimiter: char,
+) -> fmt::Result {
+    for (index, field) in fields.enumerate() {
+        if index > 0 {
+            f.write_char(delimiter)?;
+        }
+        if field.contains([delimiter, '"', '\n', '\r']) {
+            write!(f, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            f.write_str(&field)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the timestamp in UTC with microseconds as RFC 5424 timestamp, or `-` if the year
+/// doesn't have four digits
+fn write_syslog_timestamp<W: fmt::Write + ?Sized>(f: &mut W, timestamp: Timestamp) -> fmt::Result {
+    let (days, seconds) = (
+        timestamp.secs().div_euclid(86400),
+        timestamp.secs().rem_euclid(86400),
+    );
+    let (year, month, day) = civil_from_days(days);
+    if !(0..=9999).contains(&year) {
+        return f.write_char('-');
+    }
+    write!(
+        f,
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )?;
+    let micros = timestamp.nanos() / 1000;
+    if micros != 0 {
+        write!(f, ".{micros:06}")?;
+    }
+    f.write_char('Z')
+}
+
+/// Writes the timestamp as decimal number of seconds since the Unix epoch
+fn write_seconds<W: fmt::Write + ?Sized>(f: &mut W, timestamp: Timestamp) -> fmt::Result {
+    let (secs, nanos) = (timestamp.secs(), timestamp.nanos());
+    if nanos == 0 {
+        return write!(f, "{secs}");
+    }
+
+    // The nanoseconds are added to the seconds, so negative timestamps need the complement
+    let (sign, secs, nanos) = if secs < 0 {
+        ("-", (secs + 1).unsigned_abs(), 1_000_000_000 - nanos)
+    } else {
+        ("", secs.unsigned_abs(), nanos)
+    };
+    let fraction = format!("{nanos:09}");
+    write!(f, "{sign}{secs}.{}", fraction.trim_end_matches('0'))
+}
+
+/// Writes the string as JSON string, escaping characters that aren't printable ASCII
+pub(crate) fn write_json_string<W: fmt::Write + ?Sized>(f: &mut W, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in s.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            ' '..='~' => f.write_char(ch)?,
+            // Other characters are written as UTF-16 code units, using surrogate pairs
+            _ => {
+                for unit in ch.encode_utf16(&mut [0; 2]) {
+                    write!(f, "\\u{unit:04x}")?;
+                }
+            }
+        }
+    }
+    f.write_char('"')
+}
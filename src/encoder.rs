@@ -0,0 +1,990 @@
+//! Writing logfmt lines from key/value pairs, independent of [`Log`](crate::Log)
+
+use alloc::{
+    borrow::Cow,
+    format,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    cell::Cell,
+    fmt::{self, Display, Write},
+};
+
+use crate::{
+    InvalidKey, ListStyle, encode::value_needs_quotes, join_list, keys_match,
+    log::validate_new_key, unquote, write_with_quotes,
+};
+
+/// An error returned when encoding an attribute fails
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    /// The key isn't allowed by the parser
+    InvalidKey(InvalidKey),
+
+    /// A message, key, or value contains a control character while those are rejected
+    ControlCharacter,
+
+    /// Writing to the writer failed
+    Write(fmt::Error),
+
+    /// The message was written after attributes by an encoder that isn't buffered
+    MessageNotFirst,
+
+    /// The value of the attribute with the key is longer than the maximum length
+    ValueTooLong {
+        /// The key of the attribute, including the prefixes of scopes
+        key: String,
+    },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidKey(error) => error.fmt(f),
+            Self::ControlCharacter => f.write_str("control character in value"),
+            Self::Write(error) => error.fmt(f),
+            Self::MessageNotFirst => f.write_str("message after attributes"),
+            Self::ValueTooLong { key } => write!(f, "value of `{key}` too long"),
+        }
+    }
+}
+
+impl core::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::InvalidKey(error) => Some(error),
+            Self::Write(error) => Some(error),
+            Self::ControlCharacter | Self::MessageNotFirst | Self::ValueTooLong { .. } => None,
+        }
+    }
+}
+
+impl From<InvalidKey> for EncodeError {
+    fn from(error: InvalidKey) -> Self {
+        Self::InvalidKey(error)
+    }
+}
+
+impl From<fmt::Error> for EncodeError {
+    fn from(error: fmt::Error) -> Self {
+        Self::Write(error)
+    }
+}
+
+/// The attribute appended by [`Encoder::finish`] when attributes were dropped
+const TRUNCATION_MARKER: &str = "truncated=true";
+
+/// The fields written at the start of every line
+#[derive(Clone)]
+struct AutoFields {
+    clock: Rc<dyn Fn() -> u64>,
+    /// The sequence number of the next line, if lines are numbered, shared by the encoders of a
+    /// factory
+    seq: Option<Rc<Cell<u64>>>,
+}
+
+impl AutoFields {
+    fn new(clock: impl Fn() -> u64 + 'static, seq: bool) -> Self {
+        Self {
+            clock: Rc::new(clock),
+            seq: seq.then(|| Rc::new(Cell::new(0))),
+        }
+    }
+}
+
+impl fmt::Debug for AutoFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoFields")
+            .field("seq", &self.seq)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The message and attributes of a line of a buffered encoder
+#[derive(Debug, Default)]
+struct Buffer {
+    message: Option<String>,
+    attributes: Vec<(String, String)>,
+}
+
+/// The number of bytes of an attribute written without allocating
+const ATTRIBUTE_BUFFER_LEN: usize = 256;
+
+/// An attribute being formatted, on the stack unless it's longer than
+/// [`ATTRIBUTE_BUFFER_LEN`] bytes, to write it at once
+struct AttributeBuffer {
+    bytes: [u8; ATTRIBUTE_BUFFER_LEN],
+    len: usize,
+    /// The attribute, once it's too long for the stack
+    spilled: Option<String>,
+}
+
+impl AttributeBuffer {
+    /// Formats the attribute, preceded by a space unless it's the first of the line
+    fn new(key: &str, value: &str, first: bool) -> Result<Self, EncodeError> {
+        let mut attribute = Self {
+            bytes: [0; ATTRIBUTE_BUFFER_LEN],
+            len: 0,
+            spilled: None,
+        };
+        if !first {
+            attribute.write_char(' ')?;
+        }
+        attribute.write_str(key)?;
+        attribute.write_char('=')?;
+        write_value(&mut attribute, value)?;
+        Ok(attribute)
+    }
+
+    fn as_str(&self) -> &str {
+        match &self.spilled {
+            Some(spilled) => spilled,
+            // Only whole strings are copied to the bytes
+            None => core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default(),
+        }
+    }
+}
+
+impl Write for AttributeBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(spilled) = &mut self.spilled {
+            spilled.push_str(s);
+        } else if let Some(bytes) = self.bytes.get_mut(self.len..self.len + s.len()) {
+            bytes.copy_from_slice(s.as_bytes());
+            self.len += s.len();
+        } else {
+            let mut spilled = String::with_capacity(self.len + s.len());
+            spilled.push_str(self.as_str());
+            spilled.push_str(s);
+            self.spilled = Some(spilled);
+        }
+        Ok(())
+    }
+}
+
+/// The attributes added to every line, shared by the encoders of a factory
+type Context = Rc<Vec<(String, String)>>;
+
+/// Creates encoders appending the same context attributes to every line
+#[derive(Debug, Clone, Default)]
+pub struct EncoderFactory {
+    context: Context,
+    auto_fields: Option<AutoFields>,
+}
+
+impl EncoderFactory {
+    /// Create a factory without context attributes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the attribute to every line, unless the line has an attribute with the key,
+    /// ignoring surrounding quotes. The value of an earlier context attribute with the key is
+    /// replaced.
+    #[must_use]
+    pub fn context(mut self, key: &str, value: &str) -> Self {
+        let context = Rc::make_mut(&mut self.context);
+        match context
+            .iter_mut()
+            .find(|(existing, _)| keys_match(existing, key))
+        {
+            Some((_, existing)) => *existing = value.into(),
+            None => context.push((key.into(), value.into())),
+        }
+        self
+    }
+
+    /// Start every line of the encoders with automatic fields, like
+    /// [`Encoder::with_auto_fields`]. The lines of all encoders of the factory, and of its
+    /// clones, are numbered by one sequence.
+    #[must_use]
+    pub fn with_auto_fields(mut self, clock: impl Fn() -> u64 + 'static, seq: bool) -> Self {
+        self.auto_fields = Some(AutoFields::new(clock, seq));
+        self
+    }
+
+    /// Create an encoder writing to the writer, appending the context attributes when a line is
+    /// finished. The keys are validated when they're written.
+    pub fn encoder<W: Write>(&self, writer: W) -> Encoder<W> {
+        self.encoder_from(Encoder::new(writer))
+    }
+
+    /// Create a buffered encoder writing to the writer, like [`EncoderFactory::encoder`]
+    pub fn buffered<W: Write>(&self, writer: W) -> Encoder<W> {
+        self.encoder_from(Encoder::buffered(writer))
+    }
+
+    /// Adds the context attributes and automatic fields to the encoder
+    fn encoder_from<W: Write>(&self, mut encoder: Encoder<W>) -> Encoder<W> {
+        if !self.context.is_empty() {
+            encoder.context = Some(Rc::clone(&self.context));
+        }
+        encoder.auto_fields.clone_from(&self.auto_fields);
+        encoder
+    }
+}
+
+/// Writes attributes as logfmt lines to a writer, separated by spaces
+#[derive(Debug)]
+pub struct Encoder<W: Write> {
+    writer: W,
+    first: bool,
+    max_len: Option<usize>,
+    max_value_len: Option<usize>,
+    /// The number of bytes written to the writer
+    len: usize,
+    /// The attributes that may be dropped to make room for the truncation marker
+    held: String,
+    /// The end of every held attribute
+    held_ends: Vec<usize>,
+    truncated: bool,
+    /// The prefixes of the current scopes, each followed by the scope separator
+    prefix: String,
+    scope_separator: char,
+    auto_fields: Option<AutoFields>,
+    /// Whether the automatic fields of the line were written
+    line_started: bool,
+    /// The message and attributes of the line, if they're written when it's finished
+    buffer: Option<Buffer>,
+    sort_keys: bool,
+    /// The attributes appended to every line by an [`EncoderFactory`]
+    context: Option<Context>,
+    /// The keys written on the line, if there are context attributes
+    line_keys: Vec<String>,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Create an encoder writing to the writer
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            first: true,
+            max_len: None,
+            max_value_len: None,
+            len: 0,
+            held: String::new(),
+            held_ends: Vec::new(),
+            truncated: false,
+            prefix: String::new(),
+            scope_separator: '.',
+            auto_fields: None,
+            line_started: false,
+            buffer: None,
+            sort_keys: false,
+            context: None,
+            line_keys: Vec::new(),
+        }
+    }
+
+    /// Create an encoder writing to the writer when a line is finished, with the message first
+    /// after the automatic fields, and the value of the last attribute with a key, ignoring
+    /// surrounding quotes, in place of the first
+    pub fn buffered(writer: W) -> Self {
+        let mut encoder = Self::new(writer);
+        encoder.buffer = Some(Buffer::default());
+        encoder
+    }
+
+    /// Sort the attributes by their keys, ignoring surrounding quotes, if the encoder is
+    /// buffered
+    #[must_use]
+    pub const fn with_sorted_keys(mut self) -> Self {
+        self.sort_keys = true;
+        self
+    }
+
+    /// Start every line with `ts=` and the time returned by the clock, followed by `seq=` and
+    /// the number of the line starting at 0 if `seq` is set. Buffered attributes don't replace
+    /// them and sorting doesn't move them.
+    #[must_use]
+    pub fn with_auto_fields(mut self, clock: impl Fn() -> u64 + 'static, seq: bool) -> Self {
+        self.auto_fields = Some(AutoFields::new(clock, seq));
+        self
+    }
+
+    /// Join the prefixes of scopes and keys with the separator instead of `.`
+    #[must_use]
+    pub const fn with_scope_separator(mut self, separator: char) -> Self {
+        self.scope_separator = separator;
+        self
+    }
+
+    /// Limit the line to `max_len` bytes.
+    /// The first attribute that would make the line longer is dropped along with every later
+    /// attribute, and [`Encoder::finish`] appends `truncated=true`. To make room for it, the
+    /// attributes at the end are dropped until it fits, unless it doesn't fit on its own.
+    /// Attributes that may still be dropped are held back until the line is finished.
+    #[must_use]
+    pub const fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Return an error for values longer than `max_value_len` bytes, instead of writing them
+    #[must_use]
+    pub const fn with_max_value_len(mut self, max_value_len: usize) -> Self {
+        self.max_value_len = Some(max_value_len);
+        self
+    }
+
+    /// Check whether attributes were dropped to stay within the maximum length
+    pub const fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Write an attribute, quoting the value if it contains whitespace, quotes, `=`, backslashes,
+    /// or control characters.
+    /// The key is validated with the default parse options.
+    /// Keys in scopes are prefixed, the joined key is validated.
+    /// Nothing of the attribute is written if an error is returned, as long as the writer doesn't
+    /// write part of a string when it fails.
+    pub fn push(&mut self, key: &str, value: &str) -> Result<&mut Self, EncodeError> {
+        self.start_line()?;
+        let scoped;
+        let key = if self.prefix.is_empty() {
+            key
+        } else {
+            scoped = format!("{}{key}", self.prefix);
+            &scoped
+        };
+        validate_new_key(key)?;
+        if self.max_value_len.is_some_and(|max| value.len() > max) {
+            return Err(EncodeError::ValueTooLong { key: key.into() });
+        }
+        if let Some(buffer) = &mut self.buffer {
+            match buffer
+                .attributes
+                .iter_mut()
+                .find(|(existing, _)| keys_match(existing, key))
+            {
+                Some((_, existing)) => *existing = value.into(),
+                None => buffer.attributes.push((key.into(), value.into())),
+            }
+            return Ok(self);
+        }
+        self.write_attribute(key, value)
+    }
+
+    /// Write the message as `msg` attribute, before the attributes and automatic fields.
+    /// Encoders that aren't buffered return an error if attributes were written.
+    pub fn message(&mut self, message: &str) -> Result<&mut Self, EncodeError> {
+        match &mut self.buffer {
+            Some(buffer) => buffer.message = Some(message.into()),
+            None if self.line_started || !self.first => return Err(EncodeError::MessageNotFirst),
+            None => {
+                self.write_attribute("msg", message)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Writes a validated attribute to the writer
+    fn write_attribute(&mut self, key: &str, value: &str) -> Result<&mut Self, EncodeError> {
+        if let Some(max_len) = self.max_len {
+            return self.push_limited(key, value, max_len);
+        }
+
+        // Write the attribute at once, to not write part of it
+        let attribute = AttributeBuffer::new(key, value, self.first)?;
+        self.writer.write_str(attribute.as_str())?;
+        self.first = false;
+        self.mark_written(key);
+        Ok(self)
+    }
+
+    /// Remembers the key was written on the line, to not append a context attribute with it
+    fn mark_written(&mut self, key: &str) {
+        if self.context.is_some() {
+            self.line_keys.push(key.into());
+        }
+    }
+
+    /// Write an attribute with the formatted value, like [`Encoder::push`]
+    pub fn push_display(
+        &mut self,
+        key: &str,
+        value: &impl Display,
+    ) -> Result<&mut Self, EncodeError> {
+        self.push(key, &value.to_string())
+    }
+
+    /// Write the items of a list in the style, like [`Encoder::push_display`].
+    /// Empty lists aren't written with [`ListStyle::Repeat`].
+    pub fn list(
+        &mut self,
+        key: &str,
+        items: impl IntoIterator<Item = impl Display>,
+        style: ListStyle,
+    ) -> Result<&mut Self, EncodeError> {
+        match style {
+            ListStyle::Repeat => {
+                for item in items {
+                    self.push_display(key, &item)?;
+                }
+                Ok(self)
+            }
+            ListStyle::Delimited(delimiter) => self.push(key, &join_list(items, delimiter)),
+        }
+    }
+
+    /// Writes the automatic fields, if the line didn't start yet
+    fn start_line(&mut self) -> Result<(), EncodeError> {
+        if self.line_started {
+            return Ok(());
+        }
+        self.line_started = true;
+        let Some(auto_fields) = &mut self.auto_fields else {
+            return Ok(());
+        };
+        let ts = (auto_fields.clock)();
+        let seq = auto_fields
+            .seq
+            .as_ref()
+            .map(|seq| seq.replace(seq.get() + 1));
+
+        // The automatic fields aren't part of a scope, and are written right away so buffered
+        // attributes don't replace them and sorting doesn't move them
+        self.write_attribute("ts", &ts.to_string())?;
+        if let Some(seq) = seq {
+            self.write_attribute("seq", &seq.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// End the line with a newline and start the next one, which gets its own automatic fields
+    /// and maximum length
+    pub fn finish_line(&mut self) -> Result<&mut Self, EncodeError> {
+        self.end_line()?;
+        self.writer.write_char('\n')?;
+        self.first = true;
+        self.len = 0;
+        self.held.clear();
+        self.held_ends.clear();
+        self.truncated = false;
+        self.line_started = false;
+        self.line_keys.clear();
+        Ok(self)
+    }
+
+    /// Write a string attribute, like [`Encoder::push`]
+    pub fn str(&mut self, key: &str, value: &str) -> Result<&mut Self, EncodeError> {
+        self.push(key, value)
+    }
+
+    /// Write an integer attribute, like [`Encoder::push`]
+    pub fn int(&mut self, key: &str, value: i64) -> Result<&mut Self, EncodeError> {
+        self.push_display(key, &value)
+    }
+
+    /// Write the attributes pushed by the closure with keys prefixed by the prefix and the scope
+    /// separator, like `http.method`. Scopes can be nested.
+    pub fn scope(
+        &mut self,
+        prefix: &str,
+        scope: impl FnOnce(&mut Self) -> Result<&mut Self, EncodeError>,
+    ) -> Result<&mut Self, EncodeError> {
+        let len = self.prefix.len();
+        self.prefix.push_str(prefix);
+        self.prefix.push(self.scope_separator);
+        let result = scope(self).map(|_| ());
+        self.prefix.truncate(len);
+        result.map(|()| self)
+    }
+
+    /// Writes an attribute, if it fits within the maximum length
+    fn push_limited(
+        &mut self,
+        key: &str,
+        value: &str,
+        max_len: usize,
+    ) -> Result<&mut Self, EncodeError> {
+        if self.truncated {
+            return Ok(self);
+        }
+        let attribute = AttributeBuffer::new(key, value, self.first)?;
+        let attribute = attribute.as_str();
+
+        let end = self.len + self.held.len() + attribute.len();
+        if end > max_len {
+            self.truncated = true;
+        } else if self.held.is_empty() && end + 1 + TRUNCATION_MARKER.len() <= max_len {
+            // The attribute leaves room for the marker, so it's never dropped
+            self.writer.write_str(attribute)?;
+            self.len = end;
+            self.first = false;
+            self.mark_written(key);
+        } else {
+            self.held.push_str(attribute);
+            self.held_ends.push(self.held.len());
+            self.first = false;
+            self.mark_written(key);
+        }
+        Ok(self)
+    }
+
+    /// Write the automatic fields of an empty line, the buffered attributes, the held
+    /// attributes, and the truncation marker, returning the writer.
+    /// Errors writing them are ignored, so the line may be incomplete; use
+    /// [`Encoder::try_finish`] for writers that can fail.
+    pub fn finish(mut self) -> W {
+        let _ = self.end_line();
+        self.writer
+    }
+
+    /// Write the buffered attributes, the held attributes, and the truncation marker, returning
+    /// the writer
+    pub fn try_finish(mut self) -> Result<W, EncodeError> {
+        self.end_line()?;
+        Ok(self.writer)
+    }
+
+    /// Writes the automatic fields of an empty line, the buffered attributes, the held
+    /// attributes, and the truncation marker
+    fn end_line(&mut self) -> Result<(), EncodeError> {
+        self.start_line()?;
+        if let Some(mut buffer) = self.buffer.take() {
+            let result = self.write_buffer(&mut buffer);
+            buffer.message = None;
+            buffer.attributes.clear();
+            self.buffer = Some(buffer);
+            result?;
+        }
+        if let Some(context) = self.context.clone() {
+            self.write_context(&context)?;
+        }
+        Ok(self.write_held()?)
+    }
+
+    /// Writes the context attributes with keys that weren't written on the line
+    fn write_context(&mut self, context: &[(String, String)]) -> Result<(), EncodeError> {
+        for (key, value) in context {
+            if !self
+                .line_keys
+                .iter()
+                .any(|written| keys_match(written, key))
+            {
+                validate_new_key(key)?;
+                self.write_attribute(key, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered message and attributes
+    fn write_buffer(&mut self, buffer: &mut Buffer) -> Result<(), EncodeError> {
+        if let Some(message) = &buffer.message {
+            self.write_attribute("msg", message)?;
+        }
+        if self.sort_keys {
+            buffer
+                .attributes
+                .sort_by(|(a, _), (b, _)| unquote(a).cmp(unquote(b)));
+        }
+        for (key, value) in &buffer.attributes {
+            self.write_attribute(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the held attributes and the truncation marker, dropping held attributes to make
+    /// room for it
+    fn write_held(&mut self) -> fmt::Result {
+        let Some(max_len) = self.max_len.filter(|_| self.truncated) else {
+            return self.writer.write_str(&self.held);
+        };
+        if TRUNCATION_MARKER.len() > max_len {
+            return self.writer.write_str(&self.held);
+        }
+        let marked_len = |held: usize| {
+            let separator = usize::from(self.len + held > 0);
+            self.len + held + separator + TRUNCATION_MARKER.len()
+        };
+        let mut held = self.held.len();
+        while marked_len(held) > max_len {
+            self.held_ends.pop();
+            held = self.held_ends.last().copied().unwrap_or(0);
+        }
+        self.writer.write_str(&self.held[..held])?;
+        if self.len + held > 0 {
+            self.writer.write_char(' ')?;
+        }
+        self.writer.write_str(TRUNCATION_MARKER)
+    }
+}
+
+/// Writes the value, quoting and escaping it if it can't be parsed back unquoted
+pub(crate) fn write_value<W: Write + ?Sized>(f: &mut W, value: &str) -> fmt::Result {
+    if value_needs_quotes(value) {
+        write_with_quotes(f, value)
+    } else {
+        f.write_str(value)
+    }
+}
+
+/// Returns the decoded string as it's written in logfmt, like [`write_value`], as logs store
+/// keys and values as written.
+/// Only allocates if the string has to be quoted.
+pub(crate) fn encode_value(value: Cow<'_, str>) -> Cow<'_, str> {
+    if value_needs_quotes(&value) {
+        let mut quoted = String::with_capacity(value.len() + 2);
+        write_with_quotes(&mut quoted, &value).expect("writing to a string can't fail");
+        Cow::Owned(quoted)
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, rc::Rc, string::String, vec::Vec};
+    use core::cell::Cell;
+
+    use crate::{DuplicatePolicy, ErrorKind, ListStyle, Log, ParseOptions};
+
+    use super::{EncodeError, Encoder, EncoderFactory};
+
+    #[test]
+    fn round_trip() {
+        let pairs = [
+            ("plain", "value"),
+            ("spaces", "with spaces"),
+            ("quotes", "say \"hi\""),
+            ("equals", "a=b"),
+            ("lines", "line\nnext\r\tend"),
+            ("path", "C:\\temp"),
+            ("empty", ""),
+        ];
+        let mut encoder = Encoder::new(String::new());
+        for (key, value) in pairs {
+            encoder.push(key, value).unwrap();
+        }
+        let line = encoder.finish();
+        assert_eq!(
+            line,
+            r#"plain=value spaces="with spaces" quotes="say \"hi\"" equals="a=b" lines="line\nnext\r\tend" path="C:\\temp" empty="#
+        );
+
+        let log = Log::parse(&line).unwrap();
+        assert_eq!(log.attribute_count(), pairs.len());
+        for (key, value) in pairs {
+            assert_eq!(log.get_unescaped(key).unwrap().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn push_display() {
+        let mut encoder = Encoder::new(String::new());
+        encoder
+            .push_display("count", &3)
+            .unwrap()
+            .push_display("ratio", &0.5)
+            .unwrap();
+        assert_eq!(encoder.finish(), "count=3 ratio=0.5");
+    }
+
+    #[test]
+    fn max_len() {
+        let encode = |max_len, pairs: &[(&str, &str)]| {
+            let mut encoder = Encoder::new(String::new()).with_max_len(max_len);
+            for (key, value) in pairs {
+                encoder.push(key, value).unwrap();
+            }
+            let truncated = encoder.truncated();
+            (encoder.finish(), truncated)
+        };
+        let pairs = [("a", "1"), ("b", "2"), ("c", "3")];
+
+        // Lines of exactly the maximum length aren't truncated
+        assert_eq!(encode(11, &pairs), ("a=1 b=2 c=3".into(), false));
+
+        // One byte less drops the last attribute, without a marker if it doesn't fit on its own
+        assert_eq!(encode(10, &pairs), ("a=1 b=2".into(), true));
+
+        // Attributes are dropped to make room for the marker
+        let long = [("a", "1"), ("b", "2"), ("c", "value"), ("d", "0123456789")];
+        assert_eq!(encode(22, &long[..3]), ("a=1 b=2 c=value".into(), false));
+        assert_eq!(encode(22, &long), ("a=1 b=2 truncated=true".into(), true));
+        assert_eq!(encode(21, &long), ("a=1 truncated=true".into(), true));
+
+        // Later attributes are dropped even if they'd fit
+        let pairs = [("a", "1"), ("big", "0123456789abcdef"), ("c", "3")];
+        assert_eq!(encode(20, &pairs), ("a=1 truncated=true".into(), true));
+
+        // A value larger than the whole budget, and budgets too small for the marker
+        assert_eq!(
+            encode(16, &[("big", "0123456789abcdef")]),
+            ("truncated=true".into(), true)
+        );
+        assert_eq!(encode(5, &pairs), ("a=1".into(), true));
+    }
+
+    #[test]
+    fn scopes() {
+        let mut encoder = Encoder::new(String::new());
+        encoder
+            .str("msg", "done")
+            .unwrap()
+            .scope("http", |e| {
+                e.str("method", "GET")?;
+                e.scope("tls", |e| e.str("version", "1.3"))?;
+                e.int("status", 200)
+            })
+            .unwrap()
+            .int("bytes", 512)
+            .unwrap();
+        assert_eq!(
+            encoder.finish(),
+            "msg=done http.method=GET http.tls.version=1.3 http.status=200 bytes=512"
+        );
+
+        let mut encoder = Encoder::new(String::new()).with_scope_separator('_');
+        encoder.scope("db", |e| e.int("rows", 3)).unwrap();
+        assert_eq!(encoder.finish(), "db_rows=3");
+
+        // The joined key is validated, and the scope ends on errors
+        let mut encoder = Encoder::new(String::new());
+        let prefix = "a".repeat(40);
+        let Err(EncodeError::InvalidKey(error)) =
+            encoder.scope(&prefix, |e| e.str("0123456789", "value"))
+        else {
+            panic!("joined keys longer than the limit should be rejected");
+        };
+        assert_eq!(error.kind(), ErrorKind::KeyTooLong);
+        assert_eq!(error.key(), format!("{prefix}.0123456789"));
+        encoder.str("after", "1").unwrap();
+        assert_eq!(encoder.finish(), "after=1");
+    }
+
+    #[test]
+    fn auto_fields() {
+        let now = Rc::new(Cell::new(1_700_000_000));
+        let clock = {
+            let now = Rc::clone(&now);
+            move || now.get()
+        };
+        let mut encoder = Encoder::new(String::new()).with_auto_fields(clock, true);
+        encoder
+            .scope("http", |e| e.str("method", "GET"))
+            .unwrap()
+            .finish_line()
+            .unwrap();
+        now.set(1_700_000_005);
+        encoder.finish_line().unwrap();
+        now.set(1_700_000_009);
+        encoder.str("msg", "done").unwrap();
+        assert_eq!(
+            encoder.finish(),
+            concat!(
+                "ts=1700000000 seq=0 http.method=GET\n",
+                "ts=1700000005 seq=1\n",
+                "ts=1700000009 seq=2 msg=done"
+            )
+        );
+
+        // The encoders of a factory continue the same sequence
+        let factory = EncoderFactory::new().with_auto_fields(|| 7, true);
+        let mut first = factory.encoder(String::new());
+        first.int("a", 1).unwrap().finish_line().unwrap();
+        let mut second = factory.buffered(String::new());
+        second.int("b", 2).unwrap().finish_line().unwrap();
+        first.int("c", 3).unwrap();
+        assert_eq!(first.finish(), "ts=7 seq=0 a=1\nts=7 seq=2 c=3");
+        assert_eq!(second.finish(), "ts=7 seq=1 b=2\nts=7 seq=3");
+
+        let mut encoder = Encoder::new(String::new()).with_auto_fields(|| 7, false);
+        encoder.int("a", 1).unwrap();
+        assert_eq!(encoder.finish(), "ts=7 a=1");
+
+        // Buffered attributes are sorted after the automatic fields and don't replace them
+        let mut encoder = Encoder::buffered(String::new())
+            .with_sorted_keys()
+            .with_auto_fields(|| 7, true);
+        encoder.int("b", 2).unwrap().int("a", 1).unwrap();
+        encoder.message("done").unwrap();
+        assert_eq!(encoder.finish(), "ts=7 seq=0 msg=done a=1 b=2");
+
+        let mut encoder = Encoder::buffered(String::new()).with_auto_fields(|| 7, true);
+        encoder
+            .str("seq", "user")
+            .unwrap()
+            .str("ts", "now")
+            .unwrap();
+        assert_eq!(encoder.finish(), "ts=7 seq=0 seq=user ts=now");
+    }
+
+    #[test]
+    fn buffered() {
+        let mut streaming = Encoder::new(String::new());
+        let mut buffered = Encoder::buffered(String::new());
+        for encoder in [&mut streaming, &mut buffered] {
+            encoder
+                .message("request done")
+                .unwrap()
+                .str("status", "200")
+                .unwrap()
+                .str("path", "/x y")
+                .unwrap()
+                .str("\"status\"", "500")
+                .unwrap();
+        }
+        assert_eq!(
+            streaming.finish(),
+            "msg=\"request done\" status=200 path=\"/x y\" \"status\"=500"
+        );
+        assert_eq!(
+            buffered.finish(),
+            "msg=\"request done\" status=500 path=\"/x y\""
+        );
+
+        // The message is first, even if it's written last
+        let mut encoder = Encoder::buffered(String::new()).with_sorted_keys();
+        encoder
+            .int("zone", 3)
+            .unwrap()
+            .int("attempt", 2)
+            .unwrap()
+            .message("retry")
+            .unwrap()
+            .finish_line()
+            .unwrap()
+            .int("b", 1)
+            .unwrap()
+            .int("a", 2)
+            .unwrap();
+        assert_eq!(encoder.finish(), "msg=retry attempt=2 zone=3\na=2 b=1");
+
+        let mut encoder = Encoder::new(String::new());
+        encoder.int("a", 1).unwrap();
+        assert_eq!(
+            encoder.message("late").err(),
+            Some(EncodeError::MessageNotFirst)
+        );
+    }
+
+    #[test]
+    fn lists() {
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepAll,
+            ..ParseOptions::default()
+        };
+        let tags = ["a", "b,c", "d\\e f"];
+        for (style, expected) in [
+            (ListStyle::Repeat, r#"tag=a tag=b,c tag="d\\e f" empty=0"#),
+            (
+                ListStyle::Delimited(','),
+                r#"tag="a,b\\,c,d\\\\e f" empty= empty=0"#,
+            ),
+        ] {
+            let mut encoder = Encoder::new(String::new());
+            encoder
+                .list("tag", tags, style)
+                .unwrap()
+                .list("empty", [""; 0], style)
+                .unwrap()
+                .int("empty", 0)
+                .unwrap();
+            let line = encoder.finish();
+            assert_eq!(line, expected);
+
+            let log = Log::parse_with(&line, &options).unwrap();
+            assert_eq!(log.get_list("tag", style).unwrap(), tags);
+            assert_eq!(log.get_list("missing", style), None);
+        }
+
+        let log = Log::parse("tags=\"\" ids=1;2;3").unwrap();
+        let delimited = ListStyle::Delimited(';');
+        assert_eq!(log.get_list("tags", delimited), Some(Vec::new()));
+        assert_eq!(log.get_list("ids", delimited).unwrap(), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn context() {
+        let factory = EncoderFactory::new()
+            .context("device", "abc123")
+            .context("fw", "1.4.2");
+        let mut first = factory.encoder(String::new());
+        first.push("temp", "21").unwrap();
+        let mut second = factory.encoder(String::new());
+        second.push("humidity", "40").unwrap();
+        assert_eq!(first.finish(), "temp=21 device=abc123 fw=1.4.2");
+        assert_eq!(second.finish(), "humidity=40 device=abc123 fw=1.4.2");
+
+        // Values of the line override the context, on every line
+        let mut encoder = factory.encoder(String::new());
+        encoder.push("fw", "2.0").unwrap().finish_line().unwrap();
+        encoder.push("a", "1").unwrap();
+        assert_eq!(
+            encoder.finish(),
+            "fw=2.0 device=abc123\na=1 device=abc123 fw=1.4.2"
+        );
+        let mut encoder = factory.buffered(String::new());
+        encoder.push("\"device\"", "def").unwrap();
+        assert_eq!(encoder.finish(), "\"device\"=def fw=1.4.2");
+    }
+
+    #[test]
+    fn errors() {
+        let mut encoder = Encoder::new(String::new()).with_max_value_len(5);
+        assert_eq!(
+            encoder.scope("db", |e| e.str("query", "select 1")).err(),
+            Some(EncodeError::ValueTooLong {
+                key: "db.query".into()
+            })
+        );
+        let Err(EncodeError::InvalidKey(error)) = encoder.push("user@host", "1") else {
+            panic!("invalid keys should be rejected");
+        };
+        assert_eq!(
+            (error.key(), error.kind()),
+            ("user@host", ErrorKind::InvalidKey)
+        );
+        encoder.push("short", "12345").unwrap();
+        assert_eq!(encoder.finish(), "short=12345");
+
+        // Attributes that don't fit in the writer aren't written partially
+        let mut encoder = Encoder::new(heapless::String::<12>::new());
+        encoder.push("a", "1").unwrap();
+        assert_eq!(
+            encoder.push("key", "long value").err(),
+            Some(EncodeError::Write(core::fmt::Error))
+        );
+        encoder.push("b", "2").unwrap();
+        assert_eq!(encoder.finish(), "a=1 b=2");
+
+        // Errors writing the held attributes are returned by `try_finish`, and ignored by
+        // `finish`
+        let encoder = || {
+            let mut encoder = Encoder::new(heapless::String::<12>::new()).with_max_len(20);
+            encoder.push("key", "long value").unwrap();
+            encoder
+        };
+        assert_eq!(
+            encoder().try_finish().err(),
+            Some(EncodeError::Write(core::fmt::Error))
+        );
+        assert_eq!(encoder().finish(), "");
+    }
+
+    #[test]
+    fn invalid_key() {
+        let mut encoder = Encoder::new(String::new());
+        let Err(EncodeError::InvalidKey(error)) = encoder.push("", "value") else {
+            panic!("empty keys should be rejected");
+        };
+        assert_eq!(error.kind(), ErrorKind::EmptyKey);
+        assert!(matches!(
+            encoder.push("with space", "value"),
+            Err(EncodeError::InvalidKey(_))
+        ));
+        assert_eq!(encoder.finish(), "");
+
+        let mut encoder = Encoder::new(heapless::String::<4>::new());
+        assert_eq!(
+            encoder.push("key", "value").err(),
+            Some(EncodeError::Write(core::fmt::Error))
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Errors returned when parsing and validating log messages
+
+use alloc::string::String;
+use core::fmt::{self, Display};
+
+pub use crate::{EncodeError, TemplateError, TemplateErrorKind, batch::HeaderError};
+
+/// An error returned when an open string is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct UnclosedString;
+
+impl Display for UnclosedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unclosed string")
+    }
+}
+
+impl core::error::Error for UnclosedString {}
+
+/// The reason parsing a log message failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum ErrorKind {
+    /// A string wasn't closed before the end of the message
+    UnclosedString,
+
+    /// A key is empty
+    EmptyKey,
+
+    /// A key contains a character that isn't allowed in keys
+    InvalidKey,
+
+    /// A value is empty while empty values aren't allowed
+    EmptyValue,
+
+    /// An unquoted value contains `=` while that isn't allowed
+    EqualsInValue,
+
+    /// A key or value has a quote that isn't part of a pair surrounding it
+    UnbalancedQuote,
+
+    /// A key is longer than the configured limit
+    KeyTooLong,
+
+    /// A value is longer than the configured limit
+    ValueTooLong,
+
+    /// The message has more attributes than the configured limit
+    TooManyAttributes,
+
+    /// The input is longer than the configured limit
+    InputTooLong,
+
+    /// A quoted value contains an invalid escape sequence
+    InvalidEscape,
+
+    /// A value contains a control character while those are rejected
+    ControlCharacter,
+
+    /// A streamed line is longer than the configured limit
+    LineTooLong,
+
+    /// The input isn't valid UTF-8
+    InvalidUtf8,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnclosedString => "unclosed string",
+            Self::EmptyKey => "empty key",
+            Self::InvalidKey => "invalid character in key",
+            Self::EmptyValue => "empty value",
+            Self::EqualsInValue => "unquoted `=` in value",
+            Self::UnbalancedQuote => "unbalanced quote",
+            Self::KeyTooLong => "key too long",
+            Self::ValueTooLong => "value too long",
+            Self::TooManyAttributes => "too many attributes",
+            Self::InputTooLong => "input too long",
+            Self::InvalidEscape => "invalid escape sequence",
+            Self::ControlCharacter => "control character in value",
+            Self::LineTooLong => "line too long",
+            Self::InvalidUtf8 => "invalid UTF-8",
+        })
+    }
+}
+
+impl ErrorKind {
+    /// Return a stable identifier of the reason, like `err.unclosed_string`, to look up
+    /// localized messages with
+    pub const fn message_id(self) -> &'static str {
+        match self {
+            Self::UnclosedString => "err.unclosed_string",
+            Self::EmptyKey => "err.empty_key",
+            Self::InvalidKey => "err.invalid_key",
+            Self::EmptyValue => "err.empty_value",
+            Self::EqualsInValue => "err.equals_in_value",
+            Self::UnbalancedQuote => "err.unbalanced_quote",
+            Self::KeyTooLong => "err.key_too_long",
+            Self::ValueTooLong => "err.value_too_long",
+            Self::TooManyAttributes => "err.too_many_attributes",
+            Self::InputTooLong => "err.input_too_long",
+            Self::InvalidEscape => "err.invalid_escape",
+            Self::ControlCharacter => "err.control_character",
+            Self::LineTooLong => "err.line_too_long",
+            Self::InvalidUtf8 => "err.invalid_utf8",
+        }
+    }
+}
+
+/// Writes parse errors as text, like in the language of the user
+pub trait MessageRenderer {
+    /// Writes the error, usually looking up the text by [`ErrorKind::message_id`]
+    fn render(&self, error: &ParseError, f: &mut dyn fmt::Write) -> fmt::Result;
+}
+
+/// An error returned when parsing a log message fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct ParseError {
+    pub(crate) kind: ErrorKind,
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+}
+
+impl ParseError {
+    /// Return the reason parsing failed
+    pub const fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Return the byte offset of the offending token
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Return the offending token in the input that failed to parse
+    pub fn token<'input>(&self, input: &'input str) -> Option<&'input str> {
+        input.get(self.offset..self.offset + self.len)
+    }
+
+    /// Return the key of the offending token in the input that failed to parse, if it has one
+    pub fn key<'input>(&self, input: &'input str) -> Option<&'input str> {
+        self.token(input)
+            .and_then(|token| token.split_once('='))
+            .map(|(key, _)| key.trim())
+    }
+
+    /// Returns the error displayed by the renderer, instead of in English
+    pub fn display_with<'error>(
+        &'error self,
+        renderer: &'error dyn MessageRenderer,
+    ) -> impl Display + 'error {
+        struct Rendered<'error>(&'error ParseError, &'error dyn MessageRenderer);
+
+        impl Display for Rendered<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.1.render(self.0, f)
+            }
+        }
+
+        Rendered(self, renderer)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// An error returned when building a log message with an invalid key
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidKey {
+    pub(crate) key: String,
+    pub(crate) kind: ErrorKind,
+}
+
+impl InvalidKey {
+    /// Return the invalid key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Return the reason the key is invalid
+    pub const fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl Display for InvalidKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid key {:?}: {}", self.key, self.kind)
+    }
+}
+
+impl core::error::Error for InvalidKey {}
+
+/// An error returned when a log message doesn't fit in a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct BufferTooSmall {
+    pub(crate) required: usize,
+}
+
+impl BufferTooSmall {
+    /// Return the number of bytes needed to write the log message
+    pub const fn required(&self) -> usize {
+        self.required
+    }
+}
+
+impl Display for BufferTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "buffer too small, {} bytes required", self.required)
+    }
+}
+
+impl core::error::Error for BufferTooSmall {}
+
+/// The reason parsing a JSON log message failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum JsonErrorKind {
+    /// The input ended before the object was closed
+    UnexpectedEnd,
+
+    /// A character was found where it isn't allowed
+    UnexpectedCharacter,
+
+    /// A value is an object or array, while only flat objects are supported
+    NestedValue,
+
+    /// A string contains an invalid escape sequence
+    InvalidEscape,
+
+    /// A number doesn't follow the JSON number syntax
+    InvalidNumber,
+
+    /// A key isn't allowed as attribute key
+    InvalidKey,
+}
+
+impl Display for JsonErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnexpectedEnd => "unexpected end of input",
+            Self::UnexpectedCharacter => "unexpected character",
+            Self::NestedValue => "nested objects and arrays aren't supported",
+            Self::InvalidEscape => "invalid escape sequence",
+            Self::InvalidNumber => "invalid number",
+            Self::InvalidKey => "invalid key",
+        })
+    }
+}
+
+/// An error returned when parsing a JSON log message fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct JsonError {
+    pub(crate) kind: JsonErrorKind,
+    pub(crate) offset: usize,
+}
+
+impl JsonError {
+    /// Return the reason parsing failed
+    pub const fn kind(&self) -> JsonErrorKind {
+        self.kind
+    }
+
+    /// Return the byte offset where parsing failed
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+impl core::error::Error for JsonError {}
+
+/// An error returned by [`Log::parse_auto`](crate::Log::parse_auto), depending on the detected
+/// format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum AutoParseError {
+    /// Parsing the logfmt message failed
+    Logfmt(UnclosedString),
+
+    /// Parsing the JSON message failed
+    Json(JsonError),
+}
+
+impl Display for AutoParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Logfmt(error) => error.fmt(f),
+            Self::Json(error) => error.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for AutoParseError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Logfmt(error) => Some(error),
+            Self::Json(error) => Some(error),
+        }
+    }
+}
+
+/// The error returned when a value contains an invalid escape sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct UnescapeError {
+    pub(crate) offset: usize,
+}
+
+impl UnescapeError {
+    /// Return the byte offset of the backslash starting the invalid escape sequence
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for UnescapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid escape sequence at byte {}", self.offset)
+    }
+}
+
+impl core::error::Error for UnescapeError {}
+
+/// The error returned when parsing an unknown level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct UnknownLevel;
+
+impl Display for UnknownLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown level")
+    }
+}
+
+impl core::error::Error for UnknownLevel {}
@@ -0,0 +1,103 @@
+//! Sharing equal keys between owned logs, to not copy the same keys for every log of a stream
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{Log, OwnedLog};
+
+/// Stores keys, returning the stored key for keys that were stored already
+pub trait Interner {
+    /// Return the stored key equal to the key, storing the key if it's new
+    fn intern(&mut self, key: &str) -> Arc<str>;
+}
+
+/// An [`Interner`] keeping the keys in a sorted list, searched with a binary search
+#[derive(Debug, Clone, Default)]
+pub struct KeyInterner {
+    keys: Vec<Arc<str>>,
+    hits: usize,
+}
+
+impl KeyInterner {
+    /// Create an interner without keys
+    pub const fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            hits: 0,
+        }
+    }
+
+    /// Return the number of stored keys
+    pub fn unique_keys(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Return the number of keys that were found, instead of stored
+    pub const fn hits(&self) -> usize {
+        self.hits
+    }
+}
+
+impl Interner for KeyInterner {
+    fn intern(&mut self, key: &str) -> Arc<str> {
+        match self.keys.binary_search_by(|stored| (**stored).cmp(key)) {
+            Ok(index) => {
+                self.hits += 1;
+                Arc::clone(&self.keys[index])
+            }
+            Err(index) => {
+                let key = Arc::<str>::from(key);
+                self.keys.insert(index, Arc::clone(&key));
+                key
+            }
+        }
+    }
+}
+
+impl OwnedLog {
+    /// Copy the message and attributes of the log like [`OwnedLog::from`], taking the keys from
+    /// the interner so equal keys share one allocation
+    pub fn from_log_interned(log: &Log<'_>, interner: &mut impl Interner) -> Self {
+        Self {
+            message: log.message().into(),
+            attributes: log
+                .iter()
+                .map(|(key, value)| (interner.intern(key), value.into()))
+                .collect(),
+            message_is_fallback: log.message_is_fallback(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use crate::{Log, OwnedLog};
+
+    use super::{Interner, KeyInterner};
+
+    #[test]
+    fn shares_keys() {
+        let mut interner = KeyInterner::new();
+        let first = Log::parse("level=info path=/ request done").unwrap();
+        let second = Log::parse("path=/users level=warn \"user\"=bob retrying").unwrap();
+        let first = OwnedLog::from_log_interned(&first, &mut interner);
+        let second = OwnedLog::from_log_interned(&second, &mut interner);
+        assert_eq!(
+            first,
+            OwnedLog::parse("level=info path=/ request done").unwrap()
+        );
+        assert_eq!(second.message(), "retrying");
+
+        let [(level, _), (path, _)] = first.attributes() else {
+            panic!("expected two attributes");
+        };
+        assert!(Arc::ptr_eq(level, &second.attributes()[1].0));
+        assert!(Arc::ptr_eq(path, &second.attributes()[0].0));
+
+        // Keys are interned as written, with quotes
+        assert_eq!(&*second.attributes()[2].0, "\"user\"");
+        assert_eq!((interner.unique_keys(), interner.hits()), (3, 2));
+        assert!(Arc::ptr_eq(&interner.intern("path"), path));
+    }
+}
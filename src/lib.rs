@@ -1,240 +1,71 @@
 #![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 #![warn(clippy::cargo)]
 
 extern crate alloc;
-
-use alloc::{
-    borrow::{Cow, ToOwned},
-    string::String,
-};
-use core::fmt::Write as _;
-use heapless::Vec;
-
-/// An error returned when an open string is found
-#[derive(Debug)]
-pub struct UnclosedString;
-
-/// A token in the log message
-enum Token<'message> {
-    Word(&'message str),
-    Attribute(&'message str, &'message str),
-}
-
-impl<'message> Token<'message> {
-    /// Parses the token from a string
-    fn parse(s: &'message str) -> Self {
-        // Split the message in a key and value
-        // Return the message as word if not possible
-        if let Some((key, value)) = s.split_once('=') {
-            // Remove whitespace around the key and value
-            let (key, value) = (key.trim(), value.trim());
-
-            // Calculate the lengths of the key and value
-            let key_length = key.chars().count();
-            let value_length = value.chars().count();
-
-            // Make sure the key and value are valid, otherwise return it as a word
-            if (key_length > 50 && !key.starts_with('"'))
-                || key_length > 52
-                || key
-                    .chars()
-                    .take(key_length.saturating_sub(1))
-                    .skip(1)
-                    .any(|ch| ch == '"')
-                || (key.starts_with('"') && !key.ends_with('"'))
-                || (!key.starts_with('"') && key.ends_with('"'))
-                || key
-                    .chars()
-                    .any(|ch| !ch.is_alphanumeric() && !matches!(ch, '.' | '_' | '-' | '"'))
-                || (value_length > 100 && !value.starts_with('"'))
-                || value_length > 102
-                || value
-                    .chars()
-                    .take(value_length.saturating_sub(1))
-                    .skip(1)
-                    .any(|ch| ch == '"')
-                || (value.starts_with('"') && !value.ends_with('"'))
-                || (!value.starts_with('"') && value.ends_with('"'))
-            {
-                return Self::Word(s);
-            }
-            Token::Attribute(key, value)
-        } else {
-            Token::Word(s)
-        }
-    }
-}
-
-/// Contains the log message
-#[derive(Debug, PartialEq, Eq)]
-pub struct Log<'message> {
-    message: Cow<'message, str>,
-    attributes: Vec<(&'message str, &'message str), 25>,
-}
-
-impl Log<'_> {
-    /// Return the message
-    pub fn message(&self) -> &str {
-        &self.message
-    }
-
-    /// Return the list of attributes
-    pub fn attributes(&self) -> &[(&str, &str)] {
-        &self.attributes
-    }
-}
-
-impl<'message> Log<'message> {
-    /// Parse the log message
-    pub fn parse(s: &'message str) -> Result<Self, UnclosedString> {
-        // Create a list of attributes, an iterator over the string, the message string, and a
-        // variable to store whether the message property was found.
-        let mut attributes = Vec::<(&str, &str), 25>::new();
-        let mut chars = s.char_indices();
-        let mut message = String::new();
-        let mut message_property_found = false;
-
-        // Iterate through the string, parsing every token.
-        loop {
-            // Find the start of the token.
-            // Return the parse result if no token was found.
-            let Some((start, _)) = chars.by_ref().find(|(_, ch)| !ch.is_whitespace()) else {
-                // Store the full string as message, if no message was found.
-                let message = if message.is_empty() {
-                    Cow::Borrowed(s)
-                } else {
-                    Cow::Owned(message)
-                };
-                return Ok(Self {
-                    message,
-                    attributes,
-                });
-            };
-
-            // Find the end of the token
-            let mut in_string = false;
-            let end = chars
-                .by_ref()
-                .find(|(_, c)| {
-                    in_string = (in_string && *c != '"') || (!in_string && *c == '"');
-                    c.is_whitespace() && !in_string
-                })
-                .map_or_else(|| s.len(), |(end, _)| end);
-
-            // Return an error if a string wasn't closed.
-            if in_string {
-                return Err(UnclosedString);
-            }
-
-            // Parse the found token
-            let token = &s[start..end];
-            match Token::parse(token) {
-                // If it's a word, add it to the message as a word
-                Token::Word(word) => {
-                    if !message_property_found {
-                        if !message.is_empty() {
-                            message.push(' ');
-                        }
-                        write!(&mut message, "{word}").unwrap();
-                    }
-                }
-
-                // If it's an attribute
-                Token::Attribute(key, value) => {
-                    // Replace the message if this attribute is the message
-                    if matches!(key, "msg" | "message" | "\"msg\"" | "\"message\"") {
-                        message = value.to_owned();
-                        message_property_found = true;
-                        continue;
-                    }
-
-                    // Check whether the attribute already exists
-                    match attributes
-                        .iter()
-                        .position(|(found_key, _)| &key == found_key)
-                    {
-                        // If it does, change it's value
-                        Some(index) => attributes[index].1 = value,
-                        // Otherwise, try adding it to the attributes
-                        None => {
-                            // if the list is full and no message was found yet, add it to the
-                            // message
-                            if attributes.push((key, value)).is_err() && !message_property_found {
-                                if !message.is_empty() {
-                                    message.push(' ');
-                                }
-                                write!(&mut message, "{token}").unwrap();
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "allocator_api")]
+mod allocator;
+pub mod batch;
+#[cfg(feature = "defmt")]
+mod defmt_impl;
 #[cfg(test)]
-mod tests {
-    use alloc::borrow::ToOwned;
-
-    use crate::Log;
-
-    #[test]
-    fn message_with_attributes() {
-        let result =
-            Log::parse("this is foo=bar duration=10 a value=\"with spaces\" message").unwrap();
-        assert_eq!(result.message(), "this is a message".to_owned());
-        assert_eq!(
-            result.attributes(),
-            [
-                ("foo", "bar"),
-                ("duration", "10"),
-                ("value", "\"with spaces\""),
-            ]
-        );
-    }
-
-    #[test]
-    fn message_with_override() {
-        let result =
-            Log::parse("this is foo=bar a duration=10 message message=\"I am a message\"").unwrap();
-        assert_eq!(result.message(), "\"I am a message\"");
-        assert_eq!(result.attributes(), [("foo", "bar"), ("duration", "10")]);
-    }
-
-    #[test]
-    fn attributes_only() {
-        let message = "foo=bar duration=100";
-        let result = Log::parse("foo=bar duration=100").unwrap();
-        assert_eq!(result.message(), message);
-        assert_eq!(result.attributes(), [("foo", "bar"), ("duration", "100")]);
-    }
-
-    #[test]
-    fn webrequests() {
-        for (request, attributes) in [
-            "baseUrl=\"/\" hostname=localhost protocol=http",
-            "baseUrl=\"/\" hostname=localhost protocol=http name=matthew",
-        ]
-        .into_iter()
-        .zip([
-            [
-                ("baseUrl", "\"/\""),
-                ("hostname", "localhost"),
-                ("protocol", "http"),
-            ]
-            .as_slice(),
-            [
-                ("baseUrl", "\"/\""),
-                ("hostname", "localhost"),
-                ("protocol", "http"),
-                ("name", "matthew"),
-            ]
-            .as_slice(),
-        ]) {
-            let log = Log::parse(request).unwrap();
-            assert_eq!(log.message(), request);
-            assert_eq!(log.attributes(), attributes);
-        }
-    }
-}
+mod differential;
+pub mod encode;
+mod encoder;
+pub mod error;
+mod intern;
+mod literal;
+pub mod log;
+pub mod loki;
+pub mod otel;
+pub mod parse;
+pub mod prelude;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod small_string;
+#[cfg(feature = "std")]
+mod std_impl;
+mod stream;
+mod template;
+mod tokenizer;
+#[cfg(feature = "ufmt")]
+mod ufmt_impl;
+pub mod value;
+
+#[cfg(feature = "allocator_api")]
+pub use allocator::AllocLog;
+pub use encoder::{EncodeError, Encoder, EncoderFactory};
+pub use intern::{Interner, KeyInterner};
+pub use literal::parse_attribute;
+#[doc(hidden)]
+pub use literal::{count_attributes, parse_attributes};
+#[cfg(feature = "std")]
+pub use std_impl::read_logs_std;
+pub use stream::StreamParser;
+pub use template::{Template, TemplateError, TemplateErrorKind};
+
+pub use encode::{
+    ColorScheme, DEFAULT_WRAP_COLUMN, EncodeOptions, EncodeStyle, JsonValues, ListStyle,
+    SyslogOptions,
+};
+use encode::{decode_quoted, is_quoted, join_list, write_json_string, write_with_quotes};
+pub use error::{
+    AutoParseError, BufferTooSmall, ErrorKind, InvalidKey, JsonError, JsonErrorKind,
+    MessageRenderer, ParseError, UnclosedString, UnescapeError, UnknownLevel,
+};
+pub use log::{
+    Attribute, Iter, Keys, LazyLog, Log, LogBuilder, MergePolicy, OwnedLog, TypedIter, Values,
+};
+pub use parse::{
+    AttributeIter, ControlChars, DEFAULT_MAX_ATTRIBUTES, Dialect, DuplicatePolicy, EqualsInValue,
+    FlagWords, KeyFilter, KeyStyle, LineContinuation, LogVisitor, OverlongValue, ParseLines,
+    ParseOptions, Separators, Token, TokenIter, attributes, default_key_validator, parse_lines,
+    parse_visit, tokens, unescape, unquote,
+};
+use parse::{QuoteState, keys_match};
+use small_string::Message;
+pub use value::{BoolStyle, DEFAULT_NULL_VALUES, Level, Number, Timestamp, TypedValue, Value};
+use value::{LEVEL_KEYS, TIMESTAMP_KEYS, parse_duration};
@@ -0,0 +1,291 @@
+//! Parsing literal logfmt lines at compile time, with the rules of [`tokens`](crate::tokens)
+//! for ASCII keys
+
+/// The maximum number of characters in a key, like the default parse options
+const MAX_KEY_LEN: usize = 50;
+
+/// The maximum number of characters in a value, like the default parse options
+const MAX_VALUE_LEN: usize = 100;
+
+/// Returns the character starting at the index of the string and its length in bytes
+const fn char_at(s: &str, index: usize) -> (char, usize) {
+    let bytes = s.as_bytes();
+    let first = bytes[index];
+    let (len, mut code) = match first {
+        0x00..=0x7f => return (first as char, 1),
+        0xc0..=0xdf => (2, first as u32 & 0x1f),
+        0xe0..=0xef => (3, first as u32 & 0x0f),
+        _ => (4, first as u32 & 0x07),
+    };
+    let mut offset = 1;
+    while offset < len {
+        code = (code << 6) | (bytes[index + offset] as u32 & 0x3f);
+        offset += 1;
+    }
+    match char::from_u32(code) {
+        Some(ch) => (ch, len),
+        None => (char::REPLACEMENT_CHARACTER, len),
+    }
+}
+
+/// Returns the part of the string between the byte offsets
+const fn slice(s: &str, start: usize, end: usize) -> &str {
+    s.split_at(end).0.split_at(start).1
+}
+
+/// Returns the start of the next token at or after the index, skipping whitespace
+const fn token_start(s: &str, mut index: usize) -> usize {
+    while index < s.len() {
+        let (ch, len) = char_at(s, index);
+        if !ch.is_whitespace() {
+            break;
+        }
+        index += len;
+    }
+    index
+}
+
+/// Returns the end of the token starting at the index, or `None` if it contains a string that
+/// isn't closed. Escaped quotes don't end a string.
+const fn token_end(s: &str, mut index: usize) -> Option<usize> {
+    let mut in_string = false;
+    let mut escaped = false;
+    while index < s.len() {
+        let (ch, len) = char_at(s, index);
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_string = false;
+            }
+        } else if ch == '"' {
+            in_string = true;
+        } else if ch.is_whitespace() {
+            break;
+        }
+        index += len;
+    }
+    if in_string { None } else { Some(index) }
+}
+
+/// Returns the contents of a key or value without surrounding quotes, or `None` if it contains
+/// quotes that don't surround it
+const fn contents(s: &str) -> Option<&str> {
+    let bytes = s.as_bytes();
+    let quoted = bytes.len() > 1 && bytes[0] == b'"' && bytes[bytes.len() - 1] == b'"';
+    let contents = if quoted { slice(s, 1, s.len() - 1) } else { s };
+    let bytes = contents.as_bytes();
+    let mut index = 0;
+    let mut escaped = false;
+    while index < bytes.len() {
+        if bytes[index] == b'"' && !(quoted && escaped) {
+            return None;
+        }
+        escaped = !escaped && bytes[index] == b'\\';
+        index += 1;
+    }
+    Some(contents)
+}
+
+/// Checks whether the key is valid, only allowing ASCII alphanumeric characters, `.`, `_`, and
+/// `-`
+const fn valid_key(key: &str) -> bool {
+    let Some(contents) = contents(key) else {
+        return false;
+    };
+    let bytes = contents.as_bytes();
+    if bytes.is_empty() || bytes.len() > MAX_KEY_LEN {
+        return false;
+    }
+    let mut index = 0;
+    while index < bytes.len() {
+        if !matches!(bytes[index], b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'.' | b'_' | b'-') {
+            return false;
+        }
+        index += 1;
+    }
+    true
+}
+
+/// Checks whether the key is a message key of the default parse options
+const fn is_message_key(key: &str) -> bool {
+    match contents(key) {
+        Some(contents) => matches!(contents.as_bytes(), b"msg" | b"message"),
+        None => false,
+    }
+}
+
+/// Checks whether the value is valid, only limiting the length of values that aren't messages
+const fn valid_value(value: &str, message: bool) -> bool {
+    let Some(contents) = contents(value) else {
+        return false;
+    };
+
+    // Count the characters, which don't start with a continuation byte
+    let bytes = contents.as_bytes();
+    let mut chars = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        chars += (bytes[index] & 0xc0 != 0x80) as usize;
+        index += 1;
+    }
+    message || chars <= MAX_VALUE_LEN
+}
+
+/// Splits a single token in the key and value of an attribute at compile time, like
+/// [`tokens`](crate::tokens) parses it with surrounding quotes kept.
+/// Returns `None` for words, malformed attributes, input that isn't a single token, and keys
+/// with characters that aren't ASCII.
+pub const fn parse_attribute(s: &str) -> Option<(&str, &str)> {
+    if s.is_empty() || !matches!(token_end(s, 0), Some(end) if end == s.len()) {
+        return None;
+    }
+
+    // The key ends at the first `=`, even within a string
+    let bytes = s.as_bytes();
+    let mut equals = 0;
+    while equals < bytes.len() && bytes[equals] != b'=' {
+        equals += 1;
+    }
+    if equals == bytes.len() {
+        return None;
+    }
+    let key = slice(s, 0, equals);
+    let value = slice(s, equals + 1, s.len());
+    if valid_key(key) && valid_value(value, is_message_key(key)) {
+        Some((key, value))
+    } else {
+        None
+    }
+}
+
+/// Returns the number of attributes in the line, panicking if it contains anything else
+#[doc(hidden)]
+pub const fn count_attributes(line: &str) -> usize {
+    let mut count = 0;
+    let mut start = token_start(line, 0);
+    while start < line.len() {
+        let Some(end) = token_end(line, start) else {
+            panic!("unclosed string in logfmt line");
+        };
+        if parse_attribute(slice(line, start, end)).is_none() {
+            panic!("invalid attribute in logfmt line");
+        }
+        count += 1;
+        start = token_start(line, end);
+    }
+    count
+}
+
+/// Returns the attributes in the line, which has to contain `N` attributes and nothing else
+#[doc(hidden)]
+pub const fn parse_attributes<const N: usize>(line: &str) -> [(&str, &str); N] {
+    let mut attributes = [("", ""); N];
+    let mut index = 0;
+    let mut start = token_start(line, 0);
+    while index < N {
+        let end = match token_end(line, start) {
+            Some(end) => end,
+            None => panic!("unclosed string in logfmt line"),
+        };
+        attributes[index] = match parse_attribute(slice(line, start, end)) {
+            Some(attribute) => attribute,
+            None => panic!("invalid attribute in logfmt line"),
+        };
+        index += 1;
+        start = token_start(line, end);
+    }
+    attributes
+}
+
+/// Parses a literal logfmt line of attributes at compile time, into a
+/// `&'static [(&'static str, &'static str)]` with surrounding quotes kept.
+/// Words, malformed attributes, unclosed strings, and keys with characters that aren't ASCII
+/// are compile errors.
+///
+/// ```
+/// const ROUTES: &[(&str, &str)] = logfmt_nostd::const_logfmt!("at=info path=\"/\" status=200");
+/// assert_eq!(ROUTES, [("at", "info"), ("path", "\"/\""), ("status", "200")]);
+/// ```
+///
+/// ```compile_fail
+/// const WORDS: &[(&str, &str)] = logfmt_nostd::const_logfmt!("request done status=200");
+/// ```
+#[macro_export]
+macro_rules! const_logfmt {
+    ($line:expr) => {{
+        // The names are unusual to not shadow constants used by the line
+        const __LOGFMT_ATTRIBUTES: [(&str, &str); $crate::count_attributes($line)] =
+            $crate::parse_attributes($line);
+        const __LOGFMT_SLICE: &[(&str, &str)] = &__LOGFMT_ATTRIBUTES;
+        __LOGFMT_SLICE
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{Token, attributes, tokens};
+
+    use super::parse_attribute;
+
+    #[test]
+    fn matches_tokens() {
+        let inputs = [
+            "a=1",
+            "key=",
+            "\"quoted\"=value",
+            "k=\"x y\"",
+            "k=\"say \\\"hi\\\"\"",
+            "k=a=b",
+            "k=é€",
+            "k=\"x\"y",
+            "k=x\"y",
+            "\"k=1",
+            "=1",
+            "word",
+            "bad key=1",
+            "bad@key=1",
+            "a=1 b=2",
+            "k=\"open",
+            "k=\"\\\\\"",
+            "",
+        ];
+        let long_key = "k".repeat(51);
+        let long_value = alloc::format!("k={}", "é".repeat(101));
+        let long_message = alloc::format!("\"msg\"={}", "é".repeat(101));
+        let limits = [
+            alloc::format!("{}=1", &long_key[1..]),
+            alloc::format!("{long_key}=1"),
+            alloc::format!("k={}", "é".repeat(100)),
+            long_value,
+            long_message,
+        ];
+        for input in inputs
+            .iter()
+            .copied()
+            .chain(limits.iter().map(AsRef::as_ref))
+        {
+            let runtime: Vec<_> = tokens(input).collect();
+            let expected = match runtime.as_slice() {
+                [Ok(Token::Attribute(key, value))] => Some((*key, *value)),
+                _ => None,
+            };
+            assert_eq!(parse_attribute(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn const_logfmt() {
+        const LINE: &str = "at=info method=GET  path=\"/a b\" msg=\"done\" bytes=";
+        const ATTRIBUTES: &[(&str, &str)] = const_logfmt!(LINE);
+        let runtime: Vec<_> = attributes(LINE).map(Result::unwrap).collect();
+        assert_eq!(ATTRIBUTES, runtime);
+        assert_eq!(const_logfmt!(" \t"), []);
+        const PARSED: Option<(&str, &str)> = parse_attribute("level=warn");
+        assert_eq!(PARSED, Some(("level", "warn")));
+    }
+}
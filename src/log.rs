@@ -0,0 +1,4065 @@
+//! Log messages with their attributes, and the builders and accessors for them
+
+use alloc::{
+    borrow::Cow, collections::BTreeMap, format, rc::Rc, string::String, sync::Arc, vec::Vec,
+};
+use core::{
+    cmp::Ordering,
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    net::{IpAddr, SocketAddr},
+    ops::{Deref, Index, Range},
+    slice,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::encode::{decode_quoted, split_list};
+use crate::parse::{keys_match, validate_key};
+use crate::small_string::Message;
+use crate::value::{
+    LEVEL_KEYS, TIMESTAMP_KEYS, parse_bytes, parse_duration, parse_radix_i64, parse_radix_u64,
+};
+use crate::{
+    BoolStyle, DEFAULT_NULL_VALUES, EncodeError, InvalidKey, Level, ListStyle, Number,
+    ParseOptions, Timestamp, TypedValue, UnclosedString, UnescapeError, Value, unescape, unquote,
+};
+
+/// Which value is kept when merging an attribute whose key is already present
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+    /// Keep the existing value
+    #[default]
+    KeepExisting,
+
+    /// Replace the existing value with the merged one
+    Overwrite,
+}
+
+/// An attribute of a log message, ordered by key and then by value
+#[derive(Clone)]
+pub struct Attribute<'message> {
+    pub(crate) key: Cow<'message, str>,
+    pub(crate) value: Cow<'message, str>,
+    pub(crate) span: Option<(Range<usize>, Range<usize>)>,
+}
+
+impl<'message> Attribute<'message> {
+    /// Create an attribute that wasn't parsed from an input
+    const fn new(key: Cow<'message, str>, value: Cow<'message, str>) -> Self {
+        Self {
+            key,
+            value,
+            span: None,
+        }
+    }
+}
+
+impl Attribute<'_> {
+    /// Return the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Return the value
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Return the byte ranges of the key and value in the input, including quotes.
+    /// Only recorded when parsing with [`ParseOptions::record_spans`], flags have an empty value
+    /// range at the end of the flag.
+    pub fn span(&self) -> Option<(Range<usize>, Range<usize>)> {
+        self.span.clone()
+    }
+
+    /// Copy the key and value into owned storage, if they're borrowed
+    pub fn into_owned(self) -> Attribute<'static> {
+        Attribute {
+            key: Cow::Owned(self.key.into_owned()),
+            value: Cow::Owned(self.value.into_owned()),
+            span: self.span,
+        }
+    }
+}
+
+impl PartialEq for Attribute<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.value == other.value
+    }
+}
+
+impl Eq for Attribute<'_> {}
+
+impl Hash for Attribute<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+        self.value.hash(state);
+    }
+}
+
+impl PartialOrd for Attribute<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Attribute<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.key, &self.value).cmp(&(&other.key, &other.value))
+    }
+}
+
+impl fmt::Debug for Attribute<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.key(), self.value()).fmt(f)
+    }
+}
+
+impl PartialEq<(&str, &str)> for Attribute<'_> {
+    fn eq(&self, (key, value): &(&str, &str)) -> bool {
+        self.key() == *key && self.value() == *value
+    }
+}
+
+/// Contains the log message.
+/// Logs are ordered by message and then by their attributes in input order, so logs with the
+/// same attributes in a different order are different.
+#[derive(Clone, Default)]
+pub struct Log<'message> {
+    pub(crate) message: Message<'message>,
+    pub(crate) attributes: Vec<Attribute<'message>>,
+    pub(crate) message_is_fallback: bool,
+    pub(crate) message_spans: Vec<Range<usize>>,
+    pub(crate) original: Option<Cow<'message, str>>,
+}
+
+impl fmt::Debug for Log<'_> {
+    /// Formats the message and attributes as
+    /// `Log { message: "...", attributes: {"key": "value"} }`, with one attribute per line in
+    /// alternate mode
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Formats the attributes as a map
+        struct Attributes<'log>(&'log Log<'log>);
+
+        impl fmt::Debug for Attributes<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map().entries(self.0.iter()).finish()
+            }
+        }
+
+        f.debug_struct("Log")
+            .field("message", &self.message())
+            .field("attributes", &Attributes(self))
+            .finish()
+    }
+}
+
+impl PartialEq for Log<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.message == other.message && self.attributes == other.attributes
+    }
+}
+
+impl Eq for Log<'_> {}
+
+impl Hash for Log<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.message.hash(state);
+        self.attributes.hash(state);
+    }
+}
+
+impl PartialOrd for Log<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Log<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.message, &self.attributes).cmp(&(&other.message, &other.attributes))
+    }
+}
+
+impl Log<'_> {
+    /// Return the message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Return the log message without surrounding double quotes
+    pub fn message_unquoted(&self) -> &str {
+        unquote(&self.message)
+    }
+
+    /// Checks whether the message is the whole input, because no message was found while parsing
+    pub const fn message_is_fallback(&self) -> bool {
+        self.message_is_fallback
+    }
+
+    /// Return the byte ranges in the input the message was taken from.
+    /// Only recorded when parsing with [`ParseOptions::record_spans`].
+    pub fn message_spans(&self) -> &[Range<usize>] {
+        &self.message_spans
+    }
+
+    /// Return the list of attributes
+    pub fn attributes(&self) -> &[Attribute<'_>] {
+        &self.attributes
+    }
+
+    /// Return an iterator over the spans of the attributes, in the same order as
+    /// [`Log::attributes`]
+    pub fn spans(&self) -> impl ExactSizeIterator<Item = Option<(Range<usize>, Range<usize>)>> {
+        self.attributes.iter().map(Attribute::span)
+    }
+
+    /// Return the key and value of the attribute at the index
+    pub fn nth_attribute(&self, index: usize) -> Option<(&str, &str)> {
+        self.attributes
+            .get(index)
+            .map(|attribute| (attribute.key(), attribute.value()))
+    }
+
+    /// Return the number of attributes
+    pub fn attribute_count(&self) -> usize {
+        self.attributes.len()
+    }
+
+    /// Checks whether the log has neither a message nor attributes
+    pub fn is_empty(&self) -> bool {
+        self.message.is_empty() && self.attributes.is_empty()
+    }
+
+    /// Checks whether the log has any attributes
+    pub fn has_attributes(&self) -> bool {
+        !self.attributes.is_empty()
+    }
+
+    /// Copy the message and attributes into owned storage, without consuming the log
+    pub fn to_owned_log(&self) -> Log<'static> {
+        Log {
+            message: Message::from(self.message()).into_owned(),
+            attributes: self
+                .attributes
+                .iter()
+                .map(|attribute| attribute.clone().into_owned())
+                .collect(),
+            message_is_fallback: self.message_is_fallback,
+            message_spans: self.message_spans.clone(),
+            original: self
+                .original
+                .as_deref()
+                .map(|original| Cow::Owned(original.into())),
+        }
+    }
+
+    /// Return an iterator over the keys and values of the attributes
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            attributes: self.attributes.iter(),
+        }
+    }
+
+    /// Return an iterator over the keys of the attributes
+    pub fn keys(&self) -> Keys<'_> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Return an iterator over the values of the attributes
+    pub fn values(&self) -> Values<'_> {
+        Values { inner: self.iter() }
+    }
+
+    /// Return an iterator over the keys and classified values of the attributes.
+    /// Values are classified while iterating, so parsing doesn't get slower.
+    pub fn typed_iter(&self) -> TypedIter<'_> {
+        TypedIter { inner: self.iter() }
+    }
+
+    /// Return the value of the first attribute with the key, ignoring surrounding quotes
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attribute| keys_match(attribute.key(), key))
+            .map(Attribute::value)
+    }
+
+    /// Return the attributes sorted by key, keeping the order of attributes with the same key
+    pub fn attributes_sorted(&self) -> Vec<(&str, &str)> {
+        let mut attributes = self.iter().collect::<Vec<_>>();
+        attributes.sort_by_key(|(key, _)| *key);
+        attributes
+    }
+
+    /// Collect the attributes into a map sorted by key, keeping the last value of duplicate keys.
+    /// Keys are compared as stored, so quoted and unquoted keys are separate entries.
+    pub fn attributes_map(&self) -> BTreeMap<&str, &str> {
+        self.iter().collect()
+    }
+
+    /// Return an iterator over the values of every attribute with the key in input order,
+    /// ignoring surrounding quotes
+    pub fn get_all<'log>(&'log self, key: &'log str) -> impl Iterator<Item = &'log str> + 'log {
+        self.iter()
+            .filter(move |(attribute_key, _)| keys_match(attribute_key, key))
+            .map(|(_, value)| value)
+    }
+
+    /// Return the items of the list with the key in the style, without surrounding quotes and
+    /// with escape sequences of quoted values decoded. Returns `None` if the attribute doesn't
+    /// exist.
+    /// Repeated keys are only kept when parsing with
+    /// [`DuplicatePolicy::KeepAll`](crate::DuplicatePolicy::KeepAll).
+    pub fn get_list(&self, key: &str, style: ListStyle) -> Option<Vec<Cow<'_, str>>> {
+        match style {
+            ListStyle::Repeat => {
+                let items: Vec<_> = self
+                    .iter()
+                    .filter(|(attribute_key, _)| keys_match(attribute_key, key))
+                    .map(|(_, value)| decode_quoted(value))
+                    .collect();
+                (!items.is_empty()).then_some(items)
+            }
+            ListStyle::Delimited(delimiter) => self
+                .get(key)
+                .map(|value| split_list(&decode_quoted(value), delimiter)),
+        }
+    }
+
+    /// Return an iterator over the attributes whose unquoted key is the prefix or starts with the
+    /// prefix followed by `.`, with the prefix and `.` removed from the key
+    pub fn with_prefix<'log>(
+        &'log self,
+        prefix: &'log str,
+    ) -> impl Iterator<Item = (&'log str, &'log str)> + 'log {
+        self.iter().filter_map(move |(key, value)| {
+            let rest = unquote(key).strip_prefix(prefix)?;
+            match rest.strip_prefix('.') {
+                Some(rest) => Some((rest, value)),
+                None => rest.is_empty().then_some((rest, value)),
+            }
+        })
+    }
+
+    /// Return the distinct parts before the first `.` of unquoted keys, in input order.
+    /// Keys without a `.` don't have a prefix.
+    pub fn prefixes(&self) -> Vec<&str> {
+        let mut prefixes = Vec::new();
+        for (key, _) in self {
+            if let Some((prefix, _)) = unquote(key).split_once('.')
+                && !prefixes.contains(&prefix)
+            {
+                prefixes.push(prefix);
+            }
+        }
+        prefixes
+    }
+
+    /// Return an iterator over the attributes for which the predicate returns `true`
+    pub fn filtered<'log, P: FnMut(&str, &str) -> bool + 'log>(
+        &'log self,
+        mut predicate: P,
+    ) -> impl Iterator<Item = (&'log str, &'log str)> + 'log {
+        self.iter()
+            .filter(move |(key, value)| predicate(key, value))
+    }
+
+    /// Return the value of the first attribute with the key, or `Some(None)` if the value is one
+    /// of the [`DEFAULT_NULL_VALUES`]. Returns `None` if the attribute doesn't exist.
+    pub fn get_opt(&self, key: &str) -> Option<Option<&str>> {
+        self.get_opt_with(key, DEFAULT_NULL_VALUES)
+    }
+
+    /// Return the value of the first attribute with the key, or `Some(None)` if the value is one
+    /// of the null values. Quoted values are never null, unless the null values include the
+    /// quotes.
+    pub fn get_opt_with(&self, key: &str, null_values: &[&str]) -> Option<Option<&str>> {
+        self.get(key)
+            .map(|value| Some(value).filter(|value| !null_values.contains(value)))
+    }
+
+    /// Return the value of the first attribute with the key without surrounding quotes and with
+    /// escape sequences decoded, like [`unescape`]
+    pub fn get_unescaped(&self, key: &str) -> Option<Result<Cow<'_, str>, UnescapeError>> {
+        self.get(key).map(unescape)
+    }
+
+    /// Decode the value of the first attribute with the key with the decoder, after removing
+    /// surrounding quotes and decoding escape sequences like [`Log::get_unescaped`].
+    /// Returns `None` if the attribute doesn't exist, the value contains an invalid escape
+    /// sequence, or the decoder rejects the value.
+    pub fn get_with<T>(&self, key: &str, decoder: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+        decoder(&self.get_unescaped(key)?.ok()?)
+    }
+
+    /// Decode every attribute with the decoder, collecting the results in input order.
+    /// The decoder receives the unquoted key and the value like [`Log::get_with`].
+    /// Returns `None` if any value contains an invalid escape sequence or is rejected.
+    pub fn decode_all_with<T, C: FromIterator<T>>(
+        &self,
+        mut decoder: impl FnMut(&str, &str) -> Option<T>,
+    ) -> Option<C> {
+        self.iter()
+            .map(|(key, value)| decoder(unquote(key), &unescape(value).ok()?))
+            .collect()
+    }
+
+    /// Return the value of the first attribute with the key, without surrounding double quotes
+    pub fn get_unquoted(&self, key: &str) -> Option<&str> {
+        self.get(key).map(unquote)
+    }
+
+    /// Checks whether an attribute with the key exists, ignoring surrounding quotes
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Return the level from the first of the `level`, `lvl`, and `severity` attributes.
+    /// Returns `None` if none of them exist or the level isn't known.
+    pub fn level(&self) -> Option<Level> {
+        LEVEL_KEYS
+            .into_iter()
+            .find_map(|key| self.get(key))
+            .and_then(|value| Level::parse(unquote(value)))
+    }
+
+    /// Checks whether the level of the log is at least as severe as the given level.
+    /// Logs without a known level are never at least the level.
+    pub fn at_least(&self, level: Level) -> bool {
+        self.level().is_some_and(|own| own >= level)
+    }
+
+    /// Return the value of the first of the `err` and `error` attributes, without surrounding
+    /// quotes
+    pub fn error(&self) -> Option<&str> {
+        ["err", "error"]
+            .into_iter()
+            .find_map(|key| self.get(key))
+            .map(unquote)
+    }
+
+    /// Checks whether the log reports an error, either through an [`Log::error`] value other
+    /// than an empty string, `nil`, or `null`, or through an `Error` or `Fatal` [`Log::level`]
+    pub fn is_error(&self) -> bool {
+        self.error().is_some_and(|error| {
+            !(error.is_empty()
+                || error.eq_ignore_ascii_case("nil")
+                || error.eq_ignore_ascii_case("null"))
+        }) || matches!(self.level(), Some(Level::Error | Level::Fatal))
+    }
+
+    /// Return the value of the first of the `ts`, `time`, and `timestamp` attributes, without
+    /// surrounding quotes
+    pub fn timestamp(&self) -> Option<&str> {
+        TIMESTAMP_KEYS
+            .into_iter()
+            .find_map(|key| self.get(key))
+            .map(unquote)
+    }
+
+    /// Parse the timestamp returned by [`Log::timestamp`], like [`Log::get_timestamp`]
+    pub fn timestamp_parsed(&self) -> Option<Timestamp> {
+        self.timestamp().and_then(Timestamp::parse)
+    }
+
+    /// Parse the timestamp of the first of the `ts`, `time`, and `timestamp` attributes, like
+    /// [`Log::timestamp_parsed`]
+    pub fn timestamp_value(&self) -> Option<Timestamp> {
+        self.timestamp_parsed()
+    }
+
+    /// Parse the value of the first attribute with the key as an RFC 3339 timestamp or integer
+    /// number of seconds, milliseconds, microseconds, or nanoseconds since the Unix epoch, without
+    /// surrounding quotes.
+    /// Integers of up to 11 digits are seconds, 12 to 14 digits are milliseconds, 15 to 17
+    /// digits are microseconds, and 18 or 19 digits are nanoseconds.
+    pub fn get_timestamp(&self, key: &str) -> Option<Timestamp> {
+        self.get_unquoted(key).and_then(Timestamp::parse)
+    }
+
+    /// Parse the value of the first attribute with the key as signed integer, without surrounding
+    /// quotes
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get_parsed(key)?.ok()
+    }
+
+    /// Parse the value of the first attribute with the key as unsigned integer, without
+    /// surrounding quotes
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get_parsed(key)?.ok()
+    }
+
+    /// Parse the value of the first attribute with the key as signed integer, like
+    /// [`Log::get_i64`], also accepting `0x`, `0o`, and `0b` prefixes after the sign
+    pub fn get_i64_radix(&self, key: &str) -> Option<i64> {
+        self.get_unquoted(key).and_then(parse_radix_i64)
+    }
+
+    /// Parse the value of the first attribute with the key as unsigned integer, like
+    /// [`Log::get_u64`], also accepting `0x`, `0o`, and `0b` prefixes
+    pub fn get_u64_radix(&self, key: &str) -> Option<u64> {
+        self.get_unquoted(key)
+            .and_then(|value| parse_radix_u64(value.strip_prefix('+').unwrap_or(value)))
+    }
+
+    /// Parse the value of the first attribute with the key as floating point number, without
+    /// surrounding quotes
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get_parsed(key)?.ok()
+    }
+
+    /// Parse the value of the first attribute with the key as number, without surrounding quotes.
+    /// Integers are `Int` if they fit in an `i64`, otherwise `UInt` if they fit in a `u64`, and
+    /// `Float` if they don't. Values in scientific notation like `1e9` are `Float`, infinite
+    /// values and NaN return `None`.
+    pub fn get_number(&self, key: &str) -> Option<Number> {
+        self.get_unquoted(key).and_then(Number::parse)
+    }
+
+    /// Parse the value of the first attribute with the key as boolean, without surrounding
+    /// quotes. Accepts `true` and `false` ignoring case, and `1` and `0`.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get_bool_with(key, BoolStyle::Strict)
+    }
+
+    /// Parse the value of the first attribute with the key as boolean in the given style,
+    /// without surrounding quotes and ignoring case
+    pub fn get_bool_with(&self, key: &str, style: BoolStyle) -> Option<bool> {
+        self.get_unquoted(key).and_then(|value| style.parse(value))
+    }
+
+    /// Parse the value of the first attribute with the key as fraction, without surrounding
+    /// quotes. Percentages like `87%` are divided by 100 and may have whitespace before the `%`,
+    /// numbers without `%` are only accepted as ratios from 0 to 1 to avoid ambiguity.
+    pub fn get_percent(&self, key: &str) -> Option<f32> {
+        let value = self.get_unquoted(key)?;
+        let fraction = match value.strip_suffix('%') {
+            Some(percentage) => percentage.trim_end().parse::<f32>().ok()? / 100.0,
+            None => value
+                .parse::<f32>()
+                .ok()
+                .filter(|ratio| (0.0..=1.0).contains(ratio))?,
+        };
+        fraction.is_finite().then_some(fraction)
+    }
+
+    /// Parse the value of the first attribute with the key as IP address, without surrounding
+    /// quotes. IPv6 addresses may be surrounded by brackets, and the IP address of a socket
+    /// address is returned.
+    pub fn get_ip(&self, key: &str) -> Option<IpAddr> {
+        let value = self.get_unquoted(key)?;
+        value
+            .parse()
+            .ok()
+            .or_else(|| {
+                value
+                    .strip_prefix('[')
+                    .and_then(|value| value.strip_suffix(']'))
+                    .and_then(|value| value.parse().ok())
+                    .map(IpAddr::V6)
+            })
+            .or_else(|| value.parse().ok().map(|address: SocketAddr| address.ip()))
+    }
+
+    /// Parse the value of the first attribute with the key as socket address like
+    /// `10.1.2.3:443` or `[::1]:8080`, without surrounding quotes
+    pub fn get_socket_addr(&self, key: &str) -> Option<SocketAddr> {
+        self.get_parsed(key)?.ok()
+    }
+
+    /// Parse the value of the first attribute with the key as Go duration like `1h2m3.5s`,
+    /// without surrounding quotes
+    pub fn get_duration(&self, key: &str) -> Option<Duration> {
+        self.get_unquoted(key).and_then(parse_duration)
+    }
+
+    /// Parse the value of the first attribute with the key as number of bytes like `10KB` or
+    /// `1.5MiB`, without surrounding quotes.
+    /// Fractions of a byte are truncated and sizes that don't fit in a `u64` saturate.
+    pub fn get_bytes(&self, key: &str) -> Option<u64> {
+        self.get_unquoted(key).and_then(parse_bytes)
+    }
+
+    /// Return the classified value of the first attribute with the key
+    pub fn get_typed(&self, key: &str) -> Option<TypedValue<'_>> {
+        self.get(key).map(TypedValue::new)
+    }
+
+    /// Return an iterator over the keys and classified values of the attributes
+    pub fn typed_attributes(&self) -> impl Iterator<Item = (&str, TypedValue<'_>)> {
+        self.iter()
+            .map(|(key, value)| (key, TypedValue::new(value)))
+    }
+
+    /// Parse the value of the first attribute with the key, without surrounding quotes.
+    /// Returns `None` if the attribute doesn't exist.
+    pub fn get_parsed<T: FromStr>(&self, key: &str) -> Option<Result<T, T::Err>> {
+        self.get(key).map(|value| unquote(value).parse())
+    }
+}
+
+impl FromStr for Log<'static> {
+    type Err = UnclosedString;
+
+    /// Parses the log message into owned storage, like [`Log::parse`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Log::parse(s).map(Log::into_owned)
+    }
+}
+
+/// Compares the message and the attributes in order.
+///
+/// ```
+/// use logfmt_nostd::Log;
+///
+/// let log = Log::parse("this is foo=bar a message").unwrap();
+/// assert_eq!(log, ("this is a message", [("foo", "bar")].as_slice()));
+/// assert_eq!(("this is a message", [("foo", "bar")].as_slice()), log);
+/// assert_ne!(log, ("this is a message", [].as_slice()));
+/// ```
+impl PartialEq<(&str, &[(&str, &str)])> for Log<'_> {
+    fn eq(&self, (message, attributes): &(&str, &[(&str, &str)])) -> bool {
+        self.message() == *message && self.attributes() == *attributes
+    }
+}
+
+impl PartialEq<Log<'_>> for (&str, &[(&str, &str)]) {
+    fn eq(&self, log: &Log<'_>) -> bool {
+        log == self
+    }
+}
+
+impl Index<&str> for Log<'_> {
+    type Output = str;
+
+    /// Return the value of the first attribute with the key, ignoring surrounding quotes.
+    /// Panics if there is no attribute with the key.
+    fn index(&self, key: &str) -> &str {
+        self.get(key)
+            .unwrap_or_else(|| panic!("no attribute with key `{key}`"))
+    }
+}
+
+impl<'log> From<&'log Log<'_>> for BTreeMap<&'log str, &'log str> {
+    fn from(log: &'log Log<'_>) -> Self {
+        log.attributes_map()
+    }
+}
+
+impl<'log> IntoIterator for &'log Log<'_> {
+    type Item = (&'log str, &'log str);
+    type IntoIter = Iter<'log>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the keys and values of the attributes of a log message
+#[derive(Debug, Clone)]
+pub struct Iter<'log> {
+    attributes: slice::Iter<'log, Attribute<'log>>,
+}
+
+impl<'log> Iterator for Iter<'log> {
+    type Item = (&'log str, &'log str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.attributes
+            .next()
+            .map(|attribute| (attribute.key(), attribute.value()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.attributes.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.attributes
+            .next_back()
+            .map(|attribute| (attribute.key(), attribute.value()))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl FusedIterator for Iter<'_> {}
+
+/// An iterator over the keys of the attributes of a log message
+#[derive(Debug, Clone)]
+pub struct Keys<'log> {
+    inner: Iter<'log>,
+}
+
+impl<'log> Iterator for Keys<'log> {
+    type Item = &'log str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Keys<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl ExactSizeIterator for Keys<'_> {}
+
+impl FusedIterator for Keys<'_> {}
+
+/// An iterator over the values of the attributes of a log message
+#[derive(Debug, Clone)]
+pub struct Values<'log> {
+    inner: Iter<'log>,
+}
+
+impl<'log> Iterator for Values<'log> {
+    type Item = &'log str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Values<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl ExactSizeIterator for Values<'_> {}
+
+impl FusedIterator for Values<'_> {}
+
+/// An iterator over the keys and classified values of the attributes of a log message
+#[derive(Debug, Clone)]
+pub struct TypedIter<'log> {
+    inner: Iter<'log>,
+}
+
+impl<'log> Iterator for TypedIter<'log> {
+    type Item = (&'log str, Value<'log>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(key, value)| (key, TypedValue::new(value).value()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for TypedIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|(key, value)| (key, TypedValue::new(value).value()))
+    }
+}
+
+impl ExactSizeIterator for TypedIter<'_> {}
+
+impl FusedIterator for TypedIter<'_> {}
+
+/// Adds an attribute, replacing the value of an earlier attribute with the same key.
+/// Returns the replaced value.
+pub(crate) fn insert_attribute<'message>(
+    attributes: &mut Vec<Attribute<'message>>,
+    key: Cow<'message, str>,
+    value: Cow<'message, str>,
+) -> Option<Cow<'message, str>> {
+    match attributes
+        .iter_mut()
+        .find(|attribute| keys_match(attribute.key(), &key))
+    {
+        Some(attribute) => {
+            attribute.span = None;
+            Some(core::mem::replace(&mut attribute.value, value))
+        }
+        None => {
+            attributes.push(Attribute::new(key, value));
+            None
+        }
+    }
+}
+
+/// Validates a key of an attribute added after parsing
+pub(crate) fn validate_new_key(key: &str) -> Result<(), InvalidKey> {
+    validate_key(key, &ParseOptions::default()).map_err(|kind| InvalidKey {
+        key: key.into(),
+        kind,
+    })
+}
+
+/// A closure writing the value of an attribute
+type WriteValue<'message> = dyn Fn(&mut dyn fmt::Write) -> fmt::Result + 'message;
+
+/// A closure writing the value of an attribute when the log message is serialized
+#[derive(Clone)]
+struct LazyValue<'message> {
+    /// The index of the attribute
+    index: usize,
+    write: Rc<WriteValue<'message>>,
+}
+
+impl LazyValue<'_> {
+    /// Runs the closure, returning the value quoted and escaped where needed
+    fn value(&self) -> String {
+        let mut contents = String::new();
+        let _ = (self.write)(&mut contents);
+        let mut value = String::new();
+        crate::encoder::write_value(&mut value, &contents).expect("writing to a string can't fail");
+        value
+    }
+}
+
+impl fmt::Debug for LazyValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyValue")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builds a log message from a message and attributes
+#[derive(Debug, Clone, Default)]
+pub struct LogBuilder<'message> {
+    message: Cow<'message, str>,
+    attributes: Vec<Attribute<'message>>,
+    lazy_values: Vec<LazyValue<'message>>,
+}
+
+impl<'message> LogBuilder<'message> {
+    /// Set the message
+    #[must_use]
+    pub fn message(mut self, message: impl Into<Cow<'message, str>>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    /// Add an attribute, replacing the value of an earlier attribute with the same key
+    #[must_use]
+    pub fn attribute(
+        mut self,
+        key: impl Into<Cow<'message, str>>,
+        value: impl Into<Cow<'message, str>>,
+    ) -> Self {
+        let key = key.into();
+        self.remove_lazy_value(&key);
+        insert_attribute(&mut self.attributes, key, value.into());
+        self
+    }
+
+    /// Add an attribute with a value written by the closure when the log message is serialized,
+    /// replacing the value of an earlier attribute with the same key.
+    /// The value is quoted and escaped where needed, like [`LogBuilder::attr`]. If the closure
+    /// returns an error, the value is what it wrote before.
+    /// [`LogBuilder::build`] runs the closure to store the value, [`LogBuilder::build_lazy`]
+    /// keeps it.
+    #[must_use]
+    pub fn attr_lazy(
+        mut self,
+        key: impl Into<Cow<'message, str>>,
+        write: impl Fn(&mut dyn fmt::Write) -> fmt::Result + 'message,
+    ) -> Self {
+        let key = key.into();
+        self.remove_lazy_value(&key);
+        let index = self
+            .attributes
+            .iter()
+            .position(|attribute| keys_match(attribute.key(), &key))
+            .unwrap_or(self.attributes.len());
+        insert_attribute(&mut self.attributes, key, Cow::Borrowed(""));
+        self.lazy_values.push(LazyValue {
+            index,
+            write: Rc::new(write),
+        });
+        self
+    }
+
+    /// Removes the closure writing the value of the attribute with the key
+    fn remove_lazy_value(&mut self, key: &str) {
+        let attributes = &self.attributes;
+        self.lazy_values
+            .retain(|lazy_value| !keys_match(attributes[lazy_value.index].key(), key));
+    }
+
+    /// Set the message, like [`LogBuilder::message`]
+    #[must_use]
+    pub fn msg(self, message: impl Into<Cow<'message, str>>) -> Self {
+        self.message(message)
+    }
+
+    /// Add an attribute with the formatted value, replacing the value of an earlier attribute
+    /// with the same key. The value is quoted and escaped where needed, like
+    /// [`Encoder::push`](crate::Encoder::push).
+    #[must_use]
+    pub fn attr(self, key: impl Into<Cow<'message, str>>, value: impl Display) -> Self {
+        let mut raw = String::new();
+        crate::encoder::write_value(&mut raw, &format!("{value}"))
+            .expect("writing to a string can't fail");
+        self.attribute(key, raw)
+    }
+
+    /// Build the log message and return it as logfmt line, like [`Log::to_logfmt`]
+    pub fn finish_to_string(self) -> Result<String, InvalidKey> {
+        self.build_lazy().map(|log| log.to_logfmt())
+    }
+
+    /// Build the log message and write it as logfmt line, like [`Log::write_logfmt`]
+    pub fn finish_to<W: fmt::Write + ?Sized>(self, f: &mut W) -> Result<(), EncodeError> {
+        self.build_lazy()?.write_logfmt(f)?;
+        Ok(())
+    }
+
+    /// Build the log message, returning an error if a key isn't valid.
+    /// The closures of [`LogBuilder::attr_lazy`] run to store their values.
+    pub fn build(self) -> Result<Log<'message>, InvalidKey> {
+        self.build_lazy().map(LazyLog::into_log)
+    }
+
+    /// Build the log message like [`LogBuilder::build`], keeping the closures of
+    /// [`LogBuilder::attr_lazy`] to only run them when the log message is written
+    pub fn build_lazy(self) -> Result<LazyLog<'message>, InvalidKey> {
+        for attribute in &self.attributes {
+            validate_new_key(attribute.key())?;
+        }
+        Ok(LazyLog {
+            log: Log {
+                message: self.message.into(),
+                attributes: self.attributes,
+                message_is_fallback: false,
+                message_spans: Vec::new(),
+                original: None,
+            },
+            lazy_values: self.lazy_values,
+        })
+    }
+}
+
+/// A log message built with [`LogBuilder::build_lazy`], running the closures of
+/// [`LogBuilder::attr_lazy`] every time it's written.
+/// Derefs to the log, in which the lazy attributes have an empty value.
+#[derive(Debug, Clone)]
+pub struct LazyLog<'message> {
+    log: Log<'message>,
+    lazy_values: Vec<LazyValue<'message>>,
+}
+
+impl<'message> LazyLog<'message> {
+    /// Run the closures, returning the log message with their values
+    pub fn into_log(mut self) -> Log<'message> {
+        for lazy_value in &self.lazy_values {
+            self.log.attributes[lazy_value.index].value = Cow::Owned(lazy_value.value());
+        }
+        self.log
+    }
+
+    /// Writes the log message as logfmt line, like [`Log::write_logfmt`], running the closures
+    pub fn write_logfmt<W: fmt::Write + ?Sized>(&self, f: &mut W) -> fmt::Result {
+        if self.lazy_values.is_empty() {
+            return self.log.write_logfmt(f);
+        }
+        self.clone().into_log().write_logfmt(f)
+    }
+
+    /// Returns the log message as logfmt line, like [`LazyLog::write_logfmt`]
+    pub fn to_logfmt(&self) -> String {
+        let mut line = String::new();
+        self.write_logfmt(&mut line)
+            .expect("writing to a string can't fail");
+        line
+    }
+}
+
+impl<'message> Deref for LazyLog<'message> {
+    type Target = Log<'message>;
+
+    fn deref(&self) -> &Log<'message> {
+        &self.log
+    }
+}
+
+impl Display for LazyLog<'_> {
+    /// Writes the log message as logfmt line, like [`LazyLog::write_logfmt`]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_logfmt(f)
+    }
+}
+
+impl<'message> Log<'message> {
+    /// Create a builder for a log message
+    pub fn builder() -> LogBuilder<'message> {
+        LogBuilder::default()
+    }
+
+    /// Create a builder for a log message, like [`Log::builder`], for chaining into
+    /// [`LogBuilder::finish_to_string`] or [`LogBuilder::finish_to`]
+    pub fn build() -> LogBuilder<'message> {
+        LogBuilder::default()
+    }
+
+    /// Return the log message with the message replaced
+    #[must_use]
+    pub fn with_message(mut self, message: impl Into<Cow<'message, str>>) -> Self {
+        self.set_message(message);
+        self
+    }
+
+    /// Replace the message
+    pub fn set_message(&mut self, message: impl Into<Cow<'message, str>>) {
+        self.message = message.into().into();
+        self.message_is_fallback = false;
+        self.message_spans.clear();
+        self.original = None;
+    }
+
+    /// Take the message, leaving an empty message that isn't written by [`Log::write_logfmt`]
+    pub fn take_message(&mut self) -> Cow<'message, str> {
+        self.replace_message("")
+    }
+
+    /// Replace the message, returning the old one.
+    /// The new message is written by [`Log::write_logfmt`], even if the old one was the fallback.
+    pub fn replace_message(
+        &mut self,
+        message: impl Into<Cow<'message, str>>,
+    ) -> Cow<'message, str> {
+        self.message_is_fallback = false;
+        self.message_spans.clear();
+        self.original = None;
+        core::mem::replace(&mut self.message, message.into().into()).into_cow()
+    }
+
+    /// Add an attribute, replacing the value of an existing attribute with the same key, ignoring
+    /// surrounding quotes. Returns the replaced value, or an error if the key isn't valid.
+    pub fn insert(
+        &mut self,
+        key: impl Into<Cow<'message, str>>,
+        value: impl Into<Cow<'message, str>>,
+    ) -> Result<Option<Cow<'message, str>>, InvalidKey> {
+        let key = key.into();
+        validate_new_key(&key)?;
+        self.original = None;
+        Ok(insert_attribute(&mut self.attributes, key, value.into()))
+    }
+
+    /// Remove the first attribute with the key, ignoring surrounding quotes
+    pub fn remove(&mut self, key: &str) -> Option<Attribute<'message>> {
+        let index = self
+            .attributes
+            .iter()
+            .position(|attribute| keys_match(attribute.key(), key))?;
+        self.original = None;
+        Some(self.attributes.remove(index))
+    }
+
+    /// Split the log into its message and its attributes in input order, without copying
+    pub fn into_parts(self) -> (Cow<'message, str>, Vec<Attribute<'message>>) {
+        (self.message.into_cow(), self.attributes)
+    }
+
+    /// Return the message, dropping the attributes without copying the message
+    pub fn into_message(self) -> Cow<'message, str> {
+        self.message.into_cow()
+    }
+
+    /// Merge the attributes of another log into this one.
+    /// Attributes with new keys are appended as long as there are fewer than `max_attributes`,
+    /// like [`ParseOptions::max_attributes`], the policy decides which value is kept for existing
+    /// keys.
+    /// The message of the other log is used if this log doesn't have one.
+    pub fn merge(&mut self, other: &Log<'_>, policy: MergePolicy, max_attributes: Option<usize>) {
+        self.original = None;
+        if self.message.is_empty() {
+            self.message = Message::from(other.message()).into_owned();
+            self.message_is_fallback = other.message_is_fallback;
+            self.message_spans.clear();
+        }
+        for (key, value) in other {
+            let existing = self
+                .attributes
+                .iter()
+                .position(|existing| keys_match(existing.key(), key));
+            match existing {
+                Some(index) if policy == MergePolicy::Overwrite => {
+                    self.attributes[index].value = Cow::Owned(value.into());
+                    self.attributes[index].span = None;
+                }
+                None if max_attributes.is_none_or(|max| self.attributes.len() < max) => {
+                    self.attributes.push(Attribute::new(
+                        Cow::Owned(key.into()),
+                        Cow::Owned(value.into()),
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Rename the first attribute with the key, ignoring surrounding quotes.
+    /// Other attributes with the new key are removed, keeping the renamed value.
+    /// Returns whether an attribute was renamed.
+    pub fn rename_key(&mut self, from: &str, to: impl Into<Cow<'message, str>>) -> bool {
+        let Some(index) = self
+            .attributes
+            .iter()
+            .position(|attribute| keys_match(attribute.key(), from))
+        else {
+            return false;
+        };
+        let to = to.into();
+        let mut current = 0;
+        self.attributes.retain(|attribute| {
+            current += 1;
+            current - 1 == index || !keys_match(attribute.key(), &to)
+        });
+
+        // Attributes before the renamed one may have been removed
+        let index = self
+            .attributes
+            .iter()
+            .position(|attribute| keys_match(attribute.key(), from))
+            .unwrap_or(index);
+        self.attributes[index].key = to;
+        self.attributes[index].span = None;
+        self.original = None;
+        true
+    }
+
+    /// Rename the keys of attributes, like [`Log::rename_key`], for each pair of old and new key
+    /// in order
+    pub fn remap_keys(&mut self, map: &[(&str, &str)]) {
+        for (from, to) in map {
+            self.rename_key(from, Cow::Owned(String::from(*to)));
+        }
+    }
+
+    /// Sort the attributes by key, keeping the order of attributes with the same key
+    pub fn sort_attributes(&mut self) {
+        self.attributes.sort_by(|a, b| a.key().cmp(b.key()));
+        self.original = None;
+    }
+
+    /// Keep only the attributes for which the predicate returns `true`, preserving their order
+    pub fn retain(&mut self, mut predicate: impl FnMut(&str, &str) -> bool) {
+        let len = self.attributes.len();
+        self.attributes
+            .retain(|attribute| predicate(attribute.key(), attribute.value()));
+        if self.attributes.len() != len {
+            self.original = None;
+        }
+    }
+
+    /// Copy the message and attributes into owned storage, if they're borrowed
+    pub fn into_owned(self) -> Log<'static> {
+        Log {
+            message: self.message.into_owned(),
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(Attribute::into_owned)
+                .collect(),
+            message_is_fallback: self.message_is_fallback,
+            message_spans: self.message_spans,
+            original: self
+                .original
+                .map(|original| Cow::Owned(original.into_owned())),
+        }
+    }
+}
+
+/// A log message stored in owned strings, for keeping logs independently of their input.
+/// Keys are shared, so logs converted with [`OwnedLog::from_log_interned`] can share equal keys.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OwnedLog {
+    pub(crate) message: String,
+    pub(crate) attributes: Vec<(Arc<str>, String)>,
+    pub(crate) message_is_fallback: bool,
+}
+
+impl OwnedLog {
+    /// Parses the log message, like [`Log::parse`], copying the message and attributes
+    pub fn parse(s: &str) -> Result<Self, UnclosedString> {
+        Log::parse(s).map(Self::from)
+    }
+
+    /// Return the message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Return the list of attributes
+    pub fn attributes(&self) -> &[(Arc<str>, String)] {
+        &self.attributes
+    }
+
+    /// Return an iterator over the keys and values of the attributes
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&str, &str)> + ExactSizeIterator {
+        self.attributes
+            .iter()
+            .map(|(key, value)| (&**key, value.as_str()))
+    }
+
+    /// Return the value of the first attribute with the key, ignoring surrounding quotes
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .find(|(attribute_key, _)| keys_match(attribute_key, key))
+            .map(|(_, value)| value)
+    }
+
+    /// Borrow the message and attributes as a [`Log`]
+    pub fn as_log(&self) -> Log<'_> {
+        Log {
+            message: Message::Borrowed(&self.message),
+            attributes: self
+                .iter()
+                .map(|(key, value)| Attribute::new(Cow::Borrowed(key), Cow::Borrowed(value)))
+                .collect(),
+            message_is_fallback: self.message_is_fallback,
+            message_spans: Vec::new(),
+            original: None,
+        }
+    }
+}
+
+impl From<Log<'_>> for OwnedLog {
+    fn from(log: Log<'_>) -> Self {
+        Self {
+            message: log.message.into_string(),
+            attributes: log
+                .attributes
+                .into_iter()
+                .map(|attribute| (Arc::from(attribute.key), attribute.value.into_owned()))
+                .collect(),
+            message_is_fallback: log.message_is_fallback,
+        }
+    }
+}
+
+impl From<OwnedLog> for Log<'static> {
+    fn from(log: OwnedLog) -> Self {
+        Self {
+            message: log.message.into(),
+            attributes: log
+                .attributes
+                .into_iter()
+                .map(|(key, value)| {
+                    Attribute::new(Cow::Owned(String::from(&*key)), Cow::Owned(value))
+                })
+                .collect(),
+            message_is_fallback: log.message_is_fallback,
+            message_spans: Vec::new(),
+            original: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        borrow::{Cow, ToOwned},
+        collections::{BTreeMap, BTreeSet},
+        format,
+        string::{String, ToString},
+        sync::Arc,
+        vec::Vec,
+    };
+    use core::{
+        cell::Cell,
+        cmp::Ordering,
+        fmt::Write as _,
+        hash::{Hash, Hasher},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+        ops::Range,
+        time::Duration,
+    };
+
+    use crate::encode::crc32;
+    use crate::{
+        Attribute, AutoParseError, BoolStyle, BufferTooSmall, ColorScheme, ControlChars,
+        DEFAULT_MAX_ATTRIBUTES, DuplicatePolicy, EncodeError, EncodeOptions, EncodeStyle, Encoder,
+        EqualsInValue, ErrorKind, FlagWords, JsonError, JsonErrorKind, JsonValues, KeyFilter,
+        KeyStyle, Level, Log, MergePolicy, Message, MessageRenderer, Number, OverlongValue,
+        OwnedLog, ParseError, ParseOptions, Separators, SyslogOptions, Timestamp, UnclosedString,
+        UnescapeError, UnknownLevel, Value, default_key_validator, unescape, unquote,
+    };
+
+    #[test]
+    fn message_with_attributes() {
+        let result =
+            Log::parse("this is foo=bar duration=10 a value=\"with spaces\" message").unwrap();
+        assert_eq!(result.message(), "this is a message".to_owned());
+        assert_eq!(
+            result.attributes(),
+            [
+                ("foo", "bar"),
+                ("duration", "10"),
+                ("value", "\"with spaces\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn message_with_override() {
+        let result =
+            Log::parse("this is foo=bar a duration=10 message message=\"I am a message\"").unwrap();
+        assert_eq!(result.message(), "\"I am a message\"");
+        assert_eq!(result.attributes(), [("foo", "bar"), ("duration", "10")]);
+    }
+
+    #[test]
+    fn attributes_only() {
+        let message = "foo=bar duration=100";
+        let result = Log::parse("foo=bar duration=100").unwrap();
+        assert_eq!(result.message(), message);
+        assert_eq!(result.attributes(), [("foo", "bar"), ("duration", "100")]);
+    }
+
+    #[test]
+    fn webrequests() {
+        for (request, attributes) in [
+            "baseUrl=\"/\" hostname=localhost protocol=http",
+            "baseUrl=\"/\" hostname=localhost protocol=http name=matthew",
+        ]
+        .into_iter()
+        .zip([
+            [
+                ("baseUrl", "\"/\""),
+                ("hostname", "localhost"),
+                ("protocol", "http"),
+            ]
+            .as_slice(),
+            [
+                ("baseUrl", "\"/\""),
+                ("hostname", "localhost"),
+                ("protocol", "http"),
+                ("name", "matthew"),
+            ]
+            .as_slice(),
+        ]) {
+            let log = Log::parse(request).unwrap();
+            assert_eq!(log.message(), request);
+            assert_eq!(log.attributes(), attributes);
+        }
+    }
+
+    #[test]
+    fn parse_bytes() {
+        let valid = "msg=\"température\" a=1".as_bytes();
+        assert_eq!(
+            Log::parse_bytes(valid),
+            Ok(Log::parse(core::str::from_utf8(valid).unwrap()).unwrap())
+        );
+        let log = Log::parse_bytes_lossy(valid).unwrap();
+        assert!(matches!(log.attributes[0].value, Cow::Borrowed(_)));
+        assert_eq!(log.get("a"), Some("1"));
+
+        let invalid = b"started a=x\xFFy b=2";
+        let error = Log::parse_bytes(invalid).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidUtf8);
+        assert_eq!(error.offset(), 11);
+        let log = Log::parse_bytes_lossy(invalid).unwrap();
+        assert_eq!(log.message(), "started");
+        assert_eq!(log.attributes(), [("a", "x\u{FFFD}y"), ("b", "2")]);
+
+        // A multibyte character cut off at the end
+        let truncated = &"a=1 b=é".as_bytes()[..7];
+        let error = Log::parse_bytes(truncated).unwrap_err();
+        assert_eq!((error.offset(), error.token("a=1 b=x")), (6, Some("x")));
+        let log = Log::parse_bytes_lossy(truncated).unwrap();
+        assert_eq!(log.attributes(), [("a", "1"), ("b", "\u{FFFD}")]);
+    }
+
+    #[test]
+    fn projections() {
+        let log =
+            Log::parse("baseUrl=\"/\" hostname=localhost protocol=http name=matthew").unwrap();
+        let mut output = String::new();
+        log.write_attributes_only(&mut output, "\n").unwrap();
+        assert_eq!(
+            output,
+            "baseUrl=\"/\"\nhostname=localhost\nprotocol=http\nname=matthew"
+        );
+        output.clear();
+        log.write_message_only(&mut output).unwrap();
+        assert_eq!(output, "");
+
+        let mut log = Log::parse("msg=\"say \\\"hi\\\"\" a=1").unwrap();
+        log.insert("b", "x y").unwrap();
+        log.write_message_only(&mut output).unwrap();
+        assert_eq!(output, "say \"hi\"");
+        output.clear();
+        log.write_attributes_only(&mut output, ",").unwrap();
+        assert_eq!(output, "a=1,b=\"x y\"");
+    }
+
+    #[test]
+    fn unlimited_attributes() {
+        let line = (0..30).fold(String::new(), |mut line, i| {
+            write!(&mut line, "key{i}=value{i} ").unwrap();
+            line
+        });
+        let options = ParseOptions {
+            max_attributes: None,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(&line, &options).unwrap();
+        assert_eq!(log.attributes().len(), 30);
+        assert_eq!(log.attributes()[29], ("key29", "value29"));
+    }
+
+    #[test]
+    fn limited_attributes() {
+        let options = ParseOptions {
+            max_attributes: Some(2),
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("hello foo=bar duration=10 status=ok", &options).unwrap();
+        assert_eq!(log.message(), "hello status=ok");
+        assert_eq!(log.attributes(), [("foo", "bar"), ("duration", "10")]);
+    }
+
+    #[test]
+    fn long_values() {
+        let line = format!("payload=\"{}\"", "a".repeat(300));
+
+        // The value is too long by default, so it's added to the message
+        let log = Log::parse(&line).unwrap();
+        assert_eq!(log.message(), line);
+        assert!(log.attributes().is_empty());
+
+        // With a raised limit, it's parsed as an attribute
+        let options = ParseOptions {
+            max_value_len: 300,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(&line, &options).unwrap();
+        assert_eq!(log.attributes(), [("payload", &line[8..])]);
+
+        // The limit can be disabled completely
+        let options = ParseOptions {
+            max_key_len: usize::MAX,
+            max_value_len: usize::MAX,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(&line, &options).unwrap();
+        assert_eq!(log.attributes(), [("payload", &line[8..])]);
+    }
+
+    #[test]
+    fn strict() {
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        for (line, kind, offset) in [
+            ("took durtion=1o0\"s\"", ErrorKind::UnbalancedQuote, 5),
+            ("user@host=me", ErrorKind::InvalidKey, 0),
+            ("a=1 k\"e\"y=2", ErrorKind::UnbalancedQuote, 4),
+            ("unclosed=\"string", ErrorKind::UnclosedString, 0),
+        ] {
+            let error = Log::parse_with(line, &options).unwrap_err();
+            assert_eq!(error.kind(), kind);
+            assert_eq!(error.offset(), offset);
+        }
+
+        let line = format!("key={}", "a".repeat(101));
+        let error = Log::parse_with(&line, &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueTooLong);
+
+        let options = ParseOptions {
+            max_attributes: Some(1),
+            ..options
+        };
+        let error = Log::parse_with("foo=bar bar=baz", &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TooManyAttributes);
+        assert_eq!(error.offset(), 8);
+
+        // Non-strict mode keeps adding malformed tokens to the message
+        let log = Log::parse("took durtion=1o0\"s\"").unwrap();
+        assert_eq!(log.message(), "took durtion=1o0\"s\"");
+        assert!(log.attributes().is_empty());
+    }
+
+    #[test]
+    fn empty_values() {
+        // Empty values are allowed by default
+        let log = Log::parse("retrying retry= count=3").unwrap();
+        assert_eq!(log.message(), "retrying");
+        assert_eq!(log.attributes(), [("retry", ""), ("count", "3")]);
+
+        // Otherwise they're words
+        let options = ParseOptions {
+            allow_empty_values: false,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("retrying retry= count=3", &options).unwrap();
+        assert_eq!(log.message(), "retrying retry=");
+        assert_eq!(log.attributes(), [("count", "3")]);
+
+        // Or errors in strict mode
+        let options = ParseOptions {
+            strict: true,
+            ..options
+        };
+        let error = Log::parse_with("retrying retry= count=3", &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::EmptyValue);
+        assert_eq!(error.offset(), 9);
+    }
+
+    #[test]
+    fn empty_keys() {
+        for allow_empty_values in [false, true] {
+            let options = ParseOptions {
+                allow_empty_values,
+                ..ParseOptions::default()
+            };
+            let log = Log::parse_with("a = =b c=d", &options).unwrap();
+            assert_eq!(log.message(), "a = =b");
+            assert_eq!(log.attributes(), [("c", "d")]);
+        }
+    }
+
+    #[test]
+    fn flags() {
+        let options = ParseOptions {
+            bare_words_as_flags: FlagWords::Only(&["dryrun"]),
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("starting dryrun mode=fast", &options).unwrap();
+        assert_eq!(log.message(), "starting");
+        assert_eq!(log.attributes(), [("dryrun", "true"), ("mode", "fast")]);
+
+        // Words that aren't valid keys stay in the message
+        let options = ParseOptions {
+            bare_words_as_flags: FlagWords::All,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("done! dryrun mode=fast", &options).unwrap();
+        assert_eq!(log.message(), "done!");
+        assert_eq!(log.attributes(), [("dryrun", "true"), ("mode", "fast")]);
+    }
+
+    #[test]
+    fn message_keys() {
+        let options = ParseOptions {
+            message_keys: &["event", "text"],
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("foo=bar event=started msg=hello", &options).unwrap();
+        assert_eq!(log.message(), "started");
+        assert_eq!(log.attributes(), [("foo", "bar"), ("msg", "hello")]);
+
+        let log = Log::parse_with("foo=bar \"text\"=\"hello world\"", &options).unwrap();
+        assert_eq!(log.message(), "\"hello world\"");
+        assert_eq!(log.attributes(), [("foo", "bar")]);
+    }
+
+    #[test]
+    fn quoted_tokens() {
+        let log = Log::parse("\"hello world\" \"foo\"=bar").unwrap();
+        assert_eq!(log.message(), "\"hello world\"");
+        assert_eq!(log.attributes(), [("\"foo\"", "bar")]);
+
+        assert!(Log::parse("\"hello world").is_err());
+    }
+
+    #[test]
+    fn without_message_override() {
+        let options = ParseOptions {
+            override_message: false,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("hello msg=world", &options).unwrap();
+        assert_eq!(log.message(), "hello");
+        assert_eq!(log.attributes(), [("msg", "world")]);
+    }
+
+    #[test]
+    fn case_insensitive_message_keys() {
+        let options = ParseOptions {
+            case_insensitive_message_keys: true,
+            ..ParseOptions::default()
+        };
+        for line in ["Msg=hello", "MESSAGE=hello", "\"MSG\"=hello"] {
+            let log = Log::parse_with(line, &options).unwrap();
+            assert_eq!(log.message(), "hello");
+            assert!(log.attributes().is_empty());
+        }
+
+        let log = Log::parse_with("hello msgid=10", &options).unwrap();
+        assert_eq!(log.message(), "hello");
+        assert_eq!(log.attributes(), [("msgid", "10")]);
+
+        // Case sensitive matching is the default
+        let log = Log::parse("hello Msg=world").unwrap();
+        assert_eq!(log.message(), "hello");
+        assert_eq!(log.attributes(), [("Msg", "world")]);
+    }
+
+    #[test]
+    fn duplicates() {
+        let line = "tag=a status=ok tag=b tag=c";
+        for (duplicates, attributes) in [
+            (
+                DuplicatePolicy::Overwrite,
+                [("tag", "c"), ("status", "ok")].as_slice(),
+            ),
+            (
+                DuplicatePolicy::KeepFirst,
+                [("tag", "a"), ("status", "ok")].as_slice(),
+            ),
+            (
+                DuplicatePolicy::KeepAll,
+                [("tag", "a"), ("status", "ok"), ("tag", "b"), ("tag", "c")].as_slice(),
+            ),
+        ] {
+            let options = ParseOptions {
+                duplicates,
+                ..ParseOptions::default()
+            };
+            let log = Log::parse_with(line, &options).unwrap();
+            assert_eq!(log.attributes(), attributes);
+        }
+    }
+
+    #[test]
+    fn key_filter() {
+        let line = "request done a=1 b=2 duration=10 c=3 d=4 e=5 status=200 f=6 g=7 h=8";
+        for key_filter in [
+            KeyFilter::Allow(&["duration", "status"]),
+            KeyFilter::Deny(&["a", "b", "c", "d", "e", "f", "g", "h"]),
+            KeyFilter::Predicate(|key| key.len() > 1),
+        ] {
+            let options = ParseOptions {
+                key_filter,
+                max_attributes: Some(2),
+                ..ParseOptions::default()
+            };
+            let log = Log::parse_with(line, &options).unwrap();
+            assert_eq!(log.message(), "request done");
+            assert_eq!(log.attributes(), [("duration", "10"), ("status", "200")]);
+        }
+
+        // Options can still be compared
+        let options = ParseOptions {
+            key_filter: KeyFilter::Allow(&["duration"]),
+            ..ParseOptions::default()
+        };
+        assert_eq!(options, options.clone());
+        assert_ne!(options, ParseOptions::default());
+    }
+
+    #[test]
+    fn key_validator() {
+        let line = "app.kubernetes.io/name=frontend";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.message(), line);
+        assert!(log.attributes().is_empty());
+
+        let options = ParseOptions {
+            key_validator: |key| {
+                key.split('/')
+                    .all(|part| !part.is_empty() && default_key_validator(part))
+            },
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.attributes(), [("app.kubernetes.io/name", "frontend")]);
+    }
+
+    #[test]
+    fn equals_in_value() {
+        let line = "cursor=YWJjZA== url=/search?q=a=b query=\"q=a\"";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(
+            log.attributes(),
+            [
+                ("cursor", "YWJjZA=="),
+                ("url", "/search?q=a=b"),
+                ("query", "\"q=a\"")
+            ]
+        );
+
+        let options = ParseOptions {
+            equals_in_value: EqualsInValue::Reject,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message(), "cursor=YWJjZA== url=/search?q=a=b");
+        assert_eq!(log.attributes(), [("query", "\"q=a\"")]);
+
+        let options = ParseOptions {
+            strict: true,
+            ..options
+        };
+        let error = Log::parse_with(line, &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::EqualsInValue);
+        assert_eq!(error.offset(), 0);
+    }
+
+    #[test]
+    fn single_quotes() {
+        let line = "path='/tmp/my file' name=\"it's\"";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.message(), "file'");
+        assert_eq!(
+            log.attributes(),
+            [("path", "'/tmp/my"), ("name", "\"it's\"")]
+        );
+
+        let options = ParseOptions {
+            single_quotes: true,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(
+            log.attributes(),
+            [("path", "'/tmp/my file'"), ("name", "\"it's\"")]
+        );
+
+        // Keys that are empty without their quotes are malformed
+        for line in ["\"\"=v ok=1", "''=v ok=1"] {
+            let log = Log::parse_with(line, &options).unwrap();
+            assert_eq!(log.message(), &line[..4], "{line:?}");
+            assert_eq!(log.attributes(), [("ok", "1")], "{line:?}");
+            let strict = ParseOptions {
+                strict: true,
+                ..options.clone()
+            };
+            let error = Log::parse_with(line, &strict).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::EmptyKey, "{line:?}");
+        }
+
+        // Strings have to be closed by the same kind of quote
+        let error = Log::parse_with("value='abc\"", &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnclosedString);
+        let error = Log::parse_with("value='abc\" def=x", &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnclosedString);
+    }
+
+    #[test]
+    fn separators() {
+        let options = ParseOptions {
+            separators: Separators::Chars(&['|']),
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("temp=21.5|state=idle|boot|note=\"a|b\"", &options).unwrap();
+        assert_eq!(log.message(), "boot");
+        assert_eq!(
+            log.attributes(),
+            [("temp", "21.5"), ("state", "idle"), ("note", "\"a|b\"")]
+        );
+
+        let options = ParseOptions {
+            separators: Separators::Predicate(|ch| ch == ',' || ch.is_whitespace()),
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("a=1, b=2,c=3", &options).unwrap();
+        assert_eq!(log.attributes(), [("a", "1"), ("b", "2"), ("c", "3")]);
+    }
+
+    #[test]
+    fn max_input_len() {
+        let line = "a".repeat(1024 * 1024);
+        let options = ParseOptions {
+            max_input_len: Some(64 * 1024),
+            ..ParseOptions::default()
+        };
+        let error = Log::parse_with(&line, &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InputTooLong);
+        assert_eq!(error.offset(), 64 * 1024);
+
+        // Input at the limit is parsed
+        let log = Log::parse_with(&line[..64 * 1024], &options).unwrap();
+        assert_eq!(log.message(), &line[..64 * 1024]);
+    }
+
+    #[test]
+    fn trailing_text_as_message() {
+        let line = "at=info method=GET path=/ this request was  slow, reasons=unknown ";
+        let options = ParseOptions {
+            trailing_text_as_message: true,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message(), "this request was  slow, reasons=unknown");
+        assert_eq!(
+            log.attributes(),
+            [("at", "info"), ("method", "GET"), ("path", "/")]
+        );
+
+        // Words before the first attribute are parsed as usual
+        let log = Log::parse_with("starting at=info now x=y", &options).unwrap();
+        assert_eq!(log.message(), "starting now x=y");
+        assert_eq!(log.attributes(), [("at", "info")]);
+    }
+
+    #[test]
+    fn strip_quotes() {
+        let options = ParseOptions {
+            strip_quotes: true,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("\"value\"=\"with spaces\" msg=\"a message\"", &options).unwrap();
+        assert_eq!(log.message(), "a message");
+        assert_eq!(log.attributes(), [("value", "with spaces")]);
+
+        // Quoted and unquoted keys are the same key
+        let log = Log::parse_with("\"foo\"=1 foo=\"2\"", &options).unwrap();
+        assert_eq!(log.attributes(), [("foo", "2")]);
+    }
+
+    #[test]
+    fn escaped_quotes() {
+        let line = "said=\"a \\\"quoted\\\" word\" path=\"C:\\\\temp\"";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(
+            log.attributes(),
+            [
+                ("said", "\"a \\\"quoted\\\" word\""),
+                ("path", "\"C:\\\\temp\"")
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_escapes() {
+        let options = ParseOptions {
+            decode_escapes: true,
+            ..ParseOptions::default()
+        };
+        let line =
+            "msg=\"line one\\nline two\" path=\"C:\\\\temp\" a=\"\\t\\r\\u00e9\\\"\" b=raw\\n";
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message(), "\"line one\nline two\"");
+        assert_eq!(
+            log.attributes(),
+            [
+                ("path", "\"C:\\temp\""),
+                ("a", "\"\t\r\u{e9}\"\""),
+                ("b", "raw\\n")
+            ]
+        );
+        assert!(matches!(log.attributes()[2].value, Cow::Borrowed(_)));
+
+        let options = ParseOptions {
+            strip_quotes: true,
+            ..options
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message(), "line one\nline two");
+        assert_eq!(log.attributes()[1], ("a", "\t\r\u{e9}\""));
+
+        let options = ParseOptions {
+            strict: true,
+            ..options
+        };
+        for line in ["a=\"\\x\"", "a=\"\\u12\"", "a=\"\\ud800\""] {
+            let error = Log::parse_with(line, &options).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidEscape);
+        }
+    }
+
+    #[test]
+    fn normalize_keys_lowercase() {
+        let options = ParseOptions {
+            normalize_keys_lowercase: true,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("Foo=1 method=GET foo=2 STATUS=ok", &options).unwrap();
+        assert_eq!(
+            log.attributes(),
+            [("foo", "2"), ("method", "GET"), ("status", "ok")]
+        );
+        assert!(matches!(log.attributes()[1].key, Cow::Borrowed(_)));
+
+        // Keys are kept as is by default
+        let log = Log::parse("Foo=1 foo=2").unwrap();
+        assert_eq!(log.attributes(), [("Foo", "1"), ("foo", "2")]);
+    }
+
+    #[test]
+    fn key_style() {
+        let line = "-weird-=1 123=456 2fast=yes _ok=1 \"fine\"=2";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.attributes().len(), 5);
+
+        let options = ParseOptions {
+            key_style: KeyStyle::Identifier,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message(), "-weird-=1 123=456 2fast=yes");
+        assert_eq!(log.attributes(), [("_ok", "1"), ("\"fine\"", "2")]);
+
+        let options = ParseOptions {
+            strict: true,
+            ..options
+        };
+        let error = Log::parse_with("ok=1 2fast=yes", &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidKey);
+        assert_eq!(error.offset(), 5);
+    }
+
+    #[test]
+    fn control_chars() {
+        let line = "done color=\"\x1b[31mred\x1b[0m\" nul=a\0b ok=1";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.attributes()[1], ("nul", "a\0b"));
+
+        let options = ParseOptions {
+            control_chars: ControlChars::Reject,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message(), "done color=\"\x1b[31mred\x1b[0m\" nul=a\0b");
+        assert_eq!(log.attributes(), [("ok", "1")]);
+
+        let options = ParseOptions {
+            strict: true,
+            ..options
+        };
+        let error = Log::parse_with(line, &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ControlCharacter);
+        assert_eq!(error.offset(), 5);
+        assert_eq!(error.key(line), Some("color"));
+
+        let options = ParseOptions {
+            control_chars: ControlChars::Sanitize,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(
+            log.attributes(),
+            [
+                ("color", "\"\u{FFFD}[31mred\u{FFFD}[0m\""),
+                ("nul", "a\u{FFFD}b"),
+                ("ok", "1")
+            ]
+        );
+
+        // Words are sanitized too, except separators
+        let log = Log::parse_with("ring\x07 the\tbell ok=1", &options).unwrap();
+        assert_eq!(log.message(), "ring\u{FFFD} the bell");
+        let log = Log::parse_with("bell=\x07\tok=1", &options).unwrap();
+        assert_eq!(log.message(), "bell=\u{FFFD}\tok=1");
+
+        // Words and decoded escape sequences are rejected
+        let options = ParseOptions {
+            control_chars: ControlChars::Reject,
+            strict: true,
+            ..ParseOptions::default()
+        };
+        for (line, offset) in [("ok=1 ring\x07", 5), ("done bell=\"\\u0007\"", 5)] {
+            let error = Log::parse_with(line, &options).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ControlCharacter, "{line:?}");
+            assert_eq!(error.offset(), offset, "{line:?}");
+        }
+        let options = ParseOptions {
+            strict: false,
+            ..options
+        };
+        let log = Log::parse_with("done bell=\"\\u0007\" ok=1", &options).unwrap();
+        assert_eq!(log.message(), "done bell=\"\\u0007\"");
+        assert_eq!(log.attributes(), [("ok", "1")]);
+    }
+
+    #[test]
+    fn attribute_capacity_hint() {
+        let line = "a=1 b=2 c=3";
+        let log = Log::parse(line).unwrap();
+        assert!(log.attributes.capacity() >= 8);
+
+        for (attribute_capacity_hint, max_attributes) in [(0, None), (16, Some(2))] {
+            let options = ParseOptions {
+                attribute_capacity_hint,
+                max_attributes,
+                ..ParseOptions::default()
+            };
+            let parsed = Log::parse_with(line, &options).unwrap();
+            let count = max_attributes.unwrap_or(3);
+            assert_eq!(parsed.attributes(), &log.attributes()[..count]);
+            assert!(parsed.attributes.capacity() >= count);
+        }
+    }
+
+    #[test]
+    fn trim_line_endings() {
+        let log = Log::parse("\u{FEFF}level=info\r\n").unwrap();
+        assert_eq!(log.message(), "level=info");
+        assert_eq!(log.attributes(), [("level", "info")]);
+
+        let log = Log::parse("\u{FEFF}hello\r\n").unwrap();
+        assert_eq!(log.message(), "hello");
+
+        let options = ParseOptions {
+            trim_line_endings: false,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("\u{FEFF}level=info\r\n", &options).unwrap();
+        assert_eq!(log.message(), "\u{FEFF}level=info");
+        assert!(log.attributes().is_empty());
+    }
+
+    #[test]
+    fn blank_input() {
+        for line in ["", "   ", "\t\n"] {
+            let log = Log::parse(line).unwrap();
+            assert_eq!(log.message(), "");
+            assert!(log.attributes().is_empty());
+        }
+
+        let options = ParseOptions {
+            keep_blank_input: true,
+            trim_line_endings: false,
+            ..ParseOptions::default()
+        };
+        for line in ["", "   ", "\t\n"] {
+            let log = Log::parse_with(line, &options).unwrap();
+            assert_eq!(log.message(), line);
+        }
+    }
+
+    #[test]
+    fn overlong_values() {
+        let line = format!("failed trace=\"{}\" id=1", "é".repeat(1024));
+
+        let log = Log::parse(&line).unwrap();
+        assert_eq!(log.message(), &line[..line.len() - 5]);
+        assert_eq!(log.attributes(), [("id", "1")]);
+
+        let options = ParseOptions {
+            on_overlong_value: OverlongValue::Truncate(3),
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(&line, &options).unwrap();
+        assert_eq!(log.message(), "failed");
+        assert_eq!(log.attributes(), [("trace", "\"ééé…\""), ("id", "1")]);
+
+        // Values that aren't too long aren't truncated
+        let log = Log::parse_with("trace=\"long\"", &options).unwrap();
+        assert_eq!(log.attributes(), [("trace", "\"long\"")]);
+
+        // Values are truncated to the maximum length, if it's shorter
+        let options = ParseOptions {
+            on_overlong_value: OverlongValue::Truncate(2048),
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(&line, &options).unwrap();
+        let truncated = format!("\"{}…\"", "é".repeat(options.max_value_len));
+        assert_eq!(
+            log.attributes(),
+            [("trace", truncated.as_str()), ("id", "1")]
+        );
+
+        let options = ParseOptions {
+            on_overlong_value: OverlongValue::Error,
+            ..ParseOptions::default()
+        };
+        let error = Log::parse_with(&line, &options).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueTooLong);
+        assert_eq!(error.key(&line), Some("trace"));
+
+        // The length of the message isn't limited
+        let message = "é".repeat(1024);
+        let line = format!("message=\"{message}\" id=1");
+        for options in [ParseOptions::default(), options] {
+            let log = Log::parse_with(&line, &options).unwrap();
+            assert_eq!(log.message(), format!("\"{message}\""));
+        }
+    }
+
+    #[test]
+    fn get() {
+        let log = Log::parse("\"baseUrl\"=\"/\" hostname=localhost").unwrap();
+        assert_eq!(log.get("baseUrl"), Some("\"/\""));
+        assert_eq!(log.get("\"baseUrl\""), Some("\"/\""));
+        assert_eq!(log.get("\"hostname\""), Some("localhost"));
+        assert_eq!(log.get("protocol"), None);
+        assert!(log.contains_key("hostname"));
+        assert!(!log.contains_key("protocol"));
+
+        // The first value is returned when duplicates are kept
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepAll,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("tag=a tag=b", &options).unwrap();
+        assert_eq!(log.get("tag"), Some("a"));
+    }
+
+    #[test]
+    fn get_parsed() {
+        let log = Log::parse("count=\"42\" ratio=0.5 cached=true duration=10ms").unwrap();
+        assert_eq!(log.get_parsed::<u64>("count"), Some(Ok(42)));
+        assert_eq!(log.get_parsed::<f64>("ratio"), Some(Ok(0.5)));
+        assert_eq!(log.get_parsed::<bool>("cached"), Some(Ok(true)));
+        assert_eq!(
+            log.get_parsed::<u32>("duration"),
+            Some(Err("10ms".parse::<u32>().unwrap_err()))
+        );
+        assert_eq!(log.get_parsed::<u32>("missing"), None);
+    }
+
+    #[test]
+    fn iter() {
+        let log = Log::parse("foo=bar duration=10 error=false").unwrap();
+        let mut iter = log.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some(("foo", "bar")));
+        assert_eq!(iter.next_back(), Some(("error", "false")));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next(), Some(("duration", "10")));
+        assert_eq!(iter.next(), None);
+
+        let mut attributes = Vec::new();
+        for (key, value) in &log {
+            attributes.push((key, value));
+        }
+        assert_eq!(log.attributes(), attributes);
+    }
+
+    #[test]
+    fn keys_and_values() {
+        let log = Log::parse("foo=bar duration=10 error=false").unwrap();
+        assert!(log.keys().any(|key| key == "error"));
+        assert_eq!(log.keys().len(), 3);
+        assert_eq!(
+            log.keys().rev().collect::<Vec<_>>(),
+            ["error", "duration", "foo"]
+        );
+        assert_eq!(log.values().len(), 3);
+        assert_eq!(log.values().collect::<Vec<_>>(), ["bar", "10", "false"]);
+        assert_eq!(log.values().next_back(), Some("false"));
+    }
+
+    #[test]
+    fn into_owned() {
+        let line = String::from("hello foo=bar \"value\"=\"with spaces\"");
+        let log = Log::parse(&line).unwrap();
+        let copy = log.to_owned_log();
+        let owned = log.into_owned();
+        drop(line);
+        for log in [owned, copy] {
+            assert_eq!(log.message(), "hello");
+            assert_eq!(
+                log.attributes(),
+                [("foo", "bar"), ("\"value\"", "\"with spaces\"")]
+            );
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let log = Log::builder()
+            .message("request done")
+            .attribute("status", "200")
+            .attribute("path", String::from("\"/x y\""))
+            .attribute("status", "404")
+            .build()
+            .unwrap();
+        assert_eq!(log.message(), "request done");
+        assert_eq!(log.attributes(), [("status", "404"), ("path", "\"/x y\"")]);
+
+        let log = Log::builder().build().unwrap();
+        assert_eq!(log.message(), "");
+        assert!(log.attributes().is_empty());
+    }
+
+    #[test]
+    fn lazy_attributes() {
+        let calls = Cell::new(0);
+        let checksum = |f: &mut dyn core::fmt::Write| {
+            calls.set(calls.get() + 1);
+            write!(f, "{:08x}", 0xdead_beef_u32)
+        };
+        let parts = ["a", "b", "c"];
+        let joined = |f: &mut dyn core::fmt::Write| {
+            calls.set(calls.get() + 1);
+            f.write_str(&parts.join(" "))
+        };
+
+        // Dropped builders never run the closures
+        let builder = Log::build()
+            .msg("upload")
+            .attr("level", "debug")
+            .attr_lazy("checksum", checksum)
+            .attr_lazy("parts", joined);
+        drop(builder.clone());
+        assert_eq!(calls.get(), 0);
+
+        // Neither do lazily built logs that are filtered out before being written
+        let log = builder.clone().build_lazy().unwrap();
+        assert!(!log.at_least(Level::Info));
+        assert_eq!(log.get("checksum"), Some(""));
+        drop(log);
+        assert_eq!(calls.get(), 0);
+
+        let expected = "msg=upload level=debug checksum=deadbeef parts=\"a b c\"";
+        assert_eq!(builder.clone().finish_to_string().unwrap(), expected);
+        assert_eq!(calls.get(), 2);
+
+        // Lazily built logs run the closures every time they're written
+        let log = builder.clone().build_lazy().unwrap();
+        assert_eq!(log.to_string(), expected);
+        assert_eq!(log.to_logfmt(), expected);
+        assert_eq!(calls.get(), 6);
+        assert_eq!(log.into_log().to_logfmt(), expected);
+        assert_eq!(calls.get(), 8);
+
+        // Built logs run them once to store the values
+        let log = builder.build().unwrap();
+        assert_eq!(calls.get(), 10);
+        assert_eq!(log.to_logfmt(), expected);
+        assert_eq!(calls.get(), 10);
+
+        // Replaced closures don't run either
+        let log = Log::build()
+            .attr_lazy("parts", joined)
+            .attr("parts", 3)
+            .attr_lazy("checksum", joined)
+            .attr_lazy("checksum", checksum)
+            .build()
+            .unwrap();
+        assert_eq!(log.attributes(), [("parts", "3"), ("checksum", "deadbeef")]);
+        assert_eq!(calls.get(), 11);
+    }
+
+    #[test]
+    fn message_ids() {
+        let kinds = [
+            ErrorKind::UnclosedString,
+            ErrorKind::EmptyKey,
+            ErrorKind::InvalidKey,
+            ErrorKind::EmptyValue,
+            ErrorKind::EqualsInValue,
+            ErrorKind::UnbalancedQuote,
+            ErrorKind::KeyTooLong,
+            ErrorKind::ValueTooLong,
+            ErrorKind::TooManyAttributes,
+            ErrorKind::InputTooLong,
+            ErrorKind::InvalidEscape,
+            ErrorKind::ControlCharacter,
+            ErrorKind::LineTooLong,
+            ErrorKind::InvalidUtf8,
+        ];
+        let mut ids = BTreeSet::new();
+        for kind in kinds {
+            // Fails to compile if a kind is missing from the list
+            match kind {
+                ErrorKind::UnclosedString
+                | ErrorKind::EmptyKey
+                | ErrorKind::InvalidKey
+                | ErrorKind::EmptyValue
+                | ErrorKind::EqualsInValue
+                | ErrorKind::UnbalancedQuote
+                | ErrorKind::KeyTooLong
+                | ErrorKind::ValueTooLong
+                | ErrorKind::TooManyAttributes
+                | ErrorKind::InputTooLong
+                | ErrorKind::InvalidEscape
+                | ErrorKind::ControlCharacter
+                | ErrorKind::LineTooLong
+                | ErrorKind::InvalidUtf8 => {}
+            }
+            assert!(kind.message_id().starts_with("err."));
+            assert!(ids.insert(kind.message_id()), "{kind:?}");
+        }
+
+        struct Dutch;
+
+        impl MessageRenderer for Dutch {
+            fn render(
+                &self,
+                error: &ParseError,
+                f: &mut dyn core::fmt::Write,
+            ) -> core::fmt::Result {
+                let text = match error.kind().message_id() {
+                    "err.unclosed_string" => "tekenreeks niet afgesloten",
+                    id => id,
+                };
+                write!(f, "{text} bij byte {}", error.offset())
+            }
+        }
+
+        let error = Log::parse_with("a=1 b=\"open", &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            format!("{}", error.display_with(&Dutch)),
+            "tekenreeks niet afgesloten bij byte 4"
+        );
+        assert_eq!(error.to_string(), "unclosed string at byte 4");
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        let options = EncodeOptions {
+            trailer_crc32: true,
+            ..EncodeOptions::default()
+        };
+        let log = Log::parse("msg=\"disk full\" level=error").unwrap();
+        let line = log.to_logfmt_with_options(&options).unwrap();
+        let crc = crc32(b"msg=\"disk full\" level=error");
+        assert_eq!(line, format!("msg=\"disk full\" level=error crc={crc:08x}"));
+        assert_eq!(Log::parse(&line).unwrap().verify_crc(&line), Some(true));
+        let empty = Log::parse("")
+            .unwrap()
+            .to_logfmt_with_options(&options)
+            .unwrap();
+        assert_eq!(Log::parse(&empty).unwrap().verify_crc(&empty), Some(true));
+
+        // Flipping a byte anywhere fails the check
+        for index in 0..line.len() {
+            let mut bytes = line.clone().into_bytes();
+            bytes[index] ^= 1;
+            let flipped = String::from_utf8(bytes).unwrap();
+            let verified = Log::parse(&flipped)
+                .ok()
+                .and_then(|log| log.verify_crc(&flipped));
+            assert_ne!(verified, Some(true), "{flipped}");
+        }
+
+        assert_eq!(log.verify_crc("msg=\"disk full\" level=error"), None);
+
+        // The trailer counts towards the maximum line length
+        let options = EncodeOptions {
+            max_line_len: Some(39),
+            ..options
+        };
+        let log = Log::parse("level=error code=7 path=/var").unwrap();
+        let line = log.to_logfmt_with_options(&options).unwrap();
+        assert_eq!(line.len(), 39);
+        assert!(line.starts_with("level=error truncated=true crc="));
+    }
+
+    #[test]
+    fn canonical() {
+        let log = Log::parse("msg=done zone=\"eu\" \"attempt\"=2 path=\"/x y\" code=a\\b").unwrap();
+        assert_eq!(
+            log.to_logfmt_canonical(),
+            r#"msg=done attempt=2 code="a\\b" path="/x y" zone=eu"#
+        );
+
+        // Equal attributes in any order are written the same
+        let attributes = [("b", "2"), ("a", "\"x y\""), ("c", "3"), ("d", "")];
+        let mut lines = BTreeSet::new();
+        for rotation in 0..attributes.len() {
+            for reverse in [false, true] {
+                let mut shuffled = attributes;
+                shuffled.rotate_left(rotation);
+                if reverse {
+                    shuffled.reverse();
+                }
+                let mut log = Log::builder().message("request done");
+                for (key, value) in shuffled {
+                    log = log.attribute(key, value);
+                }
+                let log = log.build().unwrap();
+                let mut sorted: Vec<_> = log.iter().collect();
+                sorted.sort_unstable();
+                let mut expected: Vec<_> = attributes.into_iter().collect();
+                expected.sort_unstable();
+                assert_eq!(sorted, expected);
+                lines.insert(log.to_logfmt_canonical());
+            }
+        }
+        assert_eq!(
+            lines.into_iter().collect::<Vec<_>>(),
+            [r#"msg="request done" a="x y" b=2 c=3 d="#]
+        );
+
+        // Repeated keys are ordered by value
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepAll,
+            ..ParseOptions::default()
+        };
+        for line in ["tag=b tag=\"a\" tag=c", "tag=c tag=a \"tag\"=b"] {
+            let log = Log::parse_with(line, &options).unwrap();
+            assert_eq!(log.to_logfmt_canonical(), "tag=a tag=b tag=c", "{line:?}");
+        }
+    }
+
+    #[test]
+    fn fluent_builder() {
+        let line = Log::build()
+            .msg("request done")
+            .attr("status", 200)
+            .attr("path", "/x y")
+            .attr("ratio", 0.5)
+            .attr("status", 404)
+            .finish_to_string()
+            .unwrap();
+        assert_eq!(line, "request done status=404 path=\"/x y\" ratio=0.5");
+
+        let log = Log::build()
+            .msg("request done")
+            .attr("status", 404)
+            .attr("path", "/x y")
+            .attr("ratio", 0.5)
+            .build()
+            .unwrap();
+        assert_eq!(log.to_logfmt(), line);
+        assert_eq!(log.get_i64("status"), Some(404));
+        assert_eq!(log.attributes(), Log::parse(&line).unwrap().attributes());
+        assert_eq!(log.get_unescaped("path").unwrap().unwrap(), "/x y");
+
+        let mut output = String::new();
+        Log::build()
+            .attr("quote", "say \"hi\"")
+            .finish_to(&mut output)
+            .unwrap();
+        assert_eq!(output, r#"quote="say \"hi\"""#);
+        assert!(matches!(
+            Log::build().attr("bad key", 1).finish_to(&mut output),
+            Err(EncodeError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn builder_validation() {
+        for (key, kind) in [
+            ("user@host", ErrorKind::InvalidKey),
+            ("", ErrorKind::EmptyKey),
+            ("\"open", ErrorKind::UnbalancedQuote),
+            (&"a".repeat(51), ErrorKind::KeyTooLong),
+        ] {
+            let error = Log::builder()
+                .attribute("ok", "1")
+                .attribute(key, "value")
+                .build()
+                .unwrap_err();
+            assert_eq!(error.key(), key);
+            assert_eq!(error.kind(), kind);
+        }
+    }
+
+    #[test]
+    fn mutation() {
+        let mut log = Log::parse("request \"password\"=hunter2 status=200").unwrap();
+        assert_eq!(log.insert("source", String::from("edge-42")), Ok(None));
+        assert_eq!(log.insert("status", "500"), Ok(Some(Cow::Borrowed("200"))));
+        assert_eq!(log.get("source"), Some("edge-42"));
+        assert_eq!(log.get("status"), Some("500"));
+
+        let removed = log.remove("password").unwrap();
+        assert_eq!(removed, ("\"password\"", "hunter2"));
+        assert_eq!(log.remove("password"), None);
+        assert_eq!(log.attributes(), [("status", "500"), ("source", "edge-42")]);
+
+        log.set_message(String::from("redacted"));
+        assert_eq!(log.message(), "redacted");
+
+        let error = log.insert("user@host", "me").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidKey);
+        assert_eq!(log.attributes().len(), 2);
+    }
+
+    #[test]
+    fn display() {
+        let log =
+            Log::parse("this is foo=bar duration=10 a value=\"with spaces\" message").unwrap();
+        assert_eq!(
+            log.to_string(),
+            "this is a message foo=bar duration=10 value=\"with spaces\""
+        );
+
+        let log = Log::builder()
+            .attribute("said", "a \"quoted\" word")
+            .attribute("path", "C:\\temp dir")
+            .attribute("empty", "")
+            .build()
+            .unwrap();
+        assert_eq!(
+            log.to_string(),
+            "said=\"a \\\"quoted\\\" word\" path=\"C:\\\\temp dir\" empty="
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for line in [
+            "msg=\"hello world\" foo=bar value=\"with spaces\"",
+            "msg=started count=3 said=\"a \\\"quoted\\\" word\"",
+            "msg=\"\" baseUrl=\"/\" hostname=localhost protocol=http",
+            "foo=bar duration=100",
+            "this is foo=bar a message",
+            "say \"hi\" twice a=1",
+        ] {
+            let log = Log::parse(line).unwrap();
+            let formatted = log.to_string();
+            let reparsed = Log::parse(&formatted).unwrap();
+            assert_eq!(reparsed.message(), log.message(), "{formatted}");
+            assert_eq!(reparsed.attributes(), log.attributes());
+            assert_eq!(reparsed, log, "{formatted}");
+        }
+
+        // Messages of words are written as words, messages of one token as `msg` value
+        let formatted = Log::parse("this is foo=bar a message").unwrap().to_string();
+        assert_eq!(formatted, "this is a message foo=bar");
+        let formatted = Log::parse("done foo=bar").unwrap().to_string();
+        assert_eq!(formatted, "msg=done foo=bar");
+    }
+
+    #[test]
+    fn write_logfmt() {
+        let log = Log::parse("msg=\"hello world\" foo=bar value=\"with spaces\"").unwrap();
+        let expected = "msg=\"hello world\" foo=bar value=\"with spaces\"";
+        assert_eq!(log.to_logfmt(), expected);
+        assert_eq!(log.to_logfmt(), log.to_string());
+
+        let mut buffer = heapless::String::<64>::new();
+        log.write_logfmt(&mut buffer).unwrap();
+        assert_eq!(buffer, expected);
+
+        // Writing fails if the buffer is too small
+        let mut buffer = heapless::String::<16>::new();
+        assert!(log.write_logfmt(&mut buffer).is_err());
+
+        // Values are quoted by the same rules as the encoder's
+        let log = Log::parse("query=a=b name=it's path=C:\\temp plain=1").unwrap();
+        let mut encoder = Encoder::new(String::new());
+        for (key, value) in &log {
+            encoder.push(key, value).unwrap();
+        }
+        let line = encoder.finish();
+        assert_eq!(
+            line,
+            "query=\"a=b\" name=\"it's\" path=\"C:\\\\temp\" plain=1"
+        );
+        assert_eq!(log.to_logfmt(), line);
+    }
+
+    #[test]
+    fn to_original() {
+        let corpus = [
+            "",
+            "  leading and trailing  ",
+            "multiple   spaces\tand\ttabs a=1\t\tb=2",
+            "key=\"quoted value with  spaces\" message words   between",
+            "msg=\"quoted message\" flag \"quoted key\"=value a=b=c",
+            "dup=1 dup=2 url=\"http://example.com?a=b\" \u{e9}t\u{e9}\r\n",
+        ];
+        for line in corpus {
+            let log = Log::parse_lossless(line).unwrap();
+            assert!(matches!(log.to_original(), Cow::Borrowed(original) if original == line));
+            assert_eq!(log.clone().into_owned().to_original(), line);
+        }
+
+        // Changed logs are written as logfmt lines
+        let mut log = Log::parse_lossless("a message   a=1").unwrap();
+        log.retain(|_, _| true);
+        assert_eq!(log.to_original(), "a message   a=1");
+        log.insert("b", "2").unwrap();
+        assert!(matches!(log.to_original(), Cow::Owned(line) if line == "a message a=1 b=2"));
+        assert_eq!(Log::parse("a  b").unwrap().to_original(), "a b");
+    }
+
+    #[test]
+    fn write_logfmt_to_slice() {
+        let log = Log::parse("msg=\"h\u{e9}llo world\" foo=bar").unwrap();
+        let expected = "msg=\"h\u{e9}llo world\" foo=bar";
+
+        let mut buffer = [0; 64];
+        let len = log.write_logfmt_to_slice(&mut buffer).unwrap();
+        assert_eq!(core::str::from_utf8(&buffer[..len]), Ok(expected));
+
+        let mut buffer = [0; 26];
+        assert_eq!(log.write_logfmt_to_slice(&mut buffer), Ok(expected.len()));
+        assert_eq!(core::str::from_utf8(&buffer), Ok(expected));
+
+        let error = BufferTooSmall {
+            required: expected.len(),
+        };
+        assert_eq!(log.write_logfmt_to_slice(&mut [0; 25]), Err(error));
+        assert_eq!(log.write_logfmt_to_slice(&mut []), Err(error));
+        assert_eq!(error.to_string(), "buffer too small, 26 bytes required");
+        assert_eq!(Log::default().write_logfmt_to_slice(&mut []), Ok(0));
+    }
+
+    #[test]
+    fn escape_control_chars() {
+        let log = Log::builder()
+            .attribute("lines", "line\nnext\tend")
+            .attribute("color", "\x1b[31mred")
+            .attribute("quoted", "\"a\rb\"")
+            .build()
+            .unwrap();
+        let line = log.to_logfmt();
+        assert_eq!(
+            line,
+            r#"lines="line\nnext\tend" color="\u{001b}[31mred" quoted="a\rb""#
+        );
+        let parsed = Log::parse(&line).unwrap();
+        for (key, value) in [("lines", "line\nnext\tend"), ("color", "\x1b[31mred")] {
+            assert_eq!(parsed.get_unescaped(key).unwrap().unwrap(), value);
+        }
+
+        // The strict option rejects control characters instead
+        let strict = EncodeOptions {
+            reject_control_chars: true,
+            ..EncodeOptions::default()
+        };
+        assert_eq!(
+            log.to_logfmt_with_options(&strict),
+            Err(EncodeError::ControlCharacter)
+        );
+        assert_eq!(
+            log.to_logfmt_with_options(&EncodeOptions::default()),
+            Ok(line)
+        );
+        let mut log = Log::parse("a=1").unwrap();
+        assert_eq!(log.to_logfmt_with_options(&strict).as_deref(), Ok("a=1"));
+        log.set_message("bell\u{7}");
+        assert_eq!(
+            log.to_logfmt_with_options(&strict),
+            Err(EncodeError::ControlCharacter)
+        );
+
+        // Keys with control characters are always rejected
+        let error = Log::builder().attribute("a\nb", "1").build().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidKey);
+    }
+
+    #[test]
+    fn truncate_values() {
+        let log = Log::parse("msg=\"disk full\" word=h\u{e9}llo quoted=\"a \\\"b\\\" c\" short=ok")
+            .unwrap();
+        let options = EncodeOptions {
+            max_value_len: Some(2),
+            ..EncodeOptions::default()
+        };
+        // The cut point is moved before the multi-byte character it would split
+        assert_eq!(
+            log.to_logfmt_with_options(&options).unwrap(),
+            "msg=\"disk full\" word=h\u{2026} quoted=\"a \u{2026}\" short=ok"
+        );
+        let options = EncodeOptions {
+            max_value_len: Some(3),
+            ..EncodeOptions::default()
+        };
+        assert_eq!(
+            log.to_logfmt_with_options(&options).unwrap(),
+            "msg=\"disk full\" word=h\u{e9}\u{2026} quoted=\"a \\\"\u{2026}\" short=ok"
+        );
+    }
+
+    #[test]
+    fn truncate_line() {
+        let log = Log::parse("msg=\"disk full\" a=1 long=aaaaaaaaaaaaaaaaaaaa").unwrap();
+        let options = |max| EncodeOptions {
+            max_line_len: Some(max),
+            ..EncodeOptions::default()
+        };
+        let to_logfmt = |max| log.to_logfmt_with_options(&options(max)).unwrap();
+        assert_eq!(
+            to_logfmt(45),
+            "msg=\"disk full\" a=1 long=aaaaaaaaaaaaaaaaaaaa"
+        );
+        assert_eq!(to_logfmt(44), "msg=\"disk full\" a=1 truncated=true");
+        assert_eq!(to_logfmt(34), "msg=\"disk full\" a=1 truncated=true");
+        assert_eq!(to_logfmt(33), "msg=\"disk full\" truncated=true");
+
+        // The message is kept, even if it doesn't fit
+        assert_eq!(to_logfmt(5), "msg=\"disk full\" truncated=true");
+        let log = Log::parse("a=1 b=2").unwrap();
+        assert_eq!(
+            log.to_logfmt_with_options(&options(5)).unwrap(),
+            "truncated=true"
+        );
+    }
+
+    #[test]
+    fn write_logfmt_with() {
+        let log = Log::parse("msg=\"disk full\" path=/var level=error").unwrap();
+        assert_eq!(
+            log.to_logfmt_with(EncodeStyle::Prose),
+            "disk full path=/var level=error"
+        );
+        assert_eq!(
+            log.to_logfmt_with(EncodeStyle::Canonical),
+            "msg=\"disk full\" path=/var level=error"
+        );
+        let log = Log::parse("msg=done").unwrap();
+        assert_eq!(log.to_logfmt_with(EncodeStyle::Canonical), "msg=\"done\"");
+
+        // Messages that would be parsed as attributes are written as msg attribute
+        let mut log = Log::parse("ratio=high").unwrap();
+        log.set_message("ratio=high achieved");
+        for style in [EncodeStyle::Prose, EncodeStyle::Canonical] {
+            let line = log.to_logfmt_with(style);
+            assert_eq!(line, "msg=\"ratio=high achieved\" ratio=high");
+            let reparsed = Log::parse(&line).unwrap();
+            assert_eq!(reparsed.message_unquoted(), log.message());
+            assert_eq!(reparsed.attributes(), log.attributes());
+        }
+        log.set_message("two  spaces");
+        assert_eq!(
+            log.to_logfmt_with(EncodeStyle::Prose),
+            "msg=\"two  spaces\" ratio=high"
+        );
+
+        // Fallback messages are skipped
+        let log = Log::parse("a=1").unwrap();
+        assert_eq!(log.to_logfmt_with(EncodeStyle::Prose), "a=1");
+        assert_eq!(log.to_logfmt_with(EncodeStyle::Canonical), "a=1");
+    }
+
+    #[test]
+    fn from_str() -> Result<(), UnclosedString> {
+        let log = "level=info done".parse::<Log>()?;
+        assert_eq!(log.message(), "done");
+        assert_eq!(log.attributes(), [("level", "info")]);
+
+        fn parse(line: &str) -> Result<Log<'static>, UnclosedString> {
+            let log = line.parse::<Log>()?;
+            Ok(log)
+        }
+        assert_eq!(parse("value=\"open"), Err(UnclosedString));
+        Ok(())
+    }
+
+    #[test]
+    fn clone() {
+        let log = Log::parse("this is foo=bar a message").unwrap();
+        assert!(matches!(log.message, Message::Owned(_)));
+        let mut copy = log.clone();
+        assert_eq!(copy, log);
+
+        copy.set_message("changed");
+        copy.insert("foo", "baz").unwrap();
+        assert_eq!(log.message(), "this is a message");
+        assert_eq!(log.get("foo"), Some("bar"));
+
+        let redacted = log.clone().with_message("[redacted]");
+        assert_eq!(redacted.message(), "[redacted]");
+        assert_eq!(redacted.attributes(), log.attributes());
+    }
+
+    #[test]
+    fn level() {
+        for (line, level) in [
+            ("level=info done", Some(Level::Info)),
+            ("lvl=WARN done", Some(Level::Warn)),
+            ("\"severity\"=\"Error\" done", Some(Level::Error)),
+            ("severity=fatal level=debug", Some(Level::Debug)),
+            ("level=trace", Some(Level::Trace)),
+            ("level=verbose", None),
+            ("done", None),
+        ] {
+            let log = Log::parse(line).unwrap();
+            assert_eq!(log.level(), level);
+        }
+
+        // The raw attribute is kept
+        let log = Log::parse("lvl=WARN done").unwrap();
+        assert_eq!(log.attributes(), [("lvl", "WARN")]);
+    }
+
+    #[test]
+    fn timestamp() {
+        let log = Log::parse("\"time\"=\"2024-05-01T12:30:00Z\" done").unwrap();
+        assert_eq!(log.timestamp(), Some("2024-05-01T12:30:00Z"));
+        assert_eq!(
+            log.timestamp_parsed(),
+            Some(Timestamp::new(1_714_566_600, 0))
+        );
+
+        let log = Log::parse("timestamp=1 ts=2").unwrap();
+        assert_eq!(log.timestamp(), Some("2"));
+
+        let log = Log::parse("done").unwrap();
+        assert_eq!(log.timestamp(), None);
+        assert_eq!(log.timestamp_parsed(), None);
+    }
+
+    #[test]
+    fn timestamp_parsed() {
+        for (value, timestamp) in [
+            ("1700000000", Some(Timestamp::new(1_700_000_000, 0))),
+            ("1700000000000", Some(Timestamp::new(1_700_000_000, 0))),
+            (
+                "1700000000123",
+                Some(Timestamp::new(1_700_000_000, 123_000_000)),
+            ),
+            ("-1500", Some(Timestamp::new(-1500, 0))),
+            ("1970-01-01T00:00:00Z", Some(Timestamp::new(0, 0))),
+            (
+                "2024-05-01T12:30:00.123Z",
+                Some(Timestamp::new(1_714_566_600, 123_000_000)),
+            ),
+            (
+                "2024-05-01T14:30:00.123456789123+02:00",
+                Some(Timestamp::new(1_714_566_600, 123_456_789)),
+            ),
+            (
+                "2024-02-29t00:00:00-01:30",
+                Some(Timestamp::new(1_709_170_200, 0)),
+            ),
+            ("2023-02-29T00:00:00Z", None),
+            ("2024-05-01T24:00:00Z", None),
+            ("2024-05-01T12:30:00", None),
+            ("2024-05-01", None),
+            ("yesterday", None),
+        ] {
+            let line = format!("ts={value}");
+            let log = Log::parse(&line).unwrap();
+            assert_eq!(log.timestamp_parsed(), timestamp, "{value}");
+        }
+
+        // Nanoseconds are carried into the seconds, without overflowing
+        let timestamp = Timestamp::new(1, 2_500_000_000);
+        assert_eq!((timestamp.secs(), timestamp.nanos()), (3, 500_000_000));
+        let timestamp = Timestamp::new(i64::MAX, 1_500_000_000);
+        assert_eq!(
+            (timestamp.secs(), timestamp.nanos()),
+            (i64::MAX, 500_000_000)
+        );
+    }
+
+    #[test]
+    fn unquoted() {
+        assert_eq!(unquote("\"with spaces\""), "with spaces");
+        assert_eq!(unquote("\"\"nested\"\""), "\"nested\"");
+        assert_eq!(unquote("\"half"), "\"half");
+        assert_eq!(unquote("\""), "\"");
+        assert_eq!(unquote("bare"), "bare");
+
+        let log = Log::parse("value=\"with spaces\" other=bare msg=\"a message\"").unwrap();
+        assert_eq!(log.get("value"), Some("\"with spaces\""));
+        assert_eq!(log.get_unquoted("value"), Some("with spaces"));
+        assert_eq!(log.get_unquoted("other"), Some("bare"));
+        assert_eq!(log.get_unquoted("missing"), None);
+        assert_eq!(log.message(), "\"a message\"");
+        assert_eq!(log.message_unquoted(), "a message");
+    }
+
+    #[test]
+    fn error() {
+        let log = Log::parse("level=info err=\"timeout\" request failed").unwrap();
+        assert_eq!(log.error(), Some("timeout"));
+        assert!(log.is_error());
+
+        let log = Log::parse("error=refused err=timeout").unwrap();
+        assert_eq!(log.error(), Some("timeout"));
+
+        // nil and null are the empty error values of common languages
+        for line in ["error=nil", "err=NULL", "err=\"\""] {
+            let log = Log::parse(line).unwrap();
+            assert!(log.error().is_some(), "{line}");
+            assert!(!log.is_error(), "{line}");
+        }
+
+        let log = Log::parse("level=fatal error=nil").unwrap();
+        assert!(log.is_error());
+        let log = Log::parse("lvl=error disk full").unwrap();
+        assert_eq!(log.error(), None);
+        assert!(log.is_error());
+        let log = Log::parse("level=warn disk almost full").unwrap();
+        assert!(!log.is_error());
+
+        let logs = ["level=info ok", "err=timeout failed"].map(|line| Log::parse(line).unwrap());
+        assert_eq!(logs.iter().filter(|log| log.is_error()).count(), 1);
+    }
+
+    #[test]
+    fn merge() {
+        let context = Log::parse("region=eu instance=\"i-1\" request=ctx context").unwrap();
+
+        let mut log = Log::parse("request=42 handled").unwrap();
+        log.merge(&context, MergePolicy::KeepExisting, None);
+        assert_eq!(log.message(), "handled");
+        assert_eq!(
+            log.attributes(),
+            [("request", "42"), ("region", "eu"), ("instance", "\"i-1\"")]
+        );
+
+        let mut log = Log::parse("\"request\"=42").unwrap();
+        log.set_message("");
+        log.merge(&context, MergePolicy::Overwrite, None);
+        assert_eq!(log.message(), "context");
+        assert_eq!(
+            log.attributes(),
+            [
+                ("\"request\"", "ctx"),
+                ("region", "eu"),
+                ("instance", "\"i-1\"")
+            ]
+        );
+
+        // New attributes past the cap are dropped, existing ones can still be overwritten
+        let mut full = Log::builder();
+        for index in 0..DEFAULT_MAX_ATTRIBUTES {
+            full = full.attribute(format!("key{index}"), "old");
+        }
+        let mut log = full.build().unwrap();
+        let other = Log::parse("key0=new extra=value").unwrap();
+        log.merge(&other, MergePolicy::Overwrite, Some(DEFAULT_MAX_ATTRIBUTES));
+        assert_eq!(log.attributes().len(), DEFAULT_MAX_ATTRIBUTES);
+        assert_eq!(log.get("key0"), Some("new"));
+        assert!(!log.contains_key("extra"));
+
+        // Without a cap, logs can be merged past the default maximum
+        log.merge(&other, MergePolicy::KeepExisting, None);
+        assert_eq!(log.attributes().len(), DEFAULT_MAX_ATTRIBUTES + 1);
+        assert_eq!(log.get("extra"), Some("value"));
+    }
+
+    #[test]
+    fn retain() {
+        let line = "password=hunter2 user=bob authorization=\"Bearer x\" path=/ logged in";
+        let log = Log::parse(line).unwrap();
+        let redacted = |key: &str, _: &str| !matches!(key, "password" | "authorization");
+        assert_eq!(
+            log.filtered(redacted).collect::<Vec<_>>(),
+            [("user", "bob"), ("path", "/")]
+        );
+        assert_eq!(log.attributes().len(), 4);
+
+        let mut log = log;
+        log.retain(redacted);
+        assert_eq!(log.attributes(), [("user", "bob"), ("path", "/")]);
+
+        log.retain(|_, _| false);
+        assert!(log.attributes().is_empty());
+        assert_eq!(log.message(), "logged in");
+    }
+
+    #[test]
+    fn get_all() {
+        let line = "tag=a other=1 tag=b tag=c";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.get_all("tag").collect::<Vec<_>>(), ["c"]);
+        assert_eq!(log.get_all("missing").count(), 0);
+
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepAll,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.get_all("tag").collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(log.get_all("\"other\"").collect::<Vec<_>>(), ["1"]);
+    }
+
+    #[test]
+    fn into_parts() {
+        // Without a message, the borrowed input is used as the message
+        let line = "foo=bar duration=10";
+        let (message, attributes) = Log::parse(line).unwrap().into_parts();
+        assert!(matches!(message, Cow::Borrowed(message) if message == line));
+        assert_eq!(attributes, [("foo", "bar"), ("duration", "10")]);
+
+        let message = Log::parse(line).unwrap().into_message();
+        assert!(matches!(message, Cow::Borrowed(message) if message == line));
+
+        let log = Log::builder()
+            .message(String::from("owned"))
+            .build()
+            .unwrap();
+        assert!(matches!(log.into_message(), Cow::Owned(message) if message == "owned"));
+    }
+
+    #[test]
+    fn attributes_map() {
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepAll,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("b=1 a=2 \"a\"=3 b=4", &options).unwrap();
+        let map = log.attributes_map();
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            [("\"a\"", "3"), ("a", "2"), ("b", "4")]
+        );
+        assert_eq!(BTreeMap::from(&log), log.attributes_map());
+    }
+
+    #[test]
+    fn ordering() {
+        let logs = [
+            "b a=1",
+            "a b=1",
+            "a a=2",
+            "a a=1 b=1",
+            "a b=1 a=1",
+            "a a=1",
+            "a a=1",
+        ]
+        .map(|line| Log::parse(line).unwrap());
+        let set = logs.iter().collect::<BTreeSet<_>>();
+        assert_eq!(
+            set.into_iter().map(Log::to_logfmt).collect::<Vec<_>>(),
+            [
+                "msg=a a=1",
+                "msg=a a=1 b=1",
+                "msg=a a=2",
+                "msg=a b=1",
+                "msg=a b=1 a=1",
+                "msg=b a=1",
+            ]
+        );
+
+        // Attribute order is significant, consistent with equality
+        assert_ne!(logs[3], logs[4]);
+        assert_ne!(logs[3].cmp(&logs[4]), Ordering::Equal);
+        let hash = |log: &Log<'_>| {
+            let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+            log.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&logs[5]), hash(&logs[6]));
+        assert_ne!(hash(&logs[3]), hash(&logs[4]));
+    }
+
+    /// A minimal FNV-1a hasher, since `std`'s hashers aren't available
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    #[test]
+    fn message_is_fallback() {
+        let log = Log::parse("foo=bar duration=100").unwrap();
+        assert_eq!(log.message(), "foo=bar duration=100");
+        assert!(log.message_is_fallback());
+        assert!(log.has_attributes());
+        assert_eq!(log.attribute_count(), 2);
+        assert!(log.to_owned_log().message_is_fallback());
+        assert_eq!(log.to_string(), "foo=bar duration=100");
+
+        let log = Log::parse("foo=bar msg=\"a message\"").unwrap();
+        assert!(!log.message_is_fallback());
+        assert_eq!(log.attribute_count(), 1);
+
+        let log = Log::parse("a message foo=bar").unwrap();
+        assert!(!log.message_is_fallback());
+
+        let mut log = Log::parse("just words").unwrap();
+        assert!(!log.message_is_fallback());
+        assert!(!log.is_empty());
+        assert!(!log.has_attributes());
+        assert_eq!(log.attribute_count(), 0);
+
+        log = Log::parse("foo=bar").unwrap().with_message("replaced");
+        assert!(!log.message_is_fallback());
+        assert!(Log::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rename_key() {
+        let mut log = Log::parse("dur=5 status=200 handled").unwrap();
+        assert!(log.rename_key("dur", "duration"));
+        assert!(!log.rename_key("missing", "other"));
+        assert_eq!(log.attributes(), [("duration", "5"), ("status", "200")]);
+
+        // Renaming onto an existing key keeps the renamed value
+        let mut log = Log::parse("duration=1 \"elapsed\"=2 status=200").unwrap();
+        assert!(log.rename_key("elapsed", "duration"));
+        assert_eq!(log.attributes(), [("duration", "2"), ("status", "200")]);
+        assert_eq!(log.message(), "duration=1 \"elapsed\"=2 status=200");
+
+        let mut log = Log::parse("dur=1 duration_ms=2 elapsed=3 x=y").unwrap();
+        log.remap_keys(&[("dur", "duration"), ("duration_ms", "duration"), ("x", "z")]);
+        assert_eq!(
+            log.attributes(),
+            [("duration", "2"), ("elapsed", "3"), ("z", "y")]
+        );
+    }
+
+    #[test]
+    fn sort_attributes() {
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepAll,
+            ..ParseOptions::default()
+        };
+        let mut log = Log::parse_with("tag=z b=1 tag=a a=2 tag=m done", &options).unwrap();
+        let expected = [
+            ("a", "2"),
+            ("b", "1"),
+            ("tag", "z"),
+            ("tag", "a"),
+            ("tag", "m"),
+        ];
+        assert_eq!(log.attributes_sorted(), expected);
+        assert_eq!(log.attributes()[0], ("tag", "z"));
+
+        log.sort_attributes();
+        assert_eq!(log.attributes(), expected);
+        assert_eq!(log.message(), "done");
+    }
+
+    #[test]
+    fn owned_log() -> Result<(), UnclosedString> {
+        let owned = {
+            let line = String::from("this is foo=bar a \"key\"=\"with spaces\" message");
+            OwnedLog::parse(&line)?
+        };
+        assert_eq!(owned.message(), "this is a message");
+        assert_eq!(
+            owned.attributes(),
+            [
+                (Arc::from("foo"), "bar".to_string()),
+                (Arc::from("\"key\""), "\"with spaces\"".to_string())
+            ]
+        );
+        assert_eq!(
+            owned.iter().collect::<Vec<_>>(),
+            [("foo", "bar"), ("\"key\"", "\"with spaces\"")]
+        );
+        assert_eq!(owned.get("key"), Some("\"with spaces\""));
+        assert_eq!(owned.clone(), owned);
+
+        let log = Log::from(owned.clone());
+        assert_eq!(log, owned.as_log());
+        assert_eq!(OwnedLog::from(log), owned);
+
+        // The fallback message survives the conversions
+        let owned = OwnedLog::parse("foo=bar")?;
+        assert_eq!(owned.as_log().to_string(), "foo=bar");
+        assert!(Log::from(owned).message_is_fallback());
+        Ok(())
+    }
+
+    #[test]
+    fn replace_message() {
+        let mut log = Log::parse("user=bob password=hunter2 login with password=hunter2").unwrap();
+        let message = log.take_message();
+        assert_eq!(message, "login with");
+        assert_eq!(log.message(), "");
+        assert_eq!(log.to_string(), "user=bob password=hunter2");
+
+        let old = log.replace_message(message.replace("with", "without"));
+        assert_eq!(old, "");
+        assert_eq!(log.attributes(), [("user", "bob"), ("password", "hunter2")]);
+        let line = log.to_string();
+        let reparsed = Log::parse(&line).unwrap();
+        assert_eq!(reparsed.message_unquoted(), "login without");
+        assert_eq!(reparsed.attributes(), log.attributes());
+
+        // A replaced fallback message is written
+        let mut log = Log::parse("user=bob").unwrap();
+        assert_eq!(log.replace_message("logged in"), "user=bob");
+        assert_eq!(log.to_string(), "logged in user=bob");
+        assert_eq!(log.take_message(), "logged in");
+        assert_eq!(log.to_string(), "user=bob");
+    }
+
+    #[test]
+    fn spans() {
+        let options = ParseOptions {
+            record_spans: true,
+            bare_words_as_flags: FlagWords::Only(&["cached"]),
+            ..ParseOptions::default()
+        };
+        let line = "this is  foo=bar \"key\"=\"with spaces\" a cached foo=baz message";
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.attributes().len(), log.spans().len());
+        for (attribute, span) in log.attributes().iter().zip(log.spans()) {
+            let (key, value) = span.unwrap();
+            assert_eq!(&line[key], attribute.key());
+            if attribute.key() != "cached" {
+                assert_eq!(&line[value], attribute.value());
+            }
+        }
+        // Overwritten duplicates keep the span of the first key
+        assert_eq!(log.attributes()[0].span(), Some((9..12, 50..53)));
+        assert_eq!(log.attributes()[2].span(), Some((39..45, 45..45)));
+        let words = log
+            .message_spans()
+            .iter()
+            .map(|span| &line[span.clone()])
+            .collect::<Vec<_>>();
+        assert_eq!(words, ["this", "is", "a", "message"]);
+
+        // The message property and the fallback message are single spans
+        let line = "msg=\"a message\" foo=bar";
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message_spans(), &[Range { start: 4, end: 15 }]);
+        let line = " foo=bar ";
+        let log = Log::parse_with(line, &options).unwrap();
+        assert_eq!(log.message_spans(), &[Range { start: 0, end: 9 }]);
+
+        // Spans aren't recorded by default, or for modified attributes
+        assert!(Log::parse(line).unwrap().spans().all(|span| span.is_none()));
+        let mut log = Log::parse_with("foo=bar a=b", &options).unwrap();
+        log.insert("foo", "new").unwrap();
+        assert_eq!(
+            log.spans().collect::<Vec<_>>(),
+            [None, Some((8..9, 10..11))]
+        );
+
+        // Spans don't affect equality
+        let mut unspanned = Log::parse("foo=bar a=b").unwrap();
+        unspanned.insert("foo", "new").unwrap();
+        assert_eq!(log, unspanned);
+    }
+
+    #[test]
+    fn index() {
+        let log = Log::parse("duration=10 \"baseUrl\"=\"/\" done").unwrap();
+        assert_eq!(&log["duration"], "10");
+        assert_eq!(&log["baseUrl"], "\"/\"");
+        assert_eq!(&log["\"duration\""], "10");
+        assert_eq!(log.nth_attribute(1), Some(("\"baseUrl\"", "\"/\"")));
+        assert_eq!(log.nth_attribute(2), None);
+    }
+
+    #[test]
+    #[should_panic = "no attribute with key `missing`"]
+    fn index_missing() {
+        let log = Log::parse("duration=10").unwrap();
+        let _ = &log["missing"];
+    }
+
+    #[test]
+    fn debug() {
+        let log = Log::parse("this is foo=bar a \"key\"=\"with spaces\" message").unwrap();
+        assert_eq!(
+            format!("{log:?}"),
+            r#"Log { message: "this is a message", attributes: {"foo": "bar", "\"key\"": "\"with spaces\""} }"#
+        );
+        assert_eq!(
+            format!("{log:#?}"),
+            r#"Log {
+    message: "this is a message",
+    attributes: {
+        "foo": "bar",
+        "\"key\"": "\"with spaces\"",
+    },
+}"#
+        );
+        assert_eq!(
+            format!("{:?}", Log::parse("").unwrap()),
+            r#"Log { message: "", attributes: {} }"#
+        );
+    }
+
+    #[test]
+    fn with_prefix() {
+        let line = "http=1 http.method=GET \"http.status\"=200 https.port=443 db.query_ms=5 done";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(
+            log.with_prefix("http").collect::<Vec<_>>(),
+            [("", "1"), ("method", "GET"), ("status", "200")]
+        );
+        assert_eq!(
+            log.with_prefix("db").collect::<Vec<_>>(),
+            [("query_ms", "5")]
+        );
+        assert_eq!(log.with_prefix("query").count(), 0);
+        assert_eq!(log.prefixes(), ["http", "https", "db"]);
+    }
+
+    #[test]
+    fn quoted_duplicates() {
+        let line = "foo=1 \"foo\"=2 \"bar\"=3 bar=4 baz=5";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(
+            log.attributes(),
+            [("foo", "2"), ("\"bar\"", "4"), ("baz", "5")]
+        );
+        assert_eq!(log.get("\"foo\""), Some("2"));
+        assert!(log.contains_key("\"baz\""));
+
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepFirst,
+            single_quotes: true,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("foo=1 'foo'=2 \"foo\"=3", &options).unwrap();
+        assert_eq!(log.attributes(), [("foo", "1")]);
+
+        // Single quoted keys are looked up like double quoted ones
+        let log = Log::parse_with("'foo'=1 \"bar\"=2", &options).unwrap();
+        assert_eq!(log.get("foo"), Some("1"));
+        assert_eq!(log.get("'bar'"), Some("2"));
+
+        let mut log = Log::parse(line).unwrap();
+        assert!(log.rename_key("\"baz\"", "foo"));
+        assert_eq!(log.attributes(), [("\"bar\"", "4"), ("foo", "5")]);
+        assert_eq!(
+            log.remove("\"foo\""),
+            Some(Attribute::new("foo".into(), "5".into()))
+        );
+    }
+
+    #[test]
+    fn typed_attributes() {
+        let line = "count=42 quoted=\"42\" enabled=true off=false ratio=-0.5 big=1e3 \
+            none=null nil=nil name=bob inf=inf plus=+7 said=\"a \\\"b\\\"\"";
+        let log = Log::parse(line).unwrap();
+        let values = log
+            .typed_attributes()
+            .map(|(key, value)| (key, value.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                ("count", Value::Int(42)),
+                ("quoted", Value::QuotedStr("42")),
+                ("enabled", Value::Bool(true)),
+                ("off", Value::Bool(false)),
+                ("ratio", Value::Float(-0.5)),
+                ("big", Value::Float(1000.0)),
+                ("none", Value::Null),
+                ("nil", Value::Null),
+                ("name", Value::Str("bob")),
+                ("inf", Value::Str("inf")),
+                ("plus", Value::Int(7)),
+                ("said", Value::QuotedStr("a \\\"b\\\"")),
+            ]
+        );
+
+        // The raw value stays available
+        let count = log.get_typed("quoted").unwrap();
+        assert_eq!(count.raw(), "\"42\"");
+        assert_eq!(log.get_typed("count").unwrap().raw(), "42");
+        assert_eq!(log.get_typed("missing"), None);
+    }
+
+    #[test]
+    fn typed_getters() {
+        let line = "duration=10ms status=\"200\" retries=+3 offset=-4 ratio=0.25 cached=TRUE \
+            stale=0 fresh=1 empty= flag=yes";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.get_i64("duration"), None);
+        assert_eq!(log.get_u64("duration"), None);
+        assert_eq!(log.get_f64("duration"), None);
+        assert_eq!(log.get_i64("status"), Some(200));
+        assert_eq!(log.get_u64("status"), Some(200));
+        assert_eq!(log.get_i64("retries"), Some(3));
+        assert_eq!(log.get_u64("retries"), Some(3));
+        assert_eq!(log.get_i64("offset"), Some(-4));
+        assert_eq!(log.get_u64("offset"), None);
+        assert_eq!(log.get_f64("offset"), Some(-4.0));
+        assert_eq!(log.get_f64("ratio"), Some(0.25));
+        assert_eq!(log.get_i64("ratio"), None);
+        assert_eq!(log.get_bool("cached"), Some(true));
+        assert_eq!(log.get_bool("stale"), Some(false));
+        assert_eq!(log.get_bool("fresh"), Some(true));
+        assert_eq!(log.get_bool("empty"), None);
+        assert_eq!(log.get_bool("flag"), None);
+        assert_eq!(log.get_i64("missing"), None);
+    }
+
+    #[test]
+    fn get_duration() {
+        for (value, duration) in [
+            ("1h2m3s", Some(Duration::from_secs(3723))),
+            ("12.7ms", Some(Duration::from_micros(12_700))),
+            ("500\u{b5}s", Some(Duration::from_micros(500))),
+            ("500us", Some(Duration::from_micros(500))),
+            ("\"1m3.2s\"", Some(Duration::from_millis(63_200))),
+            ("+.5s", Some(Duration::from_millis(500))),
+            ("1.s", Some(Duration::from_secs(1))),
+            ("0", Some(Duration::ZERO)),
+            ("42ns", Some(Duration::from_nanos(42))),
+            ("0.0000000015s", Some(Duration::from_nanos(1))),
+            ("10", None),
+            ("-1s", None),
+            ("1d", None),
+            ("s", None),
+            (".s", None),
+            ("\"1h ago\"", None),
+            ("", None),
+            ("99999999999999999999h", None),
+        ] {
+            let line = format!("duration={value}");
+            let log = Log::parse(&line).unwrap();
+            assert_eq!(log.get_duration("duration"), duration, "{value}");
+        }
+    }
+
+    #[test]
+    fn get_bytes() {
+        for (value, bytes) in [
+            ("512", Some(512)),
+            ("512B", Some(512)),
+            ("10KB", Some(10_000)),
+            ("10kb", Some(10_000)),
+            ("10KiB", Some(10_240)),
+            ("\"1.5MiB\"", Some(1_572_864)),
+            ("2GB", Some(2_000_000_000)),
+            ("3gib", Some(3 << 30)),
+            ("1.5KB", Some(1500)),
+            // Fractions of a byte are truncated
+            ("1.0005KB", Some(1000)),
+            ("0.5B", Some(0)),
+            (".5KiB", Some(512)),
+            ("18446744073709551615", Some(u64::MAX)),
+            ("18446744073709551616", Some(u64::MAX)),
+            ("16EiB", Some(u64::MAX)),
+            ("15EiB", Some(15 << 60)),
+            ("999999999999999999999999999999999999999999", Some(u64::MAX)),
+            ("\"10 KB\"", None),
+            ("10XB", None),
+            ("KB", None),
+            ("-1KB", None),
+            ("", None),
+        ] {
+            let line = format!("size={value}");
+            let log = Log::parse(&line).unwrap();
+            assert_eq!(log.get_bytes("size"), bytes, "{value}");
+        }
+    }
+
+    #[test]
+    fn get_timestamp() {
+        for (value, timestamp) in [
+            ("1700000000", Some(Timestamp::new(1_700_000_000, 0))),
+            ("17000000000", Some(Timestamp::new(17_000_000_000, 0))),
+            ("170000000000", Some(Timestamp::new(170_000_000, 0))),
+            (
+                "1700000000123456",
+                Some(Timestamp::new(1_700_000_000, 123_456_000)),
+            ),
+            ("-1500", Some(Timestamp::new(-1500, 0))),
+            (
+                "-1500000000001",
+                Some(Timestamp::new(-1_500_000_001, 999_000_000)),
+            ),
+            // Like `UnixNano` timestamps of OpenTelemetry
+            (
+                "1700000000123456789",
+                Some(Timestamp::new(1_700_000_000, 123_456_789)),
+            ),
+            (
+                "170000000012345678",
+                Some(Timestamp::new(170_000_000, 12_345_678)),
+            ),
+            ("17000000001234567890", None),
+            ("0", Some(Timestamp::new(0, 0))),
+            (
+                "\"1969-12-31T23:59:59.5Z\"",
+                Some(Timestamp::new(-1, 500_000_000)),
+            ),
+            ("2000-02-29T00:00:00Z", Some(Timestamp::new(951_782_400, 0))),
+            ("1900-02-29T00:00:00Z", None),
+            (
+                "2024-12-31T23:30:00-01:00",
+                Some(Timestamp::new(1_735_691_400, 0)),
+            ),
+            (
+                "2025-01-01T00:30:00+01:00",
+                Some(Timestamp::new(1_735_687_800, 0)),
+            ),
+            ("2024-05-01T12:30:00+24:00", None),
+        ] {
+            let line = format!("at={value} ts=0");
+            let log = Log::parse(&line).unwrap();
+            assert_eq!(log.get_timestamp("at"), timestamp, "{value}");
+            assert_eq!(log.timestamp_value(), Some(Timestamp::new(0, 0)));
+        }
+        assert_eq!(Log::parse("done").unwrap().timestamp_value(), None);
+    }
+
+    #[test]
+    fn get_opt() {
+        let line = "user=null trace_id=- parent=nil span= name=\"null\" quoted=\"\" id=7";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.get_opt("user"), Some(None));
+        assert_eq!(log.get_opt("trace_id"), Some(None));
+        assert_eq!(log.get_opt("parent"), Some(None));
+        assert_eq!(log.get_opt("span"), Some(None));
+        assert_eq!(log.get_opt("name"), Some(Some("\"null\"")));
+        assert_eq!(log.get_opt("quoted"), Some(Some("\"\"")));
+        assert_eq!(log.get_opt("id"), Some(Some("7")));
+        assert_eq!(log.get_opt("missing"), None);
+
+        assert_eq!(log.get_opt_with("user", &["-"]), Some(Some("null")));
+        assert_eq!(log.get_opt_with("trace_id", &["-"]), Some(None));
+        assert_eq!(log.get_opt_with("name", &["\"null\""]), Some(None));
+    }
+
+    #[test]
+    fn radix_getters() {
+        let line = "status=0x1F mode=0o755 flags=0B101 upper=0XfF max=0xFFFFFFFFFFFFFFFF \
+            negative=-0x80 min=-0x8000000000000000 plain=+42 signed=0x-1 empty=0x bad=0o8";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.get_u64_radix("status"), Some(31));
+        assert_eq!(log.get_u64_radix("mode"), Some(0o755));
+        assert_eq!(log.get_u64_radix("flags"), Some(5));
+        assert_eq!(log.get_i64_radix("upper"), Some(255));
+        assert_eq!(log.get_u64_radix("max"), Some(u64::MAX));
+        assert_eq!(log.get_i64_radix("max"), None);
+        assert_eq!(log.get_i64_radix("negative"), Some(-128));
+        assert_eq!(log.get_u64_radix("negative"), None);
+        assert_eq!(log.get_i64_radix("min"), Some(i64::MIN));
+        assert_eq!(log.get_i64_radix("plain"), Some(42));
+        assert_eq!(log.get_u64_radix("plain"), Some(42));
+        assert_eq!(log.get_i64_radix("signed"), None);
+        assert_eq!(log.get_u64_radix("empty"), None);
+        assert_eq!(log.get_u64_radix("bad"), None);
+
+        // The plain getters stay decimal
+        assert_eq!(log.get_u64("status"), None);
+    }
+
+    #[test]
+    fn get_bool_with() {
+        let line = "tls=on debug=YES cache=0 verbose=t quiet=F off=off no=No \
+            quoted=\"on\" ambiguous=ok strict=true";
+        let log = Log::parse(line).unwrap();
+        for (key, value) in [
+            ("tls", Some(true)),
+            ("debug", Some(true)),
+            ("cache", Some(false)),
+            ("verbose", Some(true)),
+            ("quiet", Some(false)),
+            ("off", Some(false)),
+            ("no", Some(false)),
+            ("quoted", Some(true)),
+            ("ambiguous", None),
+            ("strict", Some(true)),
+            ("missing", None),
+        ] {
+            assert_eq!(log.get_bool_with(key, BoolStyle::Lenient), value, "{key}");
+        }
+
+        // The strict style doesn't accept synonyms
+        assert_eq!(log.get_bool("tls"), None);
+        assert_eq!(log.get_bool("verbose"), None);
+        assert_eq!(log.get_bool_with("debug", BoolStyle::Strict), None);
+        assert_eq!(log.get_bool("cache"), Some(false));
+        assert_eq!(log.get_bool("strict"), Some(true));
+    }
+
+    #[test]
+    fn level_ordering() {
+        assert!(Level::Trace < Level::Debug);
+        assert!(Level::Warn < Level::Error && Level::Error < Level::Fatal);
+        for (name, level) in [
+            ("warning", Level::Warn),
+            ("WARN", Level::Warn),
+            ("err", Level::Error),
+            ("critical", Level::Fatal),
+            ("Crit", Level::Fatal),
+            ("dbg", Level::Debug),
+            ("notice", Level::Info),
+        ] {
+            assert_eq!(name.parse(), Ok(level), "{name}");
+        }
+        assert_eq!("verbose".parse::<Level>(), Err(UnknownLevel));
+        for level in [Level::Trace, Level::Info, Level::Fatal] {
+            assert_eq!(level.as_str().parse(), Ok(level));
+            assert_eq!(level.to_string(), level.as_str());
+        }
+
+        let logs = ["level=debug a", "level=warning b", "lvl=err c", "d"]
+            .map(|line| Log::parse(line).unwrap());
+        let routed = logs
+            .iter()
+            .filter(|log| log.at_least(Level::Warn))
+            .map(Log::message)
+            .collect::<Vec<_>>();
+        assert_eq!(routed, ["b", "c"]);
+        assert!(logs[1].level().is_some_and(|level| level >= Level::Warn));
+        assert!(!logs[3].at_least(Level::Trace));
+    }
+
+    #[test]
+    fn get_ip() {
+        let line = "remote=10.1.2.3:443 client=2001:db8::1 local=\"[::1]:8080\" \
+            bracketed=[::1] ipv4=127.0.0.1 host=localhost:80 bad=10.1.2";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.get_ip("ipv4"), Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert_eq!(
+            log.get_ip("client"),
+            Some(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)))
+        );
+        assert_eq!(
+            log.get_ip("bracketed"),
+            Some(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        );
+        assert_eq!(
+            log.get_ip("remote"),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)))
+        );
+        assert_eq!(log.get_ip("host"), None);
+        assert_eq!(log.get_ip("bad"), None);
+        assert_eq!(log.get_ip("missing"), None);
+
+        assert_eq!(
+            log.get_socket_addr("remote"),
+            Some(SocketAddr::new(Ipv4Addr::new(10, 1, 2, 3).into(), 443))
+        );
+        assert_eq!(
+            log.get_socket_addr("local"),
+            Some(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 8080))
+        );
+        assert_eq!(log.get_socket_addr("ipv4"), None);
+        assert_eq!(log.get_socket_addr("host"), None);
+    }
+
+    #[test]
+    fn get_percent() {
+        let line = "cpu=87% hit_ratio=0.93 progress=\"45 %\" raw=87 full=1 none=0 over=150% \
+            negative=-0.5 inf=inf% nan=NaN empty=% spaced=\" 5%\"";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(log.get_percent("cpu"), Some(0.87));
+        assert_eq!(log.get_percent("hit_ratio"), Some(0.93));
+        assert_eq!(log.get_percent("progress"), Some(0.45));
+        assert_eq!(log.get_percent("raw"), None);
+        assert_eq!(log.get_percent("full"), Some(1.0));
+        assert_eq!(log.get_percent("none"), Some(0.0));
+        assert_eq!(log.get_percent("over"), Some(1.5));
+        assert_eq!(log.get_percent("negative"), None);
+        assert_eq!(log.get_percent("inf"), None);
+        assert_eq!(log.get_percent("nan"), None);
+        assert_eq!(log.get_percent("empty"), None);
+        assert_eq!(log.get_percent("spaced"), None);
+    }
+
+    #[test]
+    fn unescaped() {
+        assert!(matches!(unescape("\"plain\""), Ok(Cow::Borrowed("plain"))));
+        assert!(matches!(unescape("\"\""), Ok(Cow::Borrowed(""))));
+        assert!(matches!(unescape("bare"), Ok(Cow::Borrowed("bare"))));
+        assert_eq!(
+            unescape(r#""a \"b\" \\ \n\t\r \u00e9 \u{1F600}""#),
+            Ok(Cow::Owned("a \"b\" \\ \n\t\r \u{e9} \u{1F600}".into()))
+        );
+        for (value, offset) in [
+            (r#""trailing\""#, 9),
+            (r"trailing\", 8),
+            (r#""\x""#, 1),
+            (r#""ok \u12""#, 4),
+            (r#""\u{}""#, 1),
+            (r#""\u{1234567}""#, 1),
+            (r#""\u{110000}""#, 1),
+            (r#""\u{12""#, 1),
+            (r#""\ud800""#, 1),
+        ] {
+            assert_eq!(
+                unescape(value).map_err(|error| error.offset()),
+                Err(offset),
+                "{value}"
+            );
+        }
+        assert_eq!(
+            unescape(r"\q").unwrap_err().to_string(),
+            "invalid escape sequence at byte 0"
+        );
+
+        let log = Log::parse(r#"said="a \"quoted\" word" path=C:\\temp bad="\x""#).unwrap();
+        assert_eq!(
+            log.get_unescaped("said"),
+            Some(Ok("a \"quoted\" word".into()))
+        );
+        assert_eq!(log.get_unescaped("path"), Some(Ok(r"C:\temp".into())));
+        assert_eq!(
+            log.get_unescaped("bad"),
+            Some(Err(UnescapeError { offset: 1 }))
+        );
+        assert_eq!(log.get_unescaped("missing"), None);
+    }
+
+    #[test]
+    fn typed_iter() {
+        let log = Log::parse("count=3 ratio=0.5 name=bob size=\"7\" retries=2").unwrap();
+        let sum = log
+            .typed_iter()
+            .filter_map(|(_, value)| match value {
+                Value::Int(int) => Some(int as f64),
+                Value::Float(float) => Some(float),
+                _ => None,
+            })
+            .sum::<f64>();
+        assert_eq!(sum, 5.5);
+
+        let mut iter = log.typed_iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next_back(), Some(("retries", Value::Int(2))));
+        let rest = iter.clone().collect::<Vec<_>>();
+        assert_eq!(rest.len(), 4);
+        assert_eq!(rest[3], ("size", Value::QuotedStr("7")));
+        assert_eq!(iter.next(), Some(("count", Value::Int(3))));
+    }
+
+    #[test]
+    fn get_number() {
+        for (value, number) in [
+            ("42", Some(Number::Int(42))),
+            ("-0", Some(Number::Int(0))),
+            ("-0.0", Some(Number::Float(-0.0))),
+            ("\"-7\"", Some(Number::Int(-7))),
+            (
+                "9223372036854775808",
+                Some(Number::UInt(9_223_372_036_854_775_808)),
+            ),
+            ("18446744073709551615", Some(Number::UInt(u64::MAX))),
+            ("99999999999999999999", Some(Number::Float(1e20))),
+            (
+                "-9223372036854775809",
+                Some(Number::Float(-9_223_372_036_854_775_809.0)),
+            ),
+            ("1e9", Some(Number::Float(1e9))),
+            ("2.5E-3", Some(Number::Float(0.0025))),
+            ("1e400", None),
+            ("inf", None),
+            ("-infinity", None),
+            ("NaN", None),
+            ("10ms", None),
+            ("", None),
+        ] {
+            let line = format!("n={value}");
+            let log = Log::parse(&line).unwrap();
+            assert_eq!(log.get_number("n"), number, "{value}");
+        }
+
+        // Scientific notation isn't an integer
+        let log = Log::parse("duration_ms=1e9").unwrap();
+        assert_eq!(log.get_i64("duration_ms"), None);
+        assert_eq!(log.get_f64("duration_ms"), Some(1e9));
+    }
+
+    #[test]
+    fn get_with() {
+        // Decodes a 16 byte hexadecimal ID
+        fn hex_id(value: &str) -> Option<[u8; 16]> {
+            let mut id = [0; 16];
+            if value.len() != 32 {
+                return None;
+            }
+            for (byte, digits) in id.iter_mut().zip(value.as_bytes().chunks(2)) {
+                *byte = u8::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()?;
+            }
+            Some(id)
+        }
+
+        let line = "trace=\"0123456789abcdef0123456789ABCDEF\" span=xyz said=\"a\\tb\"";
+        let log = Log::parse(line).unwrap();
+        let trace = log.get_with("trace", hex_id).unwrap();
+        assert_eq!(trace[..4], [0x01, 0x23, 0x45, 0x67]);
+        assert_eq!(trace[15], 0xef);
+        assert_eq!(log.get_with("span", hex_id), None);
+        assert_eq!(log.get_with("missing", hex_id), None);
+        assert_eq!(log.get_with("said", |value| Some(value.len())), Some(3));
+
+        let lengths = log
+            .decode_all_with(|key, value| Some((key.to_owned(), value.len())))
+            .map(|lengths: BTreeMap<_, _>| lengths.into_iter().collect::<Vec<_>>());
+        assert_eq!(
+            lengths,
+            Some(alloc::vec![
+                ("said".to_owned(), 3),
+                ("span".to_owned(), 3),
+                ("trace".to_owned(), 32)
+            ])
+        );
+        assert_eq!(
+            log.decode_all_with::<_, Vec<_>>(|_, value| hex_id(value)),
+            None
+        );
+        let log = Log::parse("a=\"\\x\"").unwrap();
+        assert_eq!(log.decode_all_with::<_, Vec<_>>(|_, _| Some(())), None);
+    }
+
+    #[test]
+    fn to_json() {
+        let line = "msg=\"say \\\"hi\\\"\" path=C:\\temp said=\"line\\nnext\" \
+            emoji=\"\u{1F600}\" accent=\u{e9} count=3 ratio=0.5 ok=true none=null quoted=\"7\"";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(
+            log.to_json(),
+            r#"{"msg":"say \"hi\"","path":"C:\\temp","said":"line\nnext","emoji":"\ud83d\ude00","accent":"\u00e9","count":"3","ratio":"0.5","ok":"true","none":"null","quoted":"7"}"#
+        );
+        assert_eq!(
+            log.to_json_with(JsonValues::Typed),
+            r#"{"msg":"say \"hi\"","path":"C:\\temp","said":"line\nnext","emoji":"\ud83d\ude00","accent":"\u00e9","count":3,"ratio":0.5,"ok":true,"none":null,"quoted":"7"}"#
+        );
+
+        // Control characters are escaped, fallback messages are skipped
+        let log = Log::builder().attribute("bell", "\u{7}").build().unwrap();
+        assert_eq!(log.to_json(), r#"{"bell":"\u0007"}"#);
+        assert_eq!(Log::parse("a=1").unwrap().to_json(), r#"{"a":"1"}"#);
+        assert_eq!(Log::parse("").unwrap().to_json(), "{}");
+
+        let mut buffer = heapless::String::<16>::new();
+        assert!(
+            Log::parse("key=value")
+                .unwrap()
+                .write_json(&mut buffer)
+                .is_ok()
+        );
+        assert_eq!(buffer, r#"{"key":"value"}"#);
+    }
+
+    #[test]
+    fn write_csv_row() {
+        let columns = ["ts", "message", "path", "note", "missing"];
+        let mut csv = String::new();
+        Log::write_csv_header(&mut csv, &columns, ',').unwrap();
+        csv.push('\n');
+        let line = "msg=\"disk full\" ts=1 note=\"say \\\"hi\\\"\" path=\"a,b\"";
+        Log::parse(line)
+            .unwrap()
+            .write_csv_row(&mut csv, &columns, ',')
+            .unwrap();
+        assert_eq!(
+            csv,
+            "ts,message,path,note,missing\n1,disk full,\"a,b\",\"say \"\"hi\"\"\","
+        );
+
+        // Only fields containing the delimiter in use are quoted
+        let mut tsv = String::new();
+        Log::parse("a=1 b=x,y")
+            .unwrap()
+            .write_csv_row(&mut tsv, &["message", "b", "a"], '\t')
+            .unwrap();
+        assert_eq!(tsv, "\tx,y\t1");
+    }
+
+    #[test]
+    fn to_gelf() {
+        let line = "msg=\"disk full\" level=error ts=2024-01-02T03:04:05.250Z path=/var \
+            free=0 ratio=0.5 id=7 stra\u{df}e=main ok=true";
+        assert_eq!(
+            Log::parse(line).unwrap().to_gelf("db-1"),
+            r#"{"version":"1.1","host":"db-1","short_message":"disk full","timestamp":1704164645.25,"level":3,"_path":"/var","_free":0,"_ratio":0.5,"_id_":7,"_stra_e":"main","_ok":"true"}"#
+        );
+
+        // Unknown levels and timestamps are kept as additional fields
+        assert_eq!(
+            Log::parse("started level=loud time=now")
+                .unwrap()
+                .to_gelf("h"),
+            r#"{"version":"1.1","host":"h","short_message":"started","_level":"loud","_time":"now"}"#
+        );
+        assert_eq!(
+            Log::parse("old ts=-1.5").unwrap().to_gelf("h"),
+            r#"{"version":"1.1","host":"h","short_message":"old","_ts":-1.5}"#
+        );
+        let mut gelf = String::new();
+        Log::parse("fatal lvl=crit ts=0")
+            .unwrap()
+            .write_gelf(&mut gelf, "h")
+            .unwrap();
+        assert_eq!(
+            gelf,
+            r#"{"version":"1.1","host":"h","short_message":"fatal","timestamp":0,"level":2}"#
+        );
+    }
+
+    #[test]
+    fn to_syslog() {
+        let line = "msg=\"disk full\" level=error ts=2024-02-29T23:59:58.1234567+01:00 \
+            path=\"C:\\\\temp\" note=\"say \\\"hi\\\" [ok]\" \
+            a_very_long_parameter_name_over_32_chars=1";
+        let log = Log::parse(line).unwrap();
+        let sd = "[app@32473 level=\"error\" ts=\"2024-02-29T23:59:58.1234567+01:00\" \
+            path=\"C:\\\\temp\" note=\"say \\\"hi\\\" [ok\\]\" \
+            a_very_long_parameter_name_over_=\"1\"]";
+        assert_eq!(log.to_syslog_sd("app@32473"), sd);
+
+        // Errors of the user facility have priority 1 * 8 + 3
+        let options = SyslogOptions {
+            hostname: "db-1",
+            app_name: "app",
+            sd_id: "app@32473",
+            ..SyslogOptions::default()
+        };
+        assert_eq!(
+            log.to_syslog(&options),
+            format!("<11>1 2024-02-29T22:59:58.123456Z db-1 app - - {sd} disk full")
+        );
+
+        let log = Log::parse("started").unwrap();
+        assert_eq!(
+            log.to_syslog(&SyslogOptions::default()),
+            "<14>1 - - - - - [logfmt@32473] started"
+        );
+        let log = Log::parse("level=fatal ts=0").unwrap();
+        let options = SyslogOptions {
+            facility: 16,
+            ..SyslogOptions::default()
+        };
+        assert_eq!(
+            log.to_syslog(&options),
+            "<130>1 1970-01-01T00:00:00Z - - - - [logfmt@32473 level=\"fatal\" ts=\"0\"]"
+        );
+    }
+
+    #[test]
+    fn format_pretty() {
+        let pretty = |line, column| {
+            let mut output = String::new();
+            Log::parse(line)
+                .unwrap()
+                .format_pretty_with(&mut output, column)
+                .unwrap();
+            output
+        };
+        assert_eq!(
+            pretty("msg=\"request done\" id=7 duration=10 status_code=200", 80),
+            "request done\n  id          = 7\n  duration    = 10\n  status_code = 200"
+        );
+        assert_eq!(
+            pretty("a=1 key=\"a long value that wraps\" b=abcdefghijkl", 16),
+            "\n  a   = 1\n  key = a long\n        value\n        that\n        wraps\n  b   = abcdefgh\n        ijkl"
+        );
+        assert_eq!(pretty("just a message", 80), "just a message");
+        assert_eq!(pretty("", 80), "");
+
+        let mut output = String::new();
+        Log::parse("x=1")
+            .unwrap()
+            .format_pretty(&mut output)
+            .unwrap();
+        assert_eq!(output, "\n  x = 1");
+    }
+
+    #[test]
+    fn format_colored() {
+        let colored = |line, scheme| {
+            let mut output = String::new();
+            Log::parse(line)
+                .unwrap()
+                .format_colored(&mut output, &scheme)
+                .unwrap();
+            output
+        };
+        let line = "msg=\"disk full\" level=error path=\"/var/log\" free=0";
+        assert_eq!(
+            colored(line, ColorScheme::default()),
+            "\x1b[31mdisk full\x1b[0m \x1b[2mlevel=\x1b[0merror \x1b[2mpath=\x1b[0m\"/var/log\" \
+                \x1b[2mfree=\x1b[0m0"
+        );
+        assert_eq!(
+            colored("slow request level=warn", ColorScheme::default()),
+            "\x1b[33mslow request\x1b[0m \x1b[2mlevel=\x1b[0mwarn"
+        );
+        assert_eq!(
+            colored("started level=info", ColorScheme::default()),
+            "started \x1b[2mlevel=\x1b[0minfo"
+        );
+
+        // Disabled schemes write plain text
+        let plain = ColorScheme {
+            enabled: false,
+            ..ColorScheme::default()
+        };
+        assert_eq!(
+            colored(line, plain),
+            "disk full level=error path=\"/var/log\" free=0"
+        );
+
+        // The fallback message of a line of attributes isn't written
+        assert_eq!(colored("level=error a=1", plain), "level=error a=1");
+    }
+
+    #[test]
+    fn parse_json() {
+        let line = r#" {"msg": "say \"hi\"", "path": "C:\\temp", "said": "line\nnext",
+            "emoji": "\ud83d\ude00", "plain": "borrowed", "count": 3, "ratio": -1.5e3,
+            "ok": true, "none": null} "#;
+        let log = Log::parse_json(line).unwrap();
+        assert_eq!(log.message(), "\"say \\\"hi\\\"\"");
+        assert_eq!(
+            log,
+            (
+                "\"say \\\"hi\\\"\"",
+                [
+                    ("path", "\"C:\\\\temp\""),
+                    ("said", "\"line\\nnext\""),
+                    ("emoji", "\u{1F600}"),
+                    ("plain", "borrowed"),
+                    ("count", "3"),
+                    ("ratio", "-1.5e3"),
+                    ("ok", "true"),
+                    ("none", "null"),
+                ]
+                .as_slice()
+            )
+        );
+        assert!(matches!(
+            log.attributes()[3].value,
+            Cow::Borrowed("borrowed")
+        ));
+        assert_eq!(log.get_i64("count"), Some(3));
+        assert_eq!(Log::parse_json("{}").unwrap(), Log::default());
+
+        // Strings are decoded once, and encoded again when writing
+        assert_eq!(
+            log.get_unescaped("path"),
+            Some(Ok(Cow::Owned("C:\\temp".into())))
+        );
+        let json = r#"{"q":"\"x\"","path":"C:\\temp"}"#;
+        let log = Log::parse_json(json).unwrap();
+        assert_eq!(log.get_unescaped("q"), Some(Ok(Cow::Owned("\"x\"".into()))));
+        assert_eq!(log.to_json(), json);
+        assert_eq!(log.to_string(), r#"q="\"x\"" path="C:\\temp""#);
+        assert_eq!(
+            Log::parse(&log.to_string()).unwrap().attributes(),
+            log.attributes()
+        );
+
+        // Nested values, invalid numbers, and unfinished objects are rejected
+        let error = |line| Log::parse_json(line).map(|_| ()).unwrap_err();
+        assert_eq!(
+            error(r#"{"a": {"b": 1}}"#),
+            JsonError {
+                kind: JsonErrorKind::NestedValue,
+                offset: 6
+            }
+        );
+        assert_eq!(error(r#"{"a": [1]}"#).kind(), JsonErrorKind::NestedValue);
+        assert_eq!(error(r#"{"a": 01}"#).kind(), JsonErrorKind::InvalidNumber);
+        assert_eq!(error(r#"{"a": 1.}"#).kind(), JsonErrorKind::InvalidNumber);
+        assert_eq!(error(r#"{"a": "\x"}"#).kind(), JsonErrorKind::InvalidEscape);
+        assert_eq!(
+            error(r#"{"a": "\ud83d"}"#).kind(),
+            JsonErrorKind::InvalidEscape
+        );
+        assert_eq!(error(r#"{"a": 1"#).kind(), JsonErrorKind::UnexpectedEnd);
+        assert_eq!(
+            error(r#"{"a": tru}"#).kind(),
+            JsonErrorKind::UnexpectedCharacter
+        );
+        assert_eq!(error(r#"{"a": 1} x"#).offset(), 9);
+        assert_eq!(error(r#"{"": 1}"#).kind(), JsonErrorKind::InvalidKey);
+        assert_eq!(
+            error(r#"{"a": 1,}"#).to_string(),
+            "unexpected character at byte 8"
+        );
+    }
+
+    #[test]
+    fn parse_auto() {
+        let json = Log::parse_auto(r#"  {"message": "hi", "a": 1}"#).unwrap();
+        let logfmt = Log::parse_auto("msg=hi a=1").unwrap();
+        assert_eq!(json, logfmt);
+        assert!(matches!(Log::parse_auto("{"), Err(AutoParseError::Json(_))));
+        assert_eq!(
+            Log::parse_auto("a=\"open"),
+            Err(AutoParseError::Logfmt(UnclosedString))
+        );
+    }
+}
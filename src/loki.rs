@@ -0,0 +1,171 @@
+//! Building Grafana Loki push API payloads from log messages
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+
+use crate::{EncodeError, Encoder, Log, decode_quoted, keys_match, write_json_string};
+
+/// The lines of a stream with the same labels
+#[derive(Debug, Clone)]
+struct Stream {
+    labels: Vec<(String, String)>,
+    values: Vec<(u64, String)>,
+}
+
+/// A batch of log lines grouped into streams by their labels, for the Loki push API
+#[derive(Debug, Clone, Default)]
+pub struct LokiBatch {
+    labels: Vec<(String, String)>,
+    label_keys: Vec<String>,
+    streams: Vec<Stream>,
+}
+
+impl LokiBatch {
+    /// Create an empty batch with labels shared by every stream
+    pub fn new(labels: &[(&str, &str)]) -> Self {
+        Self {
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Lift the attributes with the keys into the labels of the stream, instead of keeping them
+    /// in the line
+    #[must_use]
+    pub fn with_label_keys(mut self, keys: &[&str]) -> Self {
+        self.label_keys = keys.iter().map(ToString::to_string).collect();
+        self
+    }
+
+    /// Add the log message as logfmt line to the stream with its labels.
+    /// The message is written as `msg` attribute unless it's the fallback message, and values are
+    /// written without surrounding quotes, quoted again by the [`Encoder`] where needed.
+    pub fn push(&mut self, log: &Log<'_>, timestamp_ns: u64) -> Result<(), EncodeError> {
+        let mut labels = self.labels.clone();
+        for label_key in &self.label_keys {
+            if let Some(value) = log.get(label_key) {
+                labels.push((label_key.clone(), decode_quoted(value).into_owned()));
+            }
+        }
+
+        let mut encoder = Encoder::new(String::new());
+        if !log.message().is_empty() && !log.message_is_fallback() {
+            encoder.push("msg", &decode_quoted(log.message()))?;
+        }
+        for (key, value) in log {
+            if !self
+                .label_keys
+                .iter()
+                .any(|label_key| keys_match(key, label_key))
+            {
+                encoder.push(key, &decode_quoted(value))?;
+            }
+        }
+        let line = encoder.finish();
+
+        match self
+            .streams
+            .iter_mut()
+            .find(|stream| stream.labels == labels)
+        {
+            Some(stream) => stream.values.push((timestamp_ns, line)),
+            None => self.streams.push(Stream {
+                labels,
+                values: alloc::vec![(timestamp_ns, line)],
+            }),
+        }
+        Ok(())
+    }
+
+    /// Writes the batch as push API JSON payload, with the streams in the order they were first
+    /// pushed to
+    pub fn write_json<W: Write + ?Sized>(&self, f: &mut W) -> fmt::Result {
+        f.write_str("{\"streams\":[")?;
+        for (index, stream) in self.streams.iter().enumerate() {
+            if index > 0 {
+                f.write_char(',')?;
+            }
+            f.write_str("{\"stream\":{")?;
+            for (index, (key, value)) in stream.labels.iter().enumerate() {
+                if index > 0 {
+                    f.write_char(',')?;
+                }
+                write_json_string(f, key)?;
+                f.write_char(':')?;
+                write_json_string(f, value)?;
+            }
+            f.write_str("},\"values\":[")?;
+            for (index, (timestamp_ns, line)) in stream.values.iter().enumerate() {
+                if index > 0 {
+                    f.write_char(',')?;
+                }
+                // Loki expects the nanosecond timestamps as strings
+                write!(f, "[\"{timestamp_ns}\",")?;
+                write_json_string(f, line)?;
+                f.write_char(']')?;
+            }
+            f.write_str("]}")?;
+        }
+        f.write_str("]}")
+    }
+
+    /// Returns the batch as push API JSON payload, like [`LokiBatch::write_json`]
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(&mut json)
+            .expect("writing to a string can't fail");
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Log;
+
+    use super::LokiBatch;
+
+    #[test]
+    fn streams() {
+        let mut batch = LokiBatch::new(&[("app", "api")]).with_label_keys(&["level"]);
+        let lines = [
+            "msg=\"disk full\" level=error path=\"C:\\\\temp dir\"",
+            "msg=started level=info port=80",
+            "level=error code=7",
+        ];
+        for (timestamp_ns, line) in (1_700_000_000_000_000_000..).zip(lines) {
+            batch
+                .push(&Log::parse(line).unwrap(), timestamp_ns)
+                .unwrap();
+        }
+        assert_eq!(
+            batch.to_json(),
+            concat!(
+                r#"{"streams":["#,
+                r#"{"stream":{"app":"api","level":"error"},"values":["#,
+                r#"["1700000000000000000","msg=\"disk full\" path=\"C:\\\\temp dir\""],"#,
+                r#"["1700000000000000002","code=7"]]},"#,
+                r#"{"stream":{"app":"api","level":"info"},"values":["#,
+                r#"["1700000000000000001","msg=started port=80"]]}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(LokiBatch::new(&[]).to_json(), r#"{"streams":[]}"#);
+
+        // Logs without the label keys only get the shared labels
+        let mut batch = LokiBatch::new(&[]).with_label_keys(&["level"]);
+        batch.push(&Log::parse("a=1").unwrap(), 5).unwrap();
+        assert_eq!(
+            batch.to_json(),
+            r#"{"streams":[{"stream":{},"values":[["5","a=1"]]}]}"#
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Mapping log attributes to OpenTelemetry semantic convention attributes
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use crate::{Log, TypedValue, Value, is_quoted, keys_match, parse_duration};
+
+/// How the value of a renamed attribute is converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    /// Classified like [`TypedValue::new`]
+    Typed,
+
+    /// A Go duration like `1.5s` or a number of milliseconds, converted to milliseconds
+    DurationMillis,
+}
+
+/// A renamed attribute key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rename<'mapping> {
+    /// The key in the log message
+    pub from: &'mapping str,
+
+    /// The key of the OpenTelemetry attribute
+    pub to: &'mapping str,
+
+    /// How the value is converted
+    pub conversion: Conversion,
+}
+
+/// The renames of well-known keys used by [`OtelMapping::default`]
+pub const DEFAULT_RENAMES: [Rename<'static>; 4] = [
+    Rename {
+        from: "duration",
+        to: "duration_ms",
+        conversion: Conversion::DurationMillis,
+    },
+    Rename {
+        from: "status",
+        to: "http.response.status_code",
+        conversion: Conversion::Typed,
+    },
+    Rename {
+        from: "method",
+        to: "http.request.method",
+        conversion: Conversion::Typed,
+    },
+    Rename {
+        from: "err",
+        to: "exception.message",
+        conversion: Conversion::Typed,
+    },
+];
+
+/// A table of renamed keys, used to map log attributes to OpenTelemetry attributes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtelMapping<'mapping> {
+    renames: Vec<Rename<'mapping>>,
+}
+
+impl Default for OtelMapping<'_> {
+    fn default() -> Self {
+        Self {
+            renames: DEFAULT_RENAMES.to_vec(),
+        }
+    }
+}
+
+impl<'mapping> OtelMapping<'mapping> {
+    /// Create a mapping without renames, passing every attribute through
+    pub const fn empty() -> Self {
+        Self {
+            renames: Vec::new(),
+        }
+    }
+
+    /// Rename the key, replacing an earlier rename of the same key
+    #[must_use]
+    pub fn with_rename(self, from: &'mapping str, to: &'mapping str) -> Self {
+        self.with(Rename {
+            from,
+            to,
+            conversion: Conversion::Typed,
+        })
+    }
+
+    /// Add the rename, replacing an earlier rename of the same key
+    #[must_use]
+    pub fn with(mut self, rename: Rename<'mapping>) -> Self {
+        self.renames
+            .retain(|existing| !keys_match(existing.from, rename.from));
+        self.renames.push(rename);
+        self
+    }
+
+    /// Return the renames
+    pub fn renames(&self) -> &[Rename<'mapping>] {
+        &self.renames
+    }
+
+    /// Maps the attribute to an OpenTelemetry attribute.
+    /// Values that can't be converted keep their key and are classified like [`TypedValue::new`].
+    fn map<'a>(&self, key: &'a str, raw: &'a str) -> (Cow<'a, str>, Value<'a>)
+    where
+        'mapping: 'a,
+    {
+        let typed = TypedValue::new(raw).value();
+        let Some(rename) = self
+            .renames
+            .iter()
+            .find(|rename| keys_match(rename.from, key))
+        else {
+            return (Cow::Borrowed(key), typed);
+        };
+        let value = match rename.conversion {
+            Conversion::Typed => Some(typed),
+            Conversion::DurationMillis => duration_millis(raw, typed),
+        };
+        match value {
+            Some(value) => (Cow::Borrowed(rename.to), value),
+            None => (Cow::Borrowed(key), typed),
+        }
+    }
+}
+
+/// Converts a number of milliseconds or an unquoted Go duration to milliseconds, as integer if
+/// it's a whole number of milliseconds
+fn duration_millis<'a>(raw: &str, typed: Value<'a>) -> Option<Value<'a>> {
+    match typed {
+        Value::Int(_) | Value::Float(_) => Some(typed),
+        _ if is_quoted(raw) => None,
+        _ => {
+            let nanos = parse_duration(raw)?.as_nanos();
+            Some(match i64::try_from(nanos / 1_000_000) {
+                Ok(millis) if nanos % 1_000_000 == 0 => Value::Int(millis),
+                _ => Value::Float(nanos as f64 / 1_000_000.0),
+            })
+        }
+    }
+}
+
+impl Log<'_> {
+    /// Return the attributes as OpenTelemetry attributes, renaming well-known keys like
+    /// [`DEFAULT_RENAMES`], without the message
+    pub fn to_otel_attrs(&self) -> Vec<(Cow<'_, str>, Value<'_>)> {
+        self.to_otel_attrs_with(&OtelMapping::default())
+    }
+
+    /// Return the attributes as OpenTelemetry attributes, renamed by the mapping and classified
+    /// like [`TypedValue::new`], without the message
+    pub fn to_otel_attrs_with<'a>(
+        &'a self,
+        mapping: &OtelMapping<'a>,
+    ) -> Vec<(Cow<'a, str>, Value<'a>)> {
+        self.iter()
+            .map(|(key, value)| mapping.map(key, value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, vec::Vec};
+
+    use crate::{Log, Value};
+
+    use super::{Conversion, OtelMapping, Rename};
+
+    #[test]
+    fn default_renames() {
+        let log = Log::parse(
+            "request done method=GET status=200 duration=1.5s err=\"not found\" cached=true ratio=0.5 path=/",
+        )
+        .unwrap();
+        assert_eq!(
+            log.to_otel_attrs(),
+            [
+                (Cow::Borrowed("http.request.method"), Value::Str("GET")),
+                (Cow::Borrowed("http.response.status_code"), Value::Int(200)),
+                (Cow::Borrowed("duration_ms"), Value::Int(1500)),
+                (
+                    Cow::Borrowed("exception.message"),
+                    Value::QuotedStr("not found")
+                ),
+                (Cow::Borrowed("cached"), Value::Bool(true)),
+                (Cow::Borrowed("ratio"), Value::Float(0.5)),
+                (Cow::Borrowed("path"), Value::Str("/")),
+            ]
+        );
+    }
+
+    #[test]
+    fn durations() {
+        let durations = [
+            "duration=25",
+            "duration=2.5",
+            "duration=250us",
+            "duration=1h",
+            "duration=\"10ms\"",
+            "duration=soon",
+        ]
+        .map(|line| Log::parse(line).unwrap());
+        let attrs: Vec<_> = durations
+            .iter()
+            .flat_map(|log| log.to_otel_attrs())
+            .collect();
+        assert_eq!(
+            attrs,
+            [
+                (Cow::Borrowed("duration_ms"), Value::Int(25)),
+                (Cow::Borrowed("duration_ms"), Value::Float(2.5)),
+                (Cow::Borrowed("duration_ms"), Value::Float(0.25)),
+                (Cow::Borrowed("duration_ms"), Value::Int(3_600_000)),
+                // Durations that can't be converted keep their key
+                (Cow::Borrowed("duration"), Value::QuotedStr("10ms")),
+                (Cow::Borrowed("duration"), Value::Str("soon")),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_mapping() {
+        let log = Log::parse("status=500 user=7 took=3s").unwrap();
+        let mapping = OtelMapping::default()
+            .with_rename("status", "rpc.grpc.status_code")
+            .with_rename("user", "enduser.id")
+            .with(Rename {
+                from: "took",
+                to: "duration_ms",
+                conversion: Conversion::DurationMillis,
+            });
+        assert_eq!(
+            log.to_otel_attrs_with(&mapping),
+            [
+                (Cow::Borrowed("rpc.grpc.status_code"), Value::Int(500)),
+                (Cow::Borrowed("enduser.id"), Value::Int(7)),
+                (Cow::Borrowed("duration_ms"), Value::Int(3000)),
+            ]
+        );
+        assert_eq!(
+            log.to_otel_attrs_with(&OtelMapping::empty()),
+            [
+                (Cow::Borrowed("status"), Value::Int(500)),
+                (Cow::Borrowed("user"), Value::Int(7)),
+                (Cow::Borrowed("took"), Value::Str("3s")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1661 @@
+//! Parsing log messages, with the options controlling it and the iterators over tokens and lines
+
+use alloc::{borrow::Cow, format, string::String, vec::Vec};
+use core::{
+    iter::FusedIterator,
+    ops::{ControlFlow, Range},
+};
+
+use crate::{
+    Attribute, AutoParseError, ErrorKind, JsonError, JsonErrorKind, Log, Message, ParseError,
+    UnclosedString, UnescapeError, encoder::encode_value, tokenizer::Tokenizer,
+};
+
+/// Which bare words are parsed as flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagWords<'options> {
+    /// Bare words are always part of the message
+    Disabled,
+
+    /// Every bare word that is a valid key is a flag
+    All,
+
+    /// Only the listed bare words are flags
+    Only(&'options [&'options str]),
+}
+
+impl FlagWords<'_> {
+    /// Checks whether the word should be parsed as a flag
+    fn matches(&self, word: &str) -> bool {
+        match self {
+            Self::Disabled => false,
+            Self::All => true,
+            Self::Only(words) => words.contains(&word),
+        }
+    }
+}
+
+/// How attributes with a key that was already found are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Replace the value of the earlier attribute
+    #[default]
+    Overwrite,
+
+    /// Keep the value of the earlier attribute
+    KeepFirst,
+
+    /// Add every attribute, in the order they were found
+    KeepAll,
+}
+
+/// Which attributes are kept while parsing.
+/// Predicates are compared by address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(unpredictable_function_pointer_comparisons)]
+pub enum KeyFilter<'options> {
+    /// Keep every attribute
+    All,
+
+    /// Only keep attributes with one of the listed keys
+    Allow(&'options [&'options str]),
+
+    /// Skip attributes with one of the listed keys
+    Deny(&'options [&'options str]),
+
+    /// Keep attributes for which the function, called with the key, returns true
+    Predicate(fn(&str) -> bool),
+}
+
+impl KeyFilter<'_> {
+    /// Checks whether an attribute with this key, without surrounding quotes, should be kept
+    fn keeps(&self, key: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Allow(keys) => keys.contains(&key),
+            Self::Deny(keys) => !keys.contains(&key),
+            Self::Predicate(predicate) => predicate(key),
+        }
+    }
+}
+
+/// How `=` characters in unquoted values are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EqualsInValue {
+    /// Keep them as part of the value, the key ends at the first `=`
+    #[default]
+    Allow,
+
+    /// Treat the token as malformed
+    Reject,
+}
+
+/// Which logfmt implementation the parsing rules follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// The rules of this crate, adding bare words and malformed tokens to the message
+    #[default]
+    Lenient,
+
+    /// The rules of Go's `go-logfmt/logfmt`, parsing bare words as keys with an empty value and
+    /// rejecting malformed tokens
+    GoLogfmt,
+}
+
+/// Which characters separate tokens.
+/// Predicates are compared by address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(unpredictable_function_pointer_comparisons)]
+pub enum Separators<'options> {
+    /// Unicode whitespace
+    Whitespace,
+
+    /// Any of the listed characters
+    Chars(&'options [char]),
+
+    /// Characters for which the function returns true
+    Predicate(fn(char) -> bool),
+}
+
+impl Separators<'_> {
+    /// Checks whether the character separates tokens
+    pub(crate) fn matches(&self, ch: char) -> bool {
+        match self {
+            Self::Whitespace => ch.is_whitespace(),
+            Self::Chars(chars) => chars.contains(&ch),
+            Self::Predicate(predicate) => predicate(ch),
+        }
+    }
+}
+
+/// Additional rules for the first character of keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyStyle {
+    /// Keys may start with any character allowed by the key validator
+    #[default]
+    Loose,
+
+    /// Keys have to start with an alphabetic character or `_`
+    Identifier,
+}
+
+impl KeyStyle {
+    /// Checks whether the key, without surrounding quotes, starts with an allowed character
+    fn allows(self, key: &str) -> bool {
+        match self {
+            Self::Loose => true,
+            Self::Identifier => key
+                .chars()
+                .next()
+                .is_some_and(|ch| ch.is_alphabetic() || ch == '_'),
+        }
+    }
+}
+
+/// How control characters in values and message words are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlChars {
+    /// Keep them as part of the value
+    #[default]
+    Allow,
+
+    /// Treat the token as malformed, checking strings after decoding their escape sequences.
+    /// Malformed words are still added to the message, unless parsing is strict.
+    Reject,
+
+    /// Replace them with `U+FFFD`
+    Sanitize,
+}
+
+/// How values longer than the maximum length are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlongValue {
+    /// Treat the token as malformed
+    #[default]
+    DemoteToWord,
+
+    /// Keep the attribute, truncating the value to the given number of characters, but at most
+    /// the maximum length, followed by `…`
+    Truncate(usize),
+
+    /// Return an error, even if parsing isn't strict
+    Error,
+}
+
+/// The default maximum number of attributes
+pub const DEFAULT_MAX_ATTRIBUTES: usize = 25;
+
+/// Options controlling how a log message is parsed.
+/// Key validators and predicates are compared by address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(unpredictable_function_pointer_comparisons)]
+pub struct ParseOptions<'options> {
+    /// The maximum number of attributes, `None` for unlimited.
+    /// Attributes past the limit are added to the message.
+    pub max_attributes: Option<usize>,
+
+    /// The maximum number of characters in a key, excluding surrounding quotes.
+    /// Use `usize::MAX` to disable the check.
+    pub max_key_len: usize,
+
+    /// The maximum number of characters in a value, excluding surrounding quotes.
+    /// The message attribute isn't limited.
+    /// Use `usize::MAX` to disable the check.
+    pub max_value_len: usize,
+
+    /// Return an error for malformed attributes and attributes past the limit, instead of
+    /// adding them to the message.
+    pub strict: bool,
+
+    /// Parse tokens like `key=` as attributes with an empty value, instead of as words
+    pub allow_empty_values: bool,
+
+    /// Which bare words are parsed as flags, which are attributes with the value `"true"`.
+    /// Words that aren't valid keys are always part of the message.
+    pub bare_words_as_flags: FlagWords<'options>,
+
+    /// The keys whose value replaces the message, instead of being added as attribute.
+    /// Quoted versions of these keys are recognized as well.
+    pub message_keys: &'options [&'options str],
+
+    /// Replace the message with the value of a message key.
+    /// If disabled, message keys are parsed as ordinary attributes.
+    pub override_message: bool,
+
+    /// Match message keys ignoring ASCII case, so `Msg` and `MESSAGE` are message keys too
+    pub case_insensitive_message_keys: bool,
+
+    /// How attributes with a key that was already found are handled
+    pub duplicates: DuplicatePolicy,
+
+    /// Which attributes are kept, others are skipped entirely and don't count towards the
+    /// maximum number of attributes
+    pub key_filter: KeyFilter<'options>,
+
+    /// Checks whether a key, without surrounding quotes, only contains allowed characters.
+    /// Keys that are empty without their quotes, like `""`, are rejected before it's called.
+    /// Defaults to [`default_key_validator`].
+    pub key_validator: fn(&str) -> bool,
+
+    /// How `=` characters in unquoted values are handled
+    pub equals_in_value: EqualsInValue,
+
+    /// Allow strings to be surrounded by single quotes, besides double quotes.
+    /// A string has to be closed by the same kind of quote it was opened with.
+    pub single_quotes: bool,
+
+    /// Which characters separate tokens, separators inside strings are part of the token
+    pub separators: Separators<'options>,
+
+    /// The maximum length of the input in bytes, `None` for unlimited
+    pub max_input_len: Option<usize>,
+
+    /// After a word following an attribute, add the rest of the line to the message verbatim,
+    /// without parsing it
+    pub trailing_text_as_message: bool,
+
+    /// Remove the surrounding quotes from keys, values, and message values
+    pub strip_quotes: bool,
+
+    /// Decode the escape sequences `\"`, `\'`, `\\`, `\n`, `\t`, `\r`, and `\uXXXX` in quoted
+    /// values, including message values
+    pub decode_escapes: bool,
+
+    /// Convert ASCII characters in keys to lowercase, before checking for message keys and
+    /// duplicates
+    pub normalize_keys_lowercase: bool,
+
+    /// Additional rules for the first character of keys
+    pub key_style: KeyStyle,
+
+    /// How control characters in values, including message values, and in message words are
+    /// handled. Separators aren't replaced in the message.
+    pub control_chars: ControlChars,
+
+    /// The number of attributes to allocate room for up front, limited by the maximum number of
+    /// attributes
+    pub attribute_capacity_hint: usize,
+
+    /// Ignore a leading byte order mark and trailing carriage returns and newlines
+    pub trim_line_endings: bool,
+
+    /// Use the input as message if it only contains separators, instead of an empty message
+    pub keep_blank_input: bool,
+
+    /// How values longer than the maximum length are handled
+    pub on_overlong_value: OverlongValue,
+
+    /// Whether the byte ranges of the attributes and message in the input are recorded
+    pub record_spans: bool,
+
+    /// Which logfmt implementation the rules for bare words and keys follow.
+    /// Use [`ParseOptions::dialect`] to set the other options to match as well.
+    pub dialect: Dialect,
+}
+
+impl ParseOptions<'_> {
+    /// Create the options matching the dialect.
+    /// [`Dialect::GoLogfmt`] disables the length and count limits and message keys, keeps
+    /// duplicates, strips quotes, decodes escape sequences, and returns an error for malformed
+    /// tokens.
+    pub fn dialect(dialect: Dialect) -> Self {
+        match dialect {
+            Dialect::Lenient => Self::default(),
+            Dialect::GoLogfmt => Self {
+                max_attributes: None,
+                max_key_len: usize::MAX,
+                max_value_len: usize::MAX,
+                strict: true,
+                override_message: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                key_validator: go_key_validator,
+                equals_in_value: EqualsInValue::Reject,
+                strip_quotes: true,
+                decode_escapes: true,
+                dialect,
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Checks whether the character starts or ends a string
+    pub(crate) fn is_quote(&self, ch: char) -> bool {
+        is_quote(ch, self.single_quotes)
+    }
+
+    /// Splits the surrounding quotes off a string, returning the quote and the contents
+    fn split_quotes<'s>(&self, s: &'s str) -> Option<(char, &'s str)> {
+        split_quotes(s, self.single_quotes)
+    }
+
+    /// Removes one pair of surrounding quotes, if present
+    fn unquote<'s>(&self, s: &'s str) -> &'s str {
+        self.split_quotes(s).map_or(s, |(_, contents)| contents)
+    }
+
+    /// Checks whether the key is one of the message keys
+    pub(crate) fn is_message_key(&self, key: &str) -> bool {
+        let key = self.unquote(key);
+        self.override_message
+            && self.message_keys.iter().any(|message_key| {
+                if self.case_insensitive_message_keys {
+                    message_key.eq_ignore_ascii_case(key)
+                } else {
+                    *message_key == key
+                }
+            })
+    }
+}
+
+impl Default for ParseOptions<'_> {
+    fn default() -> Self {
+        Self {
+            max_attributes: Some(DEFAULT_MAX_ATTRIBUTES),
+            max_key_len: 50,
+            max_value_len: 100,
+            strict: false,
+            allow_empty_values: true,
+            bare_words_as_flags: FlagWords::Disabled,
+            message_keys: &["msg", "message"],
+            override_message: true,
+            case_insensitive_message_keys: false,
+            duplicates: DuplicatePolicy::Overwrite,
+            key_filter: KeyFilter::All,
+            key_validator: default_key_validator,
+            equals_in_value: EqualsInValue::Allow,
+            single_quotes: false,
+            separators: Separators::Whitespace,
+            max_input_len: None,
+            trailing_text_as_message: false,
+            strip_quotes: false,
+            decode_escapes: false,
+            normalize_keys_lowercase: false,
+            key_style: KeyStyle::Loose,
+            control_chars: ControlChars::Allow,
+            attribute_capacity_hint: 8,
+            trim_line_endings: true,
+            keep_blank_input: false,
+            on_overlong_value: OverlongValue::DemoteToWord,
+            record_spans: false,
+            dialect: Dialect::Lenient,
+        }
+    }
+}
+
+/// A word or attribute in a log message, yielded by [`tokens`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'input> {
+    /// A word of the message, including tokens with `=` that aren't valid attributes
+    Word(&'input str),
+
+    /// The key and value of an attribute, as written
+    Attribute(&'input str, &'input str),
+}
+
+/// Returns an iterator lazily yielding the words and attributes in the log message in order,
+/// tokenized and validated like [`Log::parse`] without allocating.
+/// Iteration stops after an unclosed string.
+pub fn tokens(s: &str) -> TokenIter<'_> {
+    let options = ParseOptions::default();
+    TokenIter {
+        tokens: Tokenizer::new(s, &options),
+        options,
+    }
+}
+
+/// An iterator over the words and attributes in a log message, returned by [`tokens`]
+#[derive(Debug, Clone)]
+pub struct TokenIter<'input> {
+    tokens: Tokenizer<'input>,
+    options: ParseOptions<'static>,
+}
+
+impl<'input> Iterator for TokenIter<'input> {
+    type Item = Result<Token<'input>, UnclosedString>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let span = match self.tokens.next(&self.options)? {
+            Ok(span) => span,
+            Err(_) => return Some(Err(UnclosedString)),
+        };
+        let token = &self.tokens.input[span];
+
+        // The default options don't change keys and values, so they're taken from the token
+        Some(Ok(match ParsedToken::parse(token, &self.options) {
+            ParsedToken::Attribute(_, _, (key_span, value_span)) => {
+                Token::Attribute(&token[key_span], &token[value_span])
+            }
+            ParsedToken::Word(word) | ParsedToken::Malformed(word, _) => Token::Word(word),
+        }))
+    }
+}
+
+impl FusedIterator for TokenIter<'_> {}
+
+/// Callbacks for the tokens of a log message, called by [`parse_visit`] in input order.
+/// Returning [`ControlFlow::Break`] stops the scan.
+pub trait LogVisitor<'input> {
+    /// Called for a word of the message
+    fn word(&mut self, word: &'input str) -> ControlFlow<()>;
+
+    /// Called for an attribute that isn't the message
+    fn attribute(&mut self, key: &'input str, value: &'input str) -> ControlFlow<()>;
+
+    /// Called for the value of an attribute with one of the default message keys
+    fn message_override(&mut self, message: &'input str) -> ControlFlow<()>;
+}
+
+/// Calls the visitor for the words and attributes in the log message in order, tokenized and
+/// validated like [`Log::parse`] without allocating.
+/// Stops without an error when the visitor breaks.
+pub fn parse_visit<'input>(
+    s: &'input str,
+    visitor: &mut impl LogVisitor<'input>,
+) -> Result<(), UnclosedString> {
+    let options = ParseOptions::default();
+    for token in tokens(s) {
+        let flow = match token? {
+            Token::Word(word) => visitor.word(word),
+            Token::Attribute(key, value) if options.is_message_key(key) => {
+                visitor.message_override(value)
+            }
+            Token::Attribute(key, value) => visitor.attribute(key, value),
+        };
+        if flow.is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Returns an iterator lazily yielding the keys and values of the attributes in the log message,
+/// like [`tokens`] without the words.
+/// Message keys are yielded like other attributes, duplicates aren't merged, and there's no
+/// limit on the number of attributes.
+pub fn attributes(s: &str) -> AttributeIter<'_> {
+    AttributeIter { tokens: tokens(s) }
+}
+
+/// An iterator over the attributes in a log message, returned by [`attributes`]
+#[derive(Debug, Clone)]
+pub struct AttributeIter<'input> {
+    tokens: TokenIter<'input>,
+}
+
+impl<'input> Iterator for AttributeIter<'input> {
+    type Item = Result<(&'input str, &'input str), UnclosedString>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.find_map(|token| match token {
+            Ok(Token::Word(_)) => None,
+            Ok(Token::Attribute(key, value)) => Some(Ok((key, value))),
+            Err(error) => Some(Err(error)),
+        })
+    }
+}
+
+impl FusedIterator for AttributeIter<'_> {}
+
+/// A token in the log message
+enum ParsedToken<'message> {
+    Word(&'message str),
+    /// An attribute with the spans of its key and value within the token
+    Attribute(
+        Cow<'message, str>,
+        Cow<'message, str>,
+        (Range<usize>, Range<usize>),
+    ),
+    /// A token containing `=` that isn't a valid attribute
+    Malformed(&'message str, ErrorKind),
+}
+
+impl<'message> ParsedToken<'message> {
+    /// Parses the token from a string
+    fn parse(s: &'message str, options: &ParseOptions<'_>) -> Self {
+        // Split the message in a key and value
+        // Return the message as word if not possible
+        if let Some((key, value)) = s.split_once('=') {
+            // Remove whitespace around the key and value
+            let (key, value) = (key.trim(), value.trim());
+
+            // Go doesn't allow quoted keys
+            if options.dialect == Dialect::GoLogfmt && key.contains('"') {
+                return ParsedToken::Malformed(s, ErrorKind::InvalidKey);
+            }
+
+            // The message isn't stored as attribute, so its length isn't limited
+            let max_len = if options.is_message_key(key) {
+                usize::MAX
+            } else {
+                options.max_value_len
+            };
+
+            // Make sure the key and value are valid, otherwise return it as a malformed token
+            match validate_key(key, options)
+                .and_then(|()| validate_value(value, max_len, options))
+                .and_then(|()| process_value(value, max_len, options))
+            {
+                Ok(processed) => ParsedToken::Attribute(
+                    process_key(key, options),
+                    processed,
+                    (span_of(s, key), span_of(s, value)),
+                ),
+                Err(kind) => ParsedToken::Malformed(s, kind),
+            }
+        } else if options.dialect == Dialect::GoLogfmt {
+            // Parse the word as a key without a value
+            match validate_key(s, options) {
+                Ok(()) if !s.contains('"') => ParsedToken::Attribute(
+                    process_key(s, options),
+                    Cow::Borrowed(""),
+                    (0..s.len(), s.len()..s.len()),
+                ),
+                _ => ParsedToken::Malformed(s, ErrorKind::InvalidKey),
+            }
+        } else if options.bare_words_as_flags.matches(s) && validate_key(s, options).is_ok() {
+            // Parse the word as a flag
+            ParsedToken::Attribute(
+                process_key(s, options),
+                Cow::Borrowed("true"),
+                (0..s.len(), s.len()..s.len()),
+            )
+        } else if options.control_chars == ControlChars::Reject && s.contains(char::is_control) {
+            ParsedToken::Malformed(s, ErrorKind::ControlCharacter)
+        } else {
+            ParsedToken::Word(s)
+        }
+    }
+}
+
+/// Replaces the control characters in text added to the message with `U+FFFD`, if requested,
+/// except separators
+fn sanitize_message<'s>(text: &'s str, options: &ParseOptions<'_>) -> Cow<'s, str> {
+    let replaced = |ch: char| ch.is_control() && !options.separators.matches(ch);
+    if options.control_chars == ControlChars::Sanitize && text.contains(replaced) {
+        Cow::Owned(text.replace(replaced, "\u{FFFD}"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Returns the byte range of a subslice within a string
+fn span_of(s: &str, subslice: &str) -> Range<usize> {
+    let start = subslice.as_ptr() as usize - s.as_ptr() as usize;
+    start..start + subslice.len()
+}
+
+/// Checks whether the character is a double quote, or a single quote if allowed
+const fn is_quote(ch: char, single_quotes: bool) -> bool {
+    ch == '"' || (single_quotes && ch == '\'')
+}
+
+/// Splits the surrounding double quotes, or single quotes if allowed, off a string, returning
+/// the quote and the contents
+fn split_quotes(s: &str, single_quotes: bool) -> Option<(char, &str)> {
+    let quote = s.chars().next().filter(|ch| is_quote(*ch, single_quotes))?;
+    (s.len() > 1 && s.ends_with(quote)).then(|| (quote, &s[1..s.len() - 1]))
+}
+
+/// Removes one pair of surrounding double quotes, if both are present
+pub fn unquote(s: &str) -> &str {
+    split_quotes(s, false).map_or(s, |(_, contents)| contents)
+}
+
+/// Checks whether two keys are the same, ignoring surrounding double or single quotes, so keys
+/// parsed with [`ParseOptions::single_quotes`] match too
+pub(crate) fn keys_match(a: &str, b: &str) -> bool {
+    let unquote_key = |key| split_quotes(key, true).map_or(key, |(_, contents)| contents);
+    unquote_key(a) == unquote_key(b)
+}
+
+/// Checks whether a key only contains alphanumeric characters, `.`, `_`, and `-`.
+/// Quotes aren't allowed, as keys are validated without their surrounding quotes.
+pub fn default_key_validator(key: &str) -> bool {
+    key.chars()
+        .all(|ch| ch.is_alphanumeric() || matches!(ch, '.' | '_' | '-'))
+}
+
+/// Checks whether a key only contains characters allowed by Go's `go-logfmt/logfmt`
+fn go_key_validator(key: &str) -> bool {
+    !key.chars()
+        .any(|ch| matches!(ch, '=' | '"' | '\u{FFFD}') || ch.is_control())
+}
+
+/// Checks whether the string contains the quote, ignoring quotes escaped by a backslash
+pub(crate) fn contains_unescaped(s: &str, quote: char) -> bool {
+    let mut escaped = false;
+    s.chars().any(|ch| {
+        let found = !escaped && ch == quote;
+        escaped = !escaped && ch == '\\';
+        found
+    })
+}
+
+/// Checks whether a key or value is either unquoted or completely surrounded by quotes.
+/// Returns the contents without the quotes.
+fn check_quotes<'s>(s: &'s str, options: &ParseOptions<'_>) -> Result<&'s str, ErrorKind> {
+    match options.split_quotes(s) {
+        Some((quote, contents)) if !contains_unescaped(contents, quote) => Ok(contents),
+        None if !s.chars().any(|ch| options.is_quote(ch)) => Ok(s),
+        _ => Err(ErrorKind::UnbalancedQuote),
+    }
+}
+
+/// Validates an attribute key
+pub(crate) fn validate_key(key: &str, options: &ParseOptions<'_>) -> Result<(), ErrorKind> {
+    let contents = check_quotes(key, options)?;
+    if contents.is_empty() {
+        Err(ErrorKind::EmptyKey)
+    } else if !(options.key_validator)(contents) || !options.key_style.allows(contents) {
+        Err(ErrorKind::InvalidKey)
+    } else if contents.chars().count() > options.max_key_len {
+        Err(ErrorKind::KeyTooLong)
+    } else {
+        Ok(())
+    }
+}
+
+/// Validates an attribute value, which can be at most `max_len` characters long
+fn validate_value(
+    value: &str,
+    max_len: usize,
+    options: &ParseOptions<'_>,
+) -> Result<(), ErrorKind> {
+    let contents = check_quotes(value, options)?;
+    let quoted = contents.len() != value.len();
+    if value.is_empty() && !options.allow_empty_values {
+        Err(ErrorKind::EmptyValue)
+    } else if options.equals_in_value == EqualsInValue::Reject && !quoted && value.contains('=') {
+        Err(ErrorKind::EqualsInValue)
+    } else if !matches!(options.on_overlong_value, OverlongValue::Truncate(_))
+        && contents.chars().count() > max_len
+    {
+        Err(ErrorKind::ValueTooLong)
+    } else {
+        Ok(())
+    }
+}
+
+/// Removes the quotes from and lowercases a valid key, if requested
+fn process_key<'message>(key: &'message str, options: &ParseOptions<'_>) -> Cow<'message, str> {
+    let key = if options.strip_quotes {
+        options.unquote(key)
+    } else {
+        key
+    };
+    if options.normalize_keys_lowercase && key.chars().any(|ch| ch.is_ascii_uppercase()) {
+        Cow::Owned(key.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(key)
+    }
+}
+
+/// Removes the quotes from, decodes the escape sequences in, sanitizes, and truncates a valid
+/// value longer than `max_len` characters, if requested
+fn process_value<'message>(
+    value: &'message str,
+    max_len: usize,
+    options: &ParseOptions<'_>,
+) -> Result<Cow<'message, str>, ErrorKind> {
+    let (quote, contents) = match options.split_quotes(value) {
+        Some((quote, contents)) => (Some(quote), contents),
+        None => (None, value),
+    };
+
+    // Only quoted values are decoded
+    let mut contents = if quote.is_some() && options.decode_escapes {
+        decode_escapes(contents).map_err(|_| ErrorKind::InvalidEscape)?
+    } else {
+        Cow::Borrowed(contents)
+    };
+
+    // Reject or replace control characters, if requested
+    match options.control_chars {
+        ControlChars::Allow => {}
+        ControlChars::Reject => {
+            // Escape sequences are checked decoded, even if they're kept
+            let decoded = match quote {
+                Some(_) if !options.decode_escapes => {
+                    decode_escapes(&contents).unwrap_or(Cow::Borrowed(&contents))
+                }
+                _ => Cow::Borrowed(&*contents),
+            };
+            if decoded.contains(char::is_control) {
+                return Err(ErrorKind::ControlCharacter);
+            }
+        }
+        ControlChars::Sanitize if contents.contains(char::is_control) => {
+            contents = Cow::Owned(contents.replace(char::is_control, "\u{FFFD}"));
+        }
+        ControlChars::Sanitize => {}
+    }
+
+    // Truncate the value if it's too long and truncating is requested
+    if let OverlongValue::Truncate(length) = options.on_overlong_value
+        && contents.chars().count() > max_len
+        && let Some((index, _)) = contents.char_indices().nth(length.min(max_len))
+    {
+        contents = Cow::Owned(format!("{}\u{2026}", &contents[..index]));
+    }
+
+    // Put the quotes back, unless they should be removed
+    Ok(match (quote, contents) {
+        (Some(_), Cow::Borrowed(_)) if !options.strip_quotes => Cow::Borrowed(value),
+        (Some(quote), Cow::Owned(contents)) if !options.strip_quotes => {
+            Cow::Owned(format!("{quote}{contents}{quote}"))
+        }
+        (_, contents) => contents,
+    })
+}
+
+/// Decodes the escape sequences `\"`, `\'`, `\\`, `\n`, `\t`, `\r`, `\uXXXX`, and `\u{...}`.
+/// Only allocates if the string contains escape sequences.
+/// Returns the byte offset of an invalid escape sequence on error.
+fn decode_escapes(s: &str) -> Result<Cow<'_, str>, usize> {
+    // Return the string as is, if it doesn't contain escape sequences
+    if !s.contains('\\') {
+        return Ok(Cow::Borrowed(s));
+    }
+
+    // Decode the escape sequences
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(index) = rest.find('\\') {
+        decoded.push_str(&rest[..index]);
+        let offset = s.len() - rest.len() + index;
+        let mut chars = rest[index + 1..].chars();
+        decoded.push(match chars.next() {
+            Some(ch @ ('"' | '\'' | '\\')) => ch,
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('u') => {
+                // The code point is either 4 hexadecimal digits or 1 to 6 between braces
+                let after_u = chars.as_str();
+                let (digits, after) = match after_u.strip_prefix('{') {
+                    Some(braced) => {
+                        let end = braced.find('}').filter(|end| (1..=6).contains(end));
+                        let end = end.ok_or(offset)?;
+                        (&braced[..end], &braced[end + 1..])
+                    }
+                    None => {
+                        let digits = after_u.get(..4).ok_or(offset)?;
+                        (digits, &after_u[4..])
+                    }
+                };
+                chars = after.chars();
+                u32::from_str_radix(digits, 16)
+                    .ok()
+                    .filter(|_| digits.chars().all(|ch| ch.is_ascii_hexdigit()))
+                    .and_then(char::from_u32)
+                    .ok_or(offset)?
+            }
+            _ => return Err(offset),
+        });
+        rest = chars.as_str();
+    }
+    decoded.push_str(rest);
+    Ok(Cow::Owned(decoded))
+}
+
+/// Removes one pair of surrounding double quotes, if both are present, and decodes the escape
+/// sequences `\"`, `\'`, `\\`, `\n`, `\t`, `\r`, `\uXXXX`, and `\u{...}`.
+/// Only allocates if the value contains escape sequences.
+pub fn unescape(value: &str) -> Result<Cow<'_, str>, UnescapeError> {
+    let contents = unquote(value);
+    let quote_len = usize::from(contents.len() != value.len());
+    decode_escapes(contents).map_err(|offset| UnescapeError {
+        offset: offset + quote_len,
+    })
+}
+
+/// Tracks whether bytes of a line are in a string, to find newlines outside of strings.
+/// Quotes, backslashes, and newlines are ASCII, so they can't be part of other characters.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct QuoteState {
+    quote: Option<u8>,
+    escaped: bool,
+}
+
+impl QuoteState {
+    /// Updates the state with the next byte of the line
+    pub(crate) fn update(&mut self, byte: u8, is_quote: impl Fn(char) -> bool) {
+        match self.quote {
+            Some(_) if self.escaped => self.escaped = false,
+            Some(_) if byte == b'\\' => self.escaped = true,
+            Some(quote) if byte == quote => self.quote = None,
+            None if is_quote(char::from(byte)) => self.quote = Some(byte),
+            _ => {}
+        }
+    }
+
+    /// Checks whether the next byte is in a string
+    pub(crate) const fn in_string(&self) -> bool {
+        self.quote.is_some()
+    }
+}
+
+/// How lines ending in a backslash outside of strings are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineContinuation {
+    /// Keep the backslash, ending the line
+    #[default]
+    Disabled,
+
+    /// Join the line with the next one, removing the backslash and the newline
+    Join,
+
+    /// Join the line with the next one, replacing the backslash and the newline by a space
+    JoinWithSpace,
+}
+
+impl LineContinuation {
+    /// Removes the backslash continuing the line, if it's continued
+    fn strip(self, line: &str) -> Option<&str> {
+        match self {
+            Self::Disabled => None,
+            Self::Join | Self::JoinWithSpace => line.strip_suffix('\\'),
+        }
+    }
+}
+
+/// Returns an iterator lazily parsing the lines of the string, split on `\n` with a trailing
+/// `\r` removed and empty lines skipped. Each log message is yielded with its line number,
+/// starting at 1.
+pub fn parse_lines(s: &str) -> ParseLines<'_> {
+    ParseLines {
+        rest: s,
+        line: 1,
+        multiline_values: false,
+        continuation: LineContinuation::Disabled,
+    }
+}
+
+/// An iterator over the log messages of the lines of a string, returned by [`parse_lines`]
+#[derive(Debug, Clone)]
+pub struct ParseLines<'input> {
+    rest: &'input str,
+    line: usize,
+    multiline_values: bool,
+    continuation: LineContinuation,
+}
+
+impl ParseLines<'_> {
+    /// Set whether a newline in a string is part of its value, instead of ending the line,
+    /// so a log message can span lines, like
+    /// [`StreamParser::with_newlines_in_quotes`](crate::StreamParser::with_newlines_in_quotes).
+    /// Disabled by default.
+    #[must_use]
+    pub const fn with_multiline_values(mut self, multiline_values: bool) -> Self {
+        self.multiline_values = multiline_values;
+        self
+    }
+
+    /// Set how lines ending in a backslash outside of strings are handled, joining them with the
+    /// next line before parsing. Disabled by default.
+    #[must_use]
+    pub const fn with_line_continuation(mut self, continuation: LineContinuation) -> Self {
+        self.continuation = continuation;
+        self
+    }
+}
+
+impl<'input> ParseLines<'input> {
+    /// Splits the next line off the rest, without the line ending.
+    /// Returns whether the line is continued, without the backslash continuing it.
+    fn split_line(&mut self) -> (&'input str, bool) {
+        // Find the newline ending the line, counting the newlines in strings
+        let mut state = QuoteState::default();
+        let mut in_string = false;
+        let end = self.rest.bytes().position(|byte| {
+            if byte == b'\n' {
+                self.line += 1;
+                in_string = state.in_string();
+                if !(self.multiline_values && in_string) {
+                    return true;
+                }
+            }
+            state.update(byte, |ch| ch == '"');
+            false
+        });
+        let (line, rest) = match end {
+            Some(end) => (&self.rest[..end], &self.rest[end + 1..]),
+            None => (self.rest, ""),
+        };
+        self.rest = rest;
+
+        // Backslashes in strings don't continue the line
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match self.continuation.strip(line) {
+            Some(line) if end.is_some() && !in_string => (line, true),
+            _ => (line, false),
+        }
+    }
+}
+
+impl<'input> Iterator for ParseLines<'input> {
+    type Item = (usize, Result<Log<'input>, UnclosedString>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let number = self.line;
+            let (line, mut continued) = self.split_line();
+            if !continued {
+                if line.is_empty() {
+                    continue;
+                }
+                return Some((number, Log::parse(line)));
+            }
+
+            // Join the continued lines
+            let mut joined = String::from(line);
+            while continued {
+                if self.continuation == LineContinuation::JoinWithSpace {
+                    joined.push(' ');
+                }
+                let (line, next_continued) = self.split_line();
+                joined.push_str(line);
+                continued = next_continued;
+            }
+            if !joined.trim().is_empty() {
+                return Some((number, Log::parse(&joined).map(Log::into_owned)));
+            }
+        }
+        None
+    }
+}
+
+impl FusedIterator for ParseLines<'_> {}
+
+impl<'message> Log<'message> {
+    /// Parse the log message
+    pub fn parse(s: &'message str) -> Result<Self, UnclosedString> {
+        // The default options only allow unclosed strings as errors
+        Self::parse_with(s, &ParseOptions::default()).map_err(|_| UnclosedString)
+    }
+
+    /// Parse the log message, like [`Log::parse`], keeping the input for [`Log::to_original`]
+    pub fn parse_lossless(s: &'message str) -> Result<Self, UnclosedString> {
+        let mut log = Self::parse(s)?;
+        log.original = Some(Cow::Borrowed(s));
+        Ok(log)
+    }
+
+    /// Parse the log message using the given options
+    pub fn parse_with(s: &'message str, options: &ParseOptions<'_>) -> Result<Self, ParseError> {
+        // Return an error if the input is too long, before looking at it
+        if let Some(max) = options.max_input_len.filter(|max| s.len() > *max) {
+            return Err(ParseError {
+                kind: ErrorKind::InputTooLong,
+                offset: max,
+                len: s.len() - max,
+            });
+        }
+
+        // Create a tokenizer ignoring a leading byte order mark and trailing line endings, if
+        // requested
+        let mut tokens = Tokenizer::new(s, options);
+        let (content_start, s) = (tokens.content_start, tokens.input);
+
+        // Create a list of attributes, which allocates at the first attribute to not allocate
+        // for lines without attributes, the message string, and a variable to store whether the
+        // message property was found.
+        let capacity = options
+            .max_attributes
+            .map_or(options.attribute_capacity_hint, |max| {
+                max.min(options.attribute_capacity_hint)
+            });
+        let mut attributes = Vec::<Attribute<'_>>::new();
+        let mut message = Message::new();
+        let mut message_spans = Vec::new();
+        let mut message_property_found = false;
+        let mut attribute_found = false;
+        let mut token_found = false;
+
+        // Iterate through the string, parsing every token
+        while let Some(span) = tokens.next(options) {
+            let Range { start, end } = span?;
+            token_found = true;
+
+            // Parse the found token
+            let token = &s[start..end];
+            match ParsedToken::parse(token, options) {
+                // If it's malformed in strict mode or too long when that's an error, return an
+                // error
+                ParsedToken::Malformed(_, kind)
+                    if options.strict
+                        || (kind == ErrorKind::ValueTooLong
+                            && options.on_overlong_value == OverlongValue::Error) =>
+                {
+                    return Err(ParseError {
+                        kind,
+                        offset: start,
+                        len: end - start,
+                    });
+                }
+
+                // If it's a word after an attribute, add the rest of the line to the message if
+                // trailing text is the message
+                ParsedToken::Word(_) | ParsedToken::Malformed(..)
+                    if options.trailing_text_as_message && attribute_found =>
+                {
+                    if !message_property_found {
+                        if !message.is_empty() {
+                            message.push_str(" ");
+                        }
+                        let rest = s[start..].trim_end_matches(|ch| options.separators.matches(ch));
+                        message.push_str(&sanitize_message(rest, options));
+                        if options.record_spans {
+                            message_spans.push(start..start + rest.len());
+                        }
+                    }
+                    break;
+                }
+
+                // If it's a word, add it to the message as a word
+                ParsedToken::Word(word) | ParsedToken::Malformed(word, _) => {
+                    if !message_property_found {
+                        if !message.is_empty() {
+                            message.push_str(" ");
+                        }
+                        message.push_str(&sanitize_message(word, options));
+                        if options.record_spans {
+                            message_spans.push(start..end);
+                        }
+                    }
+                }
+
+                // If it's an attribute
+                ParsedToken::Attribute(key, value, (key_span, value_span)) => {
+                    attribute_found = true;
+                    let span = options.record_spans.then(|| {
+                        (
+                            start + key_span.start..start + key_span.end,
+                            start + value_span.start..start + value_span.end,
+                        )
+                    });
+
+                    // Replace the message if this attribute is the message
+                    if options.is_message_key(&key) {
+                        message = value.into();
+                        message_spans.clear();
+                        message_spans.extend(span.map(|(_, value_span)| value_span));
+                        message_property_found = true;
+                        continue;
+                    }
+
+                    // Skip the attribute if it's filtered out
+                    if !options.key_filter.keeps(options.unquote(&key)) {
+                        continue;
+                    }
+
+                    // Check whether the attribute already exists, ignoring surrounding quotes,
+                    // unless all duplicates are kept
+                    let existing = match options.duplicates {
+                        DuplicatePolicy::KeepAll => None,
+                        DuplicatePolicy::Overwrite | DuplicatePolicy::KeepFirst => {
+                            attributes.iter().position(|attribute| {
+                                options.unquote(&attribute.key) == options.unquote(&key)
+                            })
+                        }
+                    };
+                    match existing {
+                        // If it does, change it's value or keep the first one
+                        Some(index) => {
+                            if options.duplicates == DuplicatePolicy::Overwrite {
+                                let attribute = &mut attributes[index];
+                                attribute.value = value;
+                                if let (Some(existing), Some((_, value_span))) =
+                                    (&mut attribute.span, span)
+                                {
+                                    existing.1 = value_span;
+                                }
+                            }
+                        }
+                        // Otherwise, add it to the attributes if the list isn't full yet
+                        None if options
+                            .max_attributes
+                            .is_none_or(|max| attributes.len() < max) =>
+                        {
+                            if attributes.capacity() == 0 {
+                                attributes.reserve(capacity);
+                            }
+                            attributes.push(Attribute { key, value, span });
+                        }
+                        // If the list is full in strict mode, return an error
+                        None if options.strict => {
+                            return Err(ParseError {
+                                kind: ErrorKind::TooManyAttributes,
+                                offset: start,
+                                len: end - start,
+                            });
+                        }
+                        // If the list is full and no message was found yet, add it to the message
+                        None => {
+                            if !message_property_found {
+                                if !message.is_empty() {
+                                    message.push_str(" ");
+                                }
+                                message.push_str(&sanitize_message(token, options));
+                                if options.record_spans {
+                                    message_spans.push(start..end);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Store the full string as message, if no message was found and the string isn't blank.
+        let message_is_fallback = message.is_empty() && (token_found || options.keep_blank_input);
+        let message = if message_is_fallback {
+            if options.record_spans {
+                message_spans.push(content_start..s.len());
+            }
+            sanitize_message(&s[content_start..], options).into()
+        } else {
+            message
+        };
+        Ok(Self {
+            message,
+            attributes,
+            message_is_fallback,
+            message_spans,
+            original: None,
+        })
+    }
+
+    /// Parse the log message from bytes, like [`Log::parse`].
+    /// Returns an error at the first byte that isn't valid UTF-8.
+    pub fn parse_bytes(bytes: &'message [u8]) -> Result<Self, ParseError> {
+        let s = str::from_utf8(bytes).map_err(|error| ParseError {
+            kind: ErrorKind::InvalidUtf8,
+            offset: error.valid_up_to(),
+            len: error
+                .error_len()
+                .unwrap_or(bytes.len() - error.valid_up_to()),
+        })?;
+        Self::parse_with(s, &ParseOptions::default())
+    }
+
+    /// Parse the log message from bytes, like [`Log::parse`], replacing invalid UTF-8 with the
+    /// replacement character.
+    /// Valid input is borrowed, otherwise the log message owns its message and attributes.
+    pub fn parse_bytes_lossy(bytes: &'message [u8]) -> Result<Self, ParseError> {
+        match String::from_utf8_lossy(bytes) {
+            Cow::Borrowed(s) => Self::parse_with(s, &ParseOptions::default()),
+            Cow::Owned(s) => Log::parse_with(&s, &ParseOptions::default()).map(Log::into_owned),
+        }
+    }
+
+    /// Parse a flat JSON object, with the default message keys as message.
+    /// Strings are decoded and quoted and escaped where logfmt needs it, like a parsed logfmt
+    /// line stores them. Numbers, booleans, and null are kept as written.
+    pub fn parse_json(s: &'message str) -> Result<Self, JsonError> {
+        JsonParser {
+            input: s,
+            offset: 0,
+        }
+        .parse_log()
+    }
+
+    /// Parse the log message as JSON object if it starts with `{`, or as logfmt otherwise
+    pub fn parse_auto(s: &'message str) -> Result<Self, AutoParseError> {
+        if s.trim_start_matches(JSON_WHITESPACE).starts_with('{') {
+            Self::parse_json(s).map_err(AutoParseError::Json)
+        } else {
+            Self::parse(s).map_err(AutoParseError::Logfmt)
+        }
+    }
+}
+
+/// The characters JSON allows between tokens
+const JSON_WHITESPACE: [char; 4] = [' ', '\t', '\n', '\r'];
+
+/// A parser for flat JSON objects
+struct JsonParser<'input> {
+    input: &'input str,
+    offset: usize,
+}
+
+impl<'input> JsonParser<'input> {
+    /// Creates an error at the current offset
+    const fn error(&self, kind: JsonErrorKind) -> JsonError {
+        JsonError {
+            kind,
+            offset: self.offset,
+        }
+    }
+
+    /// Returns the next byte, if there is one
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.offset..];
+        self.offset += rest.len() - rest.trim_start_matches(JSON_WHITESPACE).len();
+    }
+
+    /// Skips whitespace and consumes the expected byte
+    fn expect(&mut self, expected: u8) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(byte) if byte == expected => {
+                self.offset += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.error(JsonErrorKind::UnexpectedCharacter)),
+            None => Err(self.error(JsonErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    /// Parses an object of scalar values into a log message, with the strings encoded as logfmt
+    fn parse_log(mut self) -> Result<Log<'input>, JsonError> {
+        let options = ParseOptions::default();
+        let mut log = Log::default();
+        self.expect(b'{')?;
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let key_offset = self.offset;
+                let key = self.parse_string()?;
+                self.expect(b':')?;
+                let value = encode_value(self.parse_scalar()?);
+                if options.is_message_key(&key) {
+                    log.set_message(value);
+                } else {
+                    log.insert(encode_value(key), value)
+                        .map_err(|_| JsonError {
+                            kind: JsonErrorKind::InvalidKey,
+                            offset: key_offset,
+                        })?;
+                }
+
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.offset += 1,
+                    Some(b'}') => {
+                        self.offset += 1;
+                        break;
+                    }
+                    Some(_) => return Err(self.error(JsonErrorKind::UnexpectedCharacter)),
+                    None => return Err(self.error(JsonErrorKind::UnexpectedEnd)),
+                }
+            }
+        }
+
+        // Only whitespace is allowed after the object
+        self.skip_whitespace();
+        if self.offset < self.input.len() {
+            return Err(self.error(JsonErrorKind::UnexpectedCharacter));
+        }
+        Ok(log)
+    }
+
+    /// Parses a string, number, boolean, or null, returning it as text
+    fn parse_scalar(&mut self) -> Result<Cow<'input, str>, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(self.error(JsonErrorKind::UnexpectedEnd)),
+            Some(b'"') => self.parse_string(),
+            Some(b'{' | b'[') => Err(self.error(JsonErrorKind::NestedValue)),
+            Some(b'-' | b'0'..=b'9') => self.parse_number().map(Cow::Borrowed),
+            Some(_) => {
+                let rest = &self.input[self.offset..];
+                let len = ["true", "false", "null"]
+                    .into_iter()
+                    .find(|literal| rest.starts_with(literal))
+                    .ok_or(self.error(JsonErrorKind::UnexpectedCharacter))?
+                    .len();
+                self.offset += len;
+                Ok(Cow::Borrowed(&rest[..len]))
+            }
+        }
+    }
+
+    /// Parses a number, returning it as written
+    fn parse_number(&mut self) -> Result<&'input str, JsonError> {
+        let start = self.offset;
+        let bytes = self.input.as_bytes();
+        let digits = |offset: usize| {
+            bytes[offset..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count()
+        };
+        let invalid = JsonError {
+            kind: JsonErrorKind::InvalidNumber,
+            offset: start,
+        };
+
+        // The integer part is required and can't have leading zeros
+        let mut end = start + usize::from(bytes[start] == b'-');
+        let integer = digits(end);
+        if integer == 0 || (integer > 1 && bytes[end] == b'0') {
+            return Err(invalid);
+        }
+        end += integer;
+
+        if bytes.get(end) == Some(&b'.') {
+            let fraction = digits(end + 1);
+            if fraction == 0 {
+                return Err(invalid);
+            }
+            end += 1 + fraction;
+        }
+
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            end += 1;
+            if matches!(bytes.get(end), Some(b'+' | b'-')) {
+                end += 1;
+            }
+            let exponent = digits(end);
+            if exponent == 0 {
+                return Err(invalid);
+            }
+            end += exponent;
+        }
+
+        self.offset = end;
+        Ok(&self.input[start..end])
+    }
+
+    /// Parses a string, only allocating if it contains escape sequences
+    fn parse_string(&mut self) -> Result<Cow<'input, str>, JsonError> {
+        self.expect(b'"')?;
+        let mut decoded = None::<String>;
+        let mut chunk_start = self.offset;
+        loop {
+            match self.peek() {
+                None => return Err(self.error(JsonErrorKind::UnexpectedEnd)),
+                Some(b'"') => {
+                    let chunk = &self.input[chunk_start..self.offset];
+                    self.offset += 1;
+                    return Ok(match decoded {
+                        Some(mut decoded) => {
+                            decoded.push_str(chunk);
+                            Cow::Owned(decoded)
+                        }
+                        None => Cow::Borrowed(chunk),
+                    });
+                }
+                Some(b'\\') => {
+                    let decoded = decoded.get_or_insert_default();
+                    decoded.push_str(&self.input[chunk_start..self.offset]);
+                    decoded.push(self.parse_escape()?);
+                    chunk_start = self.offset;
+                }
+                Some(0..0x20) => return Err(self.error(JsonErrorKind::UnexpectedCharacter)),
+                // Multi-byte characters never contain quotes or backslashes
+                Some(_) => self.offset += 1,
+            }
+        }
+    }
+
+    /// Parses the escape sequence starting at the current backslash
+    fn parse_escape(&mut self) -> Result<char, JsonError> {
+        let start = self.offset;
+        let invalid = JsonError {
+            kind: JsonErrorKind::InvalidEscape,
+            offset: start,
+        };
+        let escaped = self.input.as_bytes().get(start + 1).copied();
+        self.offset += 2;
+        Ok(match escaped {
+            None => {
+                self.offset = self.input.len();
+                return Err(self.error(JsonErrorKind::UnexpectedEnd));
+            }
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                let high = self.parse_hex().ok_or(invalid)?;
+                let code = if (0xD800..0xDC00).contains(&high) {
+                    // A high surrogate has to be followed by an escaped low surrogate
+                    let low = self.input[self.offset..]
+                        .starts_with("\\u")
+                        .then(|| {
+                            self.offset += 2;
+                            self.parse_hex()
+                        })
+                        .flatten()
+                        .filter(|low| (0xDC00..0xE000).contains(low))
+                        .ok_or(invalid)?;
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or(invalid)?
+            }
+            Some(_) => return Err(invalid),
+        })
+    }
+
+    /// Parses the four hexadecimal digits of a `\u` escape sequence
+    fn parse_hex(&mut self) -> Option<u32> {
+        let digits = self
+            .input
+            .get(self.offset..self.offset + 4)
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))?;
+        self.offset += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String, vec::Vec};
+    use core::ops::ControlFlow;
+
+    use crate::log::insert_attribute;
+    use crate::{Attribute, LineContinuation, Log, LogVisitor, Token, UnclosedString};
+
+    #[test]
+    fn attribute_iter() {
+        let line = "\u{FEFF}request done a=1 b=\"x y\" =bad msg=hi b=2 c=\r\n";
+
+        // Collect into a fixed array, without allocating
+        let mut found = [("", ""); 5];
+        let mut count = 0;
+        for (slot, attribute) in found.iter_mut().zip(super::attributes(line)) {
+            *slot = attribute.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 5);
+        assert_eq!(
+            found,
+            [
+                ("a", "1"),
+                ("b", "\"x y\""),
+                ("msg", "hi"),
+                ("b", "2"),
+                ("c", "")
+            ]
+        );
+
+        let mut attributes = super::attributes("a=1 b=\"open c=3");
+        assert_eq!(attributes.next(), Some(Ok(("a", "1"))));
+        assert_eq!(attributes.next(), Some(Err(UnclosedString)));
+        assert_eq!(attributes.next(), None);
+    }
+
+    #[test]
+    fn parse_visit() {
+        /// Fills a log message like `Log::parse`, within the attribute limit
+        #[derive(Default)]
+        struct Filler<'input> {
+            message: Option<&'input str>,
+            words: Vec<&'input str>,
+            attributes: Vec<Attribute<'input>>,
+            events: Vec<String>,
+        }
+
+        impl<'input> LogVisitor<'input> for Filler<'input> {
+            fn word(&mut self, word: &'input str) -> ControlFlow<()> {
+                self.events.push(format!("word {word}"));
+                self.words.push(word);
+                ControlFlow::Continue(())
+            }
+
+            fn attribute(&mut self, key: &'input str, value: &'input str) -> ControlFlow<()> {
+                self.events.push(format!("attribute {key}={value}"));
+                insert_attribute(&mut self.attributes, key.into(), value.into());
+                if key == "cmd" {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            }
+
+            fn message_override(&mut self, message: &'input str) -> ControlFlow<()> {
+                self.events.push(format!("message {message}"));
+                self.message = Some(message);
+                ControlFlow::Continue(())
+            }
+        }
+
+        let line = "hello a=1 world msg=\"greeting here\" a=2 b=\"x y\"";
+        let mut filler = Filler::default();
+        super::parse_visit(line, &mut filler).unwrap();
+        assert_eq!(
+            filler.events,
+            [
+                "word hello",
+                "attribute a=1",
+                "word world",
+                "message \"greeting here\"",
+                "attribute a=2",
+                "attribute b=\"x y\"",
+            ]
+        );
+        let message = filler
+            .message
+            .map_or_else(|| filler.words.join(" "), String::from);
+        let log = Log {
+            message: message.into(),
+            attributes: filler.attributes,
+            message_is_fallback: false,
+            message_spans: Vec::new(),
+            original: None,
+        };
+        assert_eq!(log, Log::parse(line).unwrap());
+
+        // Breaking stops the scan
+        let mut filler = Filler::default();
+        super::parse_visit("start cmd=reboot after x=1 \"open", &mut filler).unwrap();
+        assert_eq!(filler.events, ["word start", "attribute cmd=reboot"]);
+        assert_eq!(
+            super::parse_visit("a=1 \"open", &mut Filler::default()),
+            Err(UnclosedString)
+        );
+    }
+
+    #[test]
+    fn token_iter() {
+        let tokens: Vec<_> = super::tokens("started a=1 in \"quoted words\" b=\"x y\" =bad done")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Word("started"),
+                Token::Attribute("a", "1"),
+                Token::Word("in"),
+                Token::Word("\"quoted words\""),
+                Token::Attribute("b", "\"x y\""),
+                Token::Word("=bad"),
+                Token::Word("done"),
+            ]
+        );
+
+        let mut tokens = super::tokens("word \"open");
+        assert_eq!(tokens.next(), Some(Ok(Token::Word("word"))));
+        assert_eq!(tokens.next(), Some(Err(UnclosedString)));
+        assert_eq!(tokens.next(), None);
+    }
+
+    #[test]
+    fn parse_lines() {
+        let buffer = "msg=first a=1\r\n\nmsg=\"unclosed b=2\r\nthird c=3\n";
+        let mut lines = super::parse_lines(buffer);
+        let (number, log) = lines.next().unwrap();
+        assert_eq!(number, 1);
+        assert_eq!(log.unwrap().attributes(), [("a", "1")]);
+        assert_eq!(lines.next(), Some((3, Err(UnclosedString))));
+        let (number, log) = lines.next().unwrap();
+        assert_eq!(number, 4);
+        let log = log.unwrap();
+        assert_eq!(log.message(), "third");
+        assert_eq!(log.attributes(), [("c", "3")]);
+        assert_eq!(lines.next(), None);
+
+        // Strings can span lines with multiline values
+        let buffer = "error stack=\"line1\n  at foo()\" code=7\nnext a=1";
+        let mut lines = super::parse_lines(buffer).with_multiline_values(true);
+        let (number, log) = lines.next().unwrap();
+        assert_eq!(number, 1);
+        assert_eq!(
+            log.unwrap().attributes(),
+            [("stack", "\"line1\n  at foo()\""), ("code", "7")]
+        );
+        let (number, log) = lines.next().unwrap();
+        assert_eq!(number, 3);
+        assert_eq!(log.unwrap().attributes(), [("a", "1")]);
+        assert_eq!(lines.next(), None);
+        let numbers: Vec<_> = super::parse_lines(buffer)
+            .map(|(number, log)| (number, log.is_ok()))
+            .collect();
+        assert_eq!(numbers, [(1, false), (2, false), (3, true)]);
+    }
+
+    #[test]
+    fn line_continuation() {
+        let buffer =
+            "request done \\\nmethod=GET path=/a\\\r\nb=\"C:\\\\\"\ndir=\"C:\\\\\"\nlast=1\\";
+        let logs: Vec<_> = super::parse_lines(buffer)
+            .with_line_continuation(LineContinuation::Join)
+            .map(|(number, log)| (number, log.unwrap()))
+            .collect();
+        assert_eq!(
+            logs,
+            [
+                (
+                    1,
+                    Log::parse("request done method=GET path=/ab=\"C:\\\\\"").unwrap()
+                ),
+                (4, Log::parse("dir=\"C:\\\\\"").unwrap()),
+                (5, Log::parse("last=1\\").unwrap()),
+            ]
+        );
+
+        let mut lines =
+            super::parse_lines(buffer).with_line_continuation(LineContinuation::JoinWithSpace);
+        let (_, log) = lines.next().unwrap();
+        assert_eq!(
+            log.unwrap(),
+            Log::parse("request done  method=GET path=/a b=\"C:\\\\\"").unwrap()
+        );
+
+        // Backslashes at the end of a line in a string are part of the value
+        let buffer = "a=\"one \\\ntwo\" b=2";
+        let mut lines = super::parse_lines(buffer)
+            .with_multiline_values(true)
+            .with_line_continuation(LineContinuation::Join);
+        let (_, log) = lines.next().unwrap();
+        assert_eq!(
+            log.unwrap().attributes(),
+            [("a", "\"one \\\ntwo\""), ("b", "2")]
+        );
+    }
+}
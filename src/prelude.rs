@@ -0,0 +1,3 @@
+//! The items most users need, to import them at once with `use logfmt_nostd::prelude::*`
+
+pub use crate::{Encoder, Log, ParseError, ParseOptions};
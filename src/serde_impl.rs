@@ -0,0 +1,206 @@
+//! Serde support, enabled by the `serde` feature
+
+use alloc::string::{String, ToString};
+use core::fmt;
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, DeserializeSeed, MapAccess, Visitor},
+    ser::SerializeMap,
+};
+
+use crate::{Log, OwnedLog, ParseOptions, decode_quoted, encoder::encode_value};
+
+impl Serialize for Log<'_> {
+    /// Serializes the log as a map with the message as `msg` entry followed by the attributes in
+    /// input order, without surrounding quotes and with escape sequences of quoted strings
+    /// decoded.
+    /// Empty and fallback messages are skipped, duplicate keys are serialized as is.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let has_message = !self.message().is_empty() && !self.message_is_fallback();
+        let mut map =
+            serializer.serialize_map(Some(self.attribute_count() + usize::from(has_message)))?;
+        if has_message {
+            map.serialize_entry("msg", &decode_quoted(self.message()))?;
+        }
+        for (key, value) in self {
+            map.serialize_entry(&decode_quoted(key), &decode_quoted(value))?;
+        }
+        map.end()
+    }
+}
+
+/// Deserializes a scalar value into its string form, naming the key in errors
+struct ScalarSeed<'key> {
+    key: &'key str,
+}
+
+impl<'de> DeserializeSeed<'de> for ScalarSeed<'_> {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl Visitor<'_> for ScalarSeed<'_> {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a string, number, boolean, or null for key {:?}",
+            self.key
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(value.into())
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(value)
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(value.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(value.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(value.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(value.to_string())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok("null".into())
+    }
+}
+
+/// Deserializes a map into a log
+struct LogVisitor;
+
+impl<'de> Visitor<'de> for LogVisitor {
+    type Value = Log<'static>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of scalar values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let options = ParseOptions::default();
+        let mut log = Log::default();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = encode_value(map.next_value_seed(ScalarSeed { key: &key })?.into());
+            if options.is_message_key(&key) {
+                log.set_message(value.into_owned());
+            } else {
+                // Name the key as it was deserialized in errors
+                let encoded = encode_value(key.as_str().into()).into_owned();
+                log.insert(encoded, value.into_owned())
+                    .map_err(|mut error| {
+                        error.key = key;
+                        de::Error::custom(error)
+                    })?;
+            }
+        }
+        Ok(log)
+    }
+}
+
+impl<'de> Deserialize<'de> for Log<'static> {
+    /// Deserializes a map of scalar values, using the default message keys as message.
+    /// Numbers, booleans, and null are converted to strings, strings are quoted and escaped
+    /// where logfmt needs it, and keys are validated like [`Log::insert`].
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(LogVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for OwnedLog {
+    /// Deserializes a map of scalar values, like [`Log`]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Log::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use crate::{DuplicatePolicy, Log, OwnedLog, ParseOptions};
+
+    #[test]
+    fn serialize() {
+        let line = "this is foo=bar a \"key\"=\"with \\\"spaces\\\"\" message duration=10";
+        let log = Log::parse(line).unwrap();
+        assert_eq!(
+            serde_json::to_string(&log).unwrap(),
+            r#"{"msg":"this is a message","foo":"bar","key":"with \"spaces\"","duration":"10"}"#
+        );
+
+        let log = Log::parse("msg=\"quoted message\" b=2 a=1").unwrap();
+        assert_eq!(
+            serde_json::to_string(&log).unwrap(),
+            r#"{"msg":"quoted message","b":"2","a":"1"}"#
+        );
+
+        // Fallback messages are skipped
+        let log = Log::parse("b=2 a=1").unwrap();
+        assert_eq!(serde_json::to_string(&log).unwrap(), r#"{"b":"2","a":"1"}"#);
+
+        // Duplicates are serialized as is
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::KeepAll,
+            ..ParseOptions::default()
+        };
+        let log = Log::parse_with("a=1 \"a\"=2", &options).unwrap();
+        assert_eq!(serde_json::to_string(&log).unwrap(), r#"{"a":"1","a":"2"}"#);
+    }
+
+    #[test]
+    fn deserialize() {
+        let json = r#"{"msg": "hi", "duration": "10", "count": 3, "ratio": 0.5, "ok": true, "parent": null}"#;
+        let log = serde_json::from_str::<Log<'_>>(json).unwrap();
+        assert_eq!(log.message(), "hi");
+        assert_eq!(
+            log.attributes(),
+            [
+                ("duration", "10"),
+                ("count", "3"),
+                ("ratio", "0.5"),
+                ("ok", "true"),
+                ("parent", "null")
+            ]
+        );
+
+        // Strings are stored as written in logfmt, so they're decoded once
+        let json = r#"{"q":"\"x\"","path":"C:\\temp"}"#;
+        let log = serde_json::from_str::<Log<'_>>(json).unwrap();
+        assert_eq!(log.get("path"), Some("\"C:\\\\temp\""));
+        assert_eq!(log.get_unescaped("path").unwrap().unwrap(), "C:\\temp");
+        assert_eq!(serde_json::to_string(&log).unwrap(), json);
+
+        let owned = serde_json::from_str::<OwnedLog>(r#"{"message": "hi", "a": -1}"#).unwrap();
+        assert_eq!(owned.message(), "hi");
+        assert_eq!(owned.get("a"), Some("-1"));
+
+        // Nested values and keys that can't be written as logfmt are rejected
+        let error = serde_json::from_str::<Log<'_>>(r#"{"a": {"b": 1}}"#).unwrap_err();
+        assert!(error.to_string().contains(r#"for key "a""#), "{error}");
+        let error = serde_json::from_str::<Log<'_>>(r#"{"a": [1]}"#).unwrap_err();
+        assert!(error.to_string().contains(r#"for key "a""#), "{error}");
+        let error = serde_json::from_str::<Log<'_>>(r#"{"bad key": 1}"#).unwrap_err();
+        assert!(
+            error.to_string().contains(r#"invalid key "bad key""#),
+            "{error}"
+        );
+        assert!(serde_json::from_str::<Log<'_>>("[]").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Owned messages stored inline when they're short, to not allocate for short prose lines
+
+use alloc::{borrow::Cow, string::String};
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+/// The maximum number of bytes stored inline, keeping [`SmallString`] as large as a `String`
+const INLINE_CAPACITY: usize = 23;
+
+/// An owned string stored inline, until it's longer than [`INLINE_CAPACITY`] bytes and spills to
+/// a `String`
+#[derive(Clone)]
+pub(crate) enum SmallString {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Heap(String),
+}
+
+impl SmallString {
+    /// Create an empty string, without allocating
+    pub(crate) const fn new() -> Self {
+        Self::Inline {
+            len: 0,
+            bytes: [0; INLINE_CAPACITY],
+        }
+    }
+
+    /// Return the string
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Self::Inline { len, bytes } => {
+                core::str::from_utf8(&bytes[..usize::from(*len)]).expect("only strings are pushed")
+            }
+            Self::Heap(string) => string,
+        }
+    }
+
+    /// Append the string, moving to the heap if it doesn't fit inline anymore
+    pub(crate) fn push_str(&mut self, s: &str) {
+        match self {
+            Self::Inline { len, bytes } if usize::from(*len) + s.len() <= INLINE_CAPACITY => {
+                let start = usize::from(*len);
+                bytes[start..start + s.len()].copy_from_slice(s.as_bytes());
+                *len += s.len() as u8;
+            }
+            Self::Inline { .. } => {
+                let mut string = String::with_capacity(self.as_str().len() + s.len());
+                string.push_str(self.as_str());
+                string.push_str(s);
+                *self = Self::Heap(string);
+            }
+            Self::Heap(string) => string.push_str(s),
+        }
+    }
+
+    /// Convert the string into a `String`, allocating if it's stored inline
+    pub(crate) fn into_string(self) -> String {
+        match self {
+            Self::Inline { .. } => self.as_str().into(),
+            Self::Heap(string) => string,
+        }
+    }
+}
+
+impl From<&str> for SmallString {
+    fn from(s: &str) -> Self {
+        let mut string = Self::new();
+        string.push_str(s);
+        string
+    }
+}
+
+impl fmt::Write for SmallString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+/// The message of a log, either borrowed from the input like a `Cow`, or owned as
+/// [`SmallString`].
+/// Compared, ordered, and hashed as string.
+#[derive(Clone)]
+pub(crate) enum Message<'message> {
+    Borrowed(&'message str),
+    Owned(SmallString),
+}
+
+impl<'message> Message<'message> {
+    /// Create an empty message, without allocating
+    pub(crate) const fn new() -> Self {
+        Self::Borrowed("")
+    }
+
+    /// Append the string, taking ownership of the message if it's borrowed
+    pub(crate) fn push_str(&mut self, s: &str) {
+        match self {
+            Self::Borrowed(borrowed) => {
+                let mut owned = SmallString::from(*borrowed);
+                owned.push_str(s);
+                *self = Self::Owned(owned);
+            }
+            Self::Owned(owned) => owned.push_str(s),
+        }
+    }
+
+    /// Convert the message into one that doesn't borrow the input, storing short messages inline
+    pub(crate) fn into_owned(self) -> Message<'static> {
+        match self {
+            Self::Borrowed(borrowed) => Message::Owned(SmallString::from(borrowed)),
+            Self::Owned(owned) => Message::Owned(owned),
+        }
+    }
+
+    /// Convert the message into a `Cow`, allocating if it's stored inline
+    pub(crate) fn into_cow(self) -> Cow<'message, str> {
+        match self {
+            Self::Borrowed(borrowed) => Cow::Borrowed(borrowed),
+            Self::Owned(owned) => Cow::Owned(owned.into_string()),
+        }
+    }
+
+    /// Convert the message into a `String`, allocating unless it's on the heap
+    pub(crate) fn into_string(self) -> String {
+        match self {
+            Self::Borrowed(borrowed) => borrowed.into(),
+            Self::Owned(owned) => owned.into_string(),
+        }
+    }
+}
+
+impl Default for Message<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Message<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Self::Borrowed(borrowed) => borrowed,
+            Self::Owned(owned) => owned.as_str(),
+        }
+    }
+}
+
+impl AsRef<str> for Message<'_> {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl<'message> From<&'message str> for Message<'message> {
+    fn from(s: &'message str) -> Self {
+        Self::Borrowed(s)
+    }
+}
+
+impl From<String> for Message<'_> {
+    /// Keeps the string on the heap, as it's allocated already
+    fn from(s: String) -> Self {
+        Self::Owned(SmallString::Heap(s))
+    }
+}
+
+impl<'message> From<Cow<'message, str>> for Message<'message> {
+    fn from(s: Cow<'message, str>) -> Self {
+        match s {
+            Cow::Borrowed(borrowed) => Self::Borrowed(borrowed),
+            Cow::Owned(owned) => owned.into(),
+        }
+    }
+}
+
+impl fmt::Write for Message<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl PartialEq for Message<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Message<'_> {}
+
+impl PartialOrd for Message<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Message<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl Hash for Message<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use super::{INLINE_CAPACITY, Message, SmallString};
+
+    #[test]
+    fn spills_to_heap() {
+        let mut message = Message::new();
+        message.push_str("request");
+        message.push_str(" done");
+        assert!(matches!(
+            message,
+            Message::Owned(SmallString::Inline { .. })
+        ));
+        assert_eq!(&*message, "request done");
+
+        message.push_str(&"x".repeat(INLINE_CAPACITY));
+        assert!(matches!(message, Message::Owned(SmallString::Heap(_))));
+        assert_eq!(message.len(), 12 + INLINE_CAPACITY);
+
+        // Borrowed and owned messages with the same contents are the same
+        let owned = Message::from("é€").into_owned();
+        assert!(matches!(owned, Message::Owned(SmallString::Inline { .. })));
+        assert_eq!(owned, Message::Borrowed("é€"));
+        assert_eq!(owned.clone().into_cow(), Cow::<str>::Owned("é€".into()));
+        assert_eq!(
+            Message::from(Cow::Borrowed("a")).cmp(&Message::from(alloc::string::String::from("b"))),
+            core::cmp::Ordering::Less
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Reading and writing with `std::io`, enabled by the `std` feature
+
+use std::io::{self, BufRead};
+
+use alloc::vec::Vec;
+
+use crate::{Log, OwnedLog, ParseError};
+
+/// Returns an iterator reading the lines of the reader, yielding the line numbers starting at 1
+/// with the parsed log messages, like [`parse_lines`](crate::parse_lines) with strict UTF-8
+/// like [`Log::parse_bytes`].
+/// Empty lines are skipped, iteration stops at the end of the input or when reading fails.
+pub fn read_logs_std<R: BufRead>(
+    mut reader: R,
+) -> impl Iterator<Item = (usize, Result<OwnedLog, ParseError>)> {
+    let mut number = 0;
+    let mut line = Vec::new();
+    core::iter::from_fn(move || {
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line).ok()? == 0 {
+                return None;
+            }
+            number += 1;
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            if !content.is_empty() {
+                return Some((number, Log::parse_bytes(content).map(OwnedLog::from)));
+            }
+        }
+    })
+}
+
+impl From<ParseError> for io::Error {
+    /// Converts the error into an error of kind [`io::ErrorKind::InvalidData`]
+    fn from(error: ParseError) -> Self {
+        Self::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl Log<'_> {
+    /// Writes the log message as logfmt line to the writer, like [`Log::write_logfmt`], passing
+    /// the parts of the line on without buffering them
+    pub fn write_logfmt_io<W: io::Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.logfmt_chunks(&mut |chunk| w.write_all(chunk.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use alloc::{string::ToString, vec::Vec};
+
+    use crate::{ErrorKind, Log, OwnedLog};
+
+    use super::read_logs_std;
+
+    #[test]
+    fn read_logs() {
+        let input = b"level=info started\r\n\nvalue=\"open\nbad=\xff\nmsg=done a=1";
+        let logs: Vec<_> = read_logs_std(Cursor::new(&input[..])).collect();
+        let kinds: Vec<_> = logs
+            .iter()
+            .map(|(number, log)| (*number, log.as_ref().map_err(|error| error.kind())))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (1, Ok(&OwnedLog::parse("level=info started").unwrap())),
+                (3, Err(ErrorKind::UnclosedString)),
+                (4, Err(ErrorKind::InvalidUtf8)),
+                (5, Ok(&OwnedLog::parse("msg=done a=1").unwrap())),
+            ]
+        );
+
+        let error = io::Error::from(*logs[1].1.as_ref().unwrap_err());
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "unclosed string at byte 0");
+    }
+
+    #[test]
+    fn write_logfmt_io() {
+        let log = Log::parse("msg=\"disk full\" path=\"/var/log\" code=7").unwrap();
+        let mut output = Vec::new();
+        log.write_logfmt_io(&mut output).unwrap();
+        assert_eq!(output, log.to_logfmt().as_bytes());
+    }
+}
@@ -0,0 +1,234 @@
+//! Parsing log messages from input arriving in chunks
+
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+
+use crate::{ErrorKind, LineContinuation, Log, OwnedLog, ParseError, ParseOptions, QuoteState};
+
+/// A parser buffering chunks of input until a line is complete, for input like a serial port
+/// that doesn't arrive a line at a time.
+/// Lines end at a newline outside of strings, the partial line is limited to a maximum length.
+#[derive(Debug, Clone)]
+pub struct StreamParser<'options> {
+    options: ParseOptions<'options>,
+    max_line_len: usize,
+    newlines_in_quotes: bool,
+    continuation: LineContinuation,
+    /// The bytes of the partial line
+    buffer: Vec<u8>,
+    /// The complete lines, `None` for lines that were too long
+    lines: VecDeque<Option<String>>,
+    /// Whether the partial line is in a string
+    state: QuoteState,
+    /// Whether the rest of a line that was too long is skipped
+    skipping: bool,
+}
+
+impl<'options> StreamParser<'options> {
+    /// Create a parser using the default parse options, with partial lines of at most
+    /// `max_line_len` bytes
+    pub fn new(max_line_len: usize) -> Self {
+        Self {
+            options: ParseOptions::default(),
+            max_line_len,
+            newlines_in_quotes: true,
+            continuation: LineContinuation::Disabled,
+            buffer: Vec::new(),
+            lines: VecDeque::new(),
+            state: QuoteState::default(),
+            skipping: false,
+        }
+    }
+
+    /// Parse the lines with the options
+    #[must_use]
+    pub fn with_options(mut self, options: ParseOptions<'options>) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set whether a newline in a string is part of the string, instead of ending the line.
+    /// Enabled by default.
+    #[must_use]
+    pub const fn with_newlines_in_quotes(mut self, newlines_in_quotes: bool) -> Self {
+        self.newlines_in_quotes = newlines_in_quotes;
+        self
+    }
+
+    /// Set how lines ending in a backslash outside of strings are handled, like
+    /// [`ParseLines::with_line_continuation`](crate::ParseLines::with_line_continuation).
+    /// Disabled by default.
+    #[must_use]
+    pub const fn with_line_continuation(mut self, continuation: LineContinuation) -> Self {
+        self.continuation = continuation;
+        self
+    }
+
+    /// Add a chunk of input, which may end anywhere in a line or character
+    pub fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' && !self.state.in_string() && !self.skipping && self.continue_line() {
+                continue;
+            }
+            if byte == b'\n' && !(self.newlines_in_quotes && self.state.in_string()) {
+                self.end_line();
+                continue;
+            }
+
+            let options = &self.options;
+            self.state.update(byte, |ch| options.is_quote(ch));
+            if self.skipping {
+                continue;
+            }
+            if self.buffer.len() == self.max_line_len {
+                // Drop the line, reporting it once
+                self.buffer.clear();
+                self.lines.push_back(None);
+                self.skipping = true;
+                continue;
+            }
+            self.buffer.push(byte);
+        }
+    }
+
+    /// Removes the backslash continuing the partial line, returning whether it's continued
+    fn continue_line(&mut self) -> bool {
+        let carriage_return = self.buffer.ends_with(b"\r");
+        let line = &self.buffer[..self.buffer.len() - usize::from(carriage_return)];
+        if self.continuation == LineContinuation::Disabled || !line.ends_with(b"\\") {
+            return false;
+        }
+        self.buffer.truncate(line.len() - 1);
+        if self.continuation == LineContinuation::JoinWithSpace {
+            self.buffer.push(b' ');
+        }
+        true
+    }
+
+    /// Completes the partial line, unless it was too long
+    fn end_line(&mut self) {
+        if self.skipping {
+            self.skipping = false;
+        } else {
+            let line = String::from_utf8_lossy(&self.buffer).into_owned();
+            self.lines.push_back(Some(line));
+        }
+        self.buffer.clear();
+        self.state = QuoteState::default();
+    }
+
+    /// Treat the partial line as complete, at the end of the input
+    pub fn finish(&mut self) {
+        self.end_line();
+    }
+
+    /// Parse the next complete line, skipping blank lines.
+    /// Invalid UTF-8 is replaced by the replacement character, lines that were too long return
+    /// a [`ErrorKind::LineTooLong`] error.
+    pub fn next_log(&mut self) -> Option<Result<OwnedLog, ParseError>> {
+        loop {
+            let Some(line) = self.lines.pop_front()? else {
+                return Some(Err(ParseError {
+                    kind: ErrorKind::LineTooLong,
+                    offset: self.max_line_len,
+                    len: 0,
+                }));
+            };
+            if line.trim_end_matches('\r').is_empty() {
+                continue;
+            }
+            return Some(Log::parse_with(&line, &self.options).map(OwnedLog::from));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, LineContinuation, OwnedLog};
+
+    use super::StreamParser;
+
+    #[test]
+    fn chunks() {
+        let line = "msg=\"température élevée\" zone=\"salle 1\" value=42\r\n".as_bytes();
+
+        // Split inside the message, both bytes of two `é`s, after a space in the second string,
+        // and between the carriage return and the newline
+        let boundaries = [7, 10, 19, 40, 52, line.len()];
+        let mut parser = StreamParser::new(128);
+        let mut start = 0;
+        for end in boundaries {
+            parser.push(&line[start..end]);
+            if end < line.len() {
+                assert!(parser.next_log().is_none());
+            }
+            start = end;
+        }
+        let log = parser.next_log().unwrap().unwrap();
+        assert_eq!(
+            log,
+            OwnedLog::parse("msg=\"température élevée\" zone=\"salle 1\" value=42").unwrap()
+        );
+        assert!(parser.next_log().is_none());
+    }
+
+    #[test]
+    fn newlines_in_quotes() {
+        let input = b"first a=\"one\ntwo\" b=2\n\nsecond c=3";
+        let mut parser = StreamParser::new(64);
+        parser.push(input);
+        let log = parser.next_log().unwrap().unwrap();
+        assert_eq!(log.get("a"), Some("\"one\ntwo\""));
+        assert!(parser.next_log().is_none());
+        parser.finish();
+        assert_eq!(parser.next_log().unwrap().unwrap().get("c"), Some("3"));
+
+        let mut parser = StreamParser::new(64).with_newlines_in_quotes(false);
+        parser.push(input);
+        let error = parser.next_log().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnclosedString);
+        assert_eq!(
+            parser.next_log().unwrap().unwrap_err().kind(),
+            ErrorKind::UnclosedString
+        );
+    }
+
+    #[test]
+    fn line_continuation() {
+        let mut parser =
+            StreamParser::new(64).with_line_continuation(LineContinuation::JoinWithSpace);
+        parser.push(b"request done\\\nmethod=GET\\");
+        parser.push(b"\r\npath=\"C:\\\\\"\nnext=\"a\\\\\"\n");
+        assert_eq!(
+            parser.next_log().unwrap().unwrap(),
+            OwnedLog::parse("request done method=GET path=\"C:\\\\\"").unwrap()
+        );
+        assert_eq!(
+            parser.next_log().unwrap().unwrap().get("next"),
+            Some("\"a\\\\\"")
+        );
+        assert!(parser.next_log().is_none());
+
+        // Backslashes in strings don't continue the line
+        let mut parser = StreamParser::new(64)
+            .with_newlines_in_quotes(false)
+            .with_line_continuation(LineContinuation::Join);
+        parser.push(b"a=\"open \\\n");
+        assert_eq!(
+            parser.next_log().unwrap().unwrap_err().kind(),
+            ErrorKind::UnclosedString
+        );
+    }
+
+    #[test]
+    fn line_too_long() {
+        let mut parser = StreamParser::new(8);
+        parser.push(b"a=1 b=2 c=3");
+        parser.push(b" d=4\ne=5\n");
+        assert_eq!(
+            parser.next_log().unwrap().unwrap_err().kind(),
+            ErrorKind::LineTooLong
+        );
+        assert_eq!(parser.next_log().unwrap().unwrap().get("e"), Some("5"));
+        assert!(parser.next_log().is_none());
+    }
+}
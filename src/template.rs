@@ -0,0 +1,226 @@
+//! Rendering log messages through templates with placeholders
+
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Write};
+
+use crate::{LEVEL_KEYS, Log, TIMESTAMP_KEYS, decode_quoted};
+
+/// The reason compiling a template failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub enum TemplateErrorKind {
+    /// A `{` isn't followed by a `}`
+    UnclosedPlaceholder,
+
+    /// A `}` isn't part of a placeholder or `}}`
+    UnmatchedBrace,
+
+    /// A placeholder isn't known, while unknown placeholders are rejected
+    UnknownPlaceholder,
+}
+
+impl Display for TemplateErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnclosedPlaceholder => "unclosed placeholder",
+            Self::UnmatchedBrace => "unmatched `}`",
+            Self::UnknownPlaceholder => "unknown placeholder",
+        })
+    }
+}
+
+/// An error returned when compiling a template fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+pub struct TemplateError {
+    kind: TemplateErrorKind,
+    offset: usize,
+}
+
+impl TemplateError {
+    /// Return the reason compiling failed
+    pub const fn kind(&self) -> TemplateErrorKind {
+        self.kind
+    }
+
+    /// Return the byte offset of the offending brace or placeholder
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+impl core::error::Error for TemplateError {}
+
+/// A part of a compiled template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part<'template> {
+    Literal(&'template str),
+    Message,
+    Level,
+    Timestamp,
+    Attribute(&'template str),
+    Unknown,
+}
+
+/// A compiled template, rendering `{message}`, `{level}`, `{ts}`, and `{attr:key}` placeholders
+/// with `{{` and `}}` as literal braces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template<'template> {
+    parts: Vec<Part<'template>>,
+}
+
+impl<'template> Template<'template> {
+    /// Compile the template, rendering unknown placeholders as nothing
+    pub fn compile(template: &'template str) -> Result<Self, TemplateError> {
+        Self::compile_inner(template, false)
+    }
+
+    /// Compile the template, returning an error for unknown placeholders
+    pub fn compile_strict(template: &'template str) -> Result<Self, TemplateError> {
+        Self::compile_inner(template, true)
+    }
+
+    fn compile_inner(template: &'template str, strict: bool) -> Result<Self, TemplateError> {
+        let error = |kind, offset| TemplateError { kind, offset };
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(index) = rest.find(['{', '}']) {
+            let offset = template.len() - rest.len() + index;
+            if index > 0 {
+                parts.push(Part::Literal(&rest[..index]));
+            }
+            let after = &rest[index + 1..];
+
+            // Doubled braces are literal braces
+            if after.starts_with(&rest[index..=index]) {
+                parts.push(Part::Literal(&rest[index..=index]));
+                rest = &after[1..];
+                continue;
+            }
+            if rest[index..].starts_with('}') {
+                return Err(error(TemplateErrorKind::UnmatchedBrace, offset));
+            }
+
+            let end = after
+                .find('}')
+                .ok_or(error(TemplateErrorKind::UnclosedPlaceholder, offset))?;
+            let part = match &after[..end] {
+                "message" => Part::Message,
+                "level" => Part::Level,
+                "ts" => Part::Timestamp,
+                name => match name.strip_prefix("attr:") {
+                    Some(key) => Part::Attribute(key),
+                    None if strict => {
+                        return Err(error(TemplateErrorKind::UnknownPlaceholder, offset));
+                    }
+                    None => Part::Unknown,
+                },
+            };
+            parts.push(part);
+            rest = &after[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Writes the log message through the template.
+    /// Values are written without surrounding quotes and with escape sequences of quoted
+    /// strings decoded, missing values and the fallback message as nothing.
+    pub fn render<W: Write + ?Sized>(&self, f: &mut W, log: &Log<'_>) -> fmt::Result {
+        let first_of = |keys: [&str; 3]| keys.into_iter().find_map(|key| log.get(key));
+        for part in &self.parts {
+            let value = match part {
+                Part::Literal(literal) => {
+                    f.write_str(literal)?;
+                    continue;
+                }
+                Part::Message if log.message_is_fallback() => None,
+                Part::Message => Some(log.message()),
+                Part::Level => first_of(LEVEL_KEYS),
+                Part::Timestamp => first_of(TIMESTAMP_KEYS),
+                Part::Attribute(key) => log.get(key),
+                Part::Unknown => None,
+            };
+            if let Some(value) = value {
+                f.write_str(&decode_quoted(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Log<'_> {
+    /// Writes the log message through the compiled template, like [`Template::render`]
+    pub fn render<W: Write + ?Sized>(&self, f: &mut W, template: &Template<'_>) -> fmt::Result {
+        template.render(f, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use crate::Log;
+
+    use super::{Template, TemplateError, TemplateErrorKind};
+
+    #[test]
+    fn render() {
+        let template =
+            Template::compile("{{{ts}}} [{level}] {message} took={attr:duration} {unknown}|")
+                .unwrap();
+        let lines = [
+            "msg=\"disk full\" level=error ts=2024-01-02T03:04:05Z duration=\"10 ms\"",
+            "started lvl=info time=1",
+            "a=1",
+        ];
+        let rendered = lines.map(|line| {
+            let mut output = String::new();
+            Log::parse(line)
+                .unwrap()
+                .render(&mut output, &template)
+                .unwrap();
+            output
+        });
+        assert_eq!(
+            rendered,
+            [
+                "{2024-01-02T03:04:05Z} [error] disk full took=10 ms |",
+                "{1} [info] started took= |",
+                "{} []  took= |",
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_errors() {
+        let error = |kind, offset| Err(TemplateError { kind, offset });
+        assert_eq!(
+            Template::compile("a {message"),
+            error(TemplateErrorKind::UnclosedPlaceholder, 2)
+        );
+        assert_eq!(
+            Template::compile("a } b"),
+            error(TemplateErrorKind::UnmatchedBrace, 2)
+        );
+        assert!(Template::compile("{other}").is_ok());
+        assert_eq!(
+            Template::compile_strict("{message} {other}"),
+            error(TemplateErrorKind::UnknownPlaceholder, 10)
+        );
+        assert_eq!(
+            Template::compile("{{}}").unwrap(),
+            Template::compile_strict("{{}}").unwrap()
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Splitting log messages in tokens, with an explicit state machine tracking strings
+
+use core::ops::Range;
+
+use crate::{ErrorKind, ParseError, ParseOptions};
+
+/// Where the tokenizer is within a token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Outside of a string, where a separator ends the token
+    Bare,
+
+    /// Within a string opened by the quote
+    InQuotes(char),
+
+    /// Within a string opened by the quote, after a backslash escaping the next character
+    AfterBackslashInQuotes(char),
+}
+
+impl State {
+    /// Returns the state after the character
+    fn after(self, ch: char, options: &ParseOptions<'_>) -> Self {
+        match self {
+            Self::Bare if options.is_quote(ch) => Self::InQuotes(ch),
+            Self::Bare => Self::Bare,
+            Self::InQuotes(quote) if ch == '\\' => Self::AfterBackslashInQuotes(quote),
+            Self::InQuotes(quote) if ch == quote => Self::Bare,
+            Self::InQuotes(quote) | Self::AfterBackslashInQuotes(quote) => Self::InQuotes(quote),
+        }
+    }
+
+    /// Checks whether the character ends the token, when it led to this state
+    fn ends_token(self, ch: char, options: &ParseOptions<'_>) -> bool {
+        self == Self::Bare && options.separators.matches(ch)
+    }
+}
+
+/// Splits a log message in tokens, separated by separators outside of strings
+#[derive(Debug, Clone)]
+pub(crate) struct Tokenizer<'input> {
+    /// The input without the ignored byte order mark and line endings
+    pub(crate) input: &'input str,
+    /// The offset of the content after the ignored byte order mark
+    pub(crate) content_start: usize,
+    chars: core::iter::Skip<core::str::CharIndices<'input>>,
+}
+
+impl<'input> Tokenizer<'input> {
+    /// Creates a tokenizer, ignoring a leading byte order mark and trailing line endings if
+    /// requested
+    pub(crate) fn new(s: &'input str, options: &ParseOptions<'_>) -> Self {
+        let (content_start, input) = if options.trim_line_endings {
+            let start = if s.starts_with('\u{FEFF}') {
+                '\u{FEFF}'.len_utf8()
+            } else {
+                0
+            };
+            (
+                start,
+                &s[..s.trim_end_matches(['\r', '\n']).len().max(start)],
+            )
+        } else {
+            (0, s)
+        };
+        Self {
+            input,
+            content_start,
+            chars: input.char_indices().skip(usize::from(content_start > 0)),
+        }
+    }
+
+    /// Returns the span of the next token, starting at the first character that isn't a
+    /// separator, or an error if it contains a string that isn't closed
+    pub(crate) fn next(
+        &mut self,
+        options: &ParseOptions<'_>,
+    ) -> Option<Result<Range<usize>, ParseError>> {
+        let (start, first) = self
+            .chars
+            .by_ref()
+            .find(|(_, ch)| !options.separators.matches(*ch))?;
+
+        // Find the end of the token, the token may start with a string
+        let mut state = State::Bare.after(first, options);
+        let mut end = self.input.len();
+        for (index, ch) in self.chars.by_ref() {
+            state = state.after(ch, options);
+            if state.ends_token(ch, options) {
+                end = index;
+                break;
+            }
+        }
+
+        // Return an error if a string wasn't closed, the rest of the input is consumed
+        if state != State::Bare {
+            return Some(Err(ParseError {
+                kind: ErrorKind::UnclosedString,
+                offset: start,
+                len: end - start,
+            }));
+        }
+        Some(Ok(start..end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, string::String, vec::Vec};
+
+    use proptest::test_runner::{Config, TestRunner};
+
+    use crate::{
+        Dialect, Log, ParseOptions, Separators, Token, differential::string_of, tokens, unquote,
+    };
+
+    use super::{State, Tokenizer};
+
+    #[test]
+    fn transitions() {
+        let options = ParseOptions::default();
+        let single = ParseOptions {
+            single_quotes: true,
+            ..ParseOptions::default()
+        };
+        let cases = [
+            (State::Bare, 'a', State::Bare, &options),
+            (State::Bare, '\\', State::Bare, &options),
+            (State::Bare, '"', State::InQuotes('"'), &options),
+            (State::Bare, '\'', State::Bare, &options),
+            (State::Bare, '\'', State::InQuotes('\''), &single),
+            (State::InQuotes('"'), 'a', State::InQuotes('"'), &options),
+            (State::InQuotes('"'), ' ', State::InQuotes('"'), &options),
+            (State::InQuotes('"'), '"', State::Bare, &options),
+            (State::InQuotes('\''), '"', State::InQuotes('\''), &single),
+            (State::InQuotes('\''), '\'', State::Bare, &single),
+            (
+                State::InQuotes('"'),
+                '\\',
+                State::AfterBackslashInQuotes('"'),
+                &options,
+            ),
+            (
+                State::AfterBackslashInQuotes('"'),
+                '"',
+                State::InQuotes('"'),
+                &options,
+            ),
+            (
+                State::AfterBackslashInQuotes('"'),
+                '\\',
+                State::InQuotes('"'),
+                &options,
+            ),
+        ];
+        for (state, ch, expected, options) in cases {
+            assert_eq!(state.after(ch, options), expected, "{state:?} {ch:?}");
+        }
+
+        // Only separators outside of strings end tokens
+        assert!(State::Bare.ends_token(' ', &options));
+        assert!(!State::Bare.ends_token('a', &options));
+        assert!(!State::InQuotes('"').ends_token(' ', &options));
+        assert!(!State::AfterBackslashInQuotes('"').ends_token(' ', &options));
+    }
+
+    #[test]
+    fn spans() {
+        let spans = |input| {
+            let options = ParseOptions::default();
+            let mut tokenizer = Tokenizer::new(input, &options);
+            let mut spans = Vec::new();
+            while let Some(span) = tokenizer.next(&options) {
+                spans.push(span.map_err(|error| error.offset));
+            }
+            spans
+        };
+        assert_eq!(spans(" a=1  b=\"x y\"\n"), [Ok(1..4), Ok(6..13)]);
+        assert_eq!(spans("k=\"a\\\" b\" c"), [Ok(0..9), Ok(10..11)]);
+        assert_eq!(spans("k=\"a\\\\\" b"), [Ok(0..7), Ok(8..9)]);
+        assert_eq!(spans("x\"y z\"w v"), [Ok(0..7), Ok(8..9)]);
+        assert_eq!(spans("a k=\"open \\\""), [Ok(0..1), Err(2)]);
+        assert_eq!(spans("k=\"\\"), [Err(0)]);
+        assert_eq!(spans("\u{FEFF}a\r\n"), [Ok(3..4)]);
+    }
+
+    /// Checks whether the string lies within the input
+    fn within(input: &str, s: &str) -> bool {
+        let range = input.as_bytes().as_ptr_range();
+        let start = s.as_ptr();
+        range.start <= start && start.wrapping_add(s.len()) <= range.end
+    }
+
+    /// Checks the invariants of tokenizing and parsing the input: token spans are ordered, don't
+    /// overlap, and lie on character boundaries, parsing doesn't panic, and keys and values of
+    /// attributes are borrowed from the input unless parsing fails
+    fn check_invariants(input: &str, option_sets: &[ParseOptions<'_>]) {
+        for options in option_sets {
+            let mut tokenizer = Tokenizer::new(input, options);
+            let mut previous_end = tokenizer.content_start;
+            while let Some(span) = tokenizer.next(options) {
+                let Ok(span) = span else {
+                    assert!(tokenizer.next(options).is_none(), "{input:?}");
+                    break;
+                };
+                assert!(
+                    previous_end <= span.start && span.start < span.end,
+                    "{input:?}"
+                );
+                assert!(tokenizer.input.get(span.clone()).is_some(), "{input:?}");
+                previous_end = span.end;
+            }
+            let _ = Log::parse_with(input, options);
+        }
+
+        if let Ok(log) = Log::parse(input) {
+            for attribute in &log.attributes {
+                for part in [&attribute.key, &attribute.value] {
+                    assert!(
+                        matches!(part, Cow::Borrowed(part) if within(input, part)),
+                        "{input:?}"
+                    );
+                }
+            }
+        }
+        for token in tokens(input) {
+            match token {
+                Ok(Token::Attribute(key, value)) => {
+                    assert!(within(input, key) && within(input, value), "{input:?}");
+                    assert!(!unquote(key).is_empty(), "{input:?}");
+                }
+                Ok(Token::Word(word)) => assert!(within(input, word), "{input:?}"),
+                Err(_) => break,
+            }
+        }
+    }
+
+    #[test]
+    fn invariants() {
+        const CHARS: [char; 16] = [
+            'a', 'k', '1', '=', '"', '\'', '\\', ' ', '\t', '\n', '\r', ',', 'é', '€', '😀',
+            '\u{FEFF}',
+        ];
+        let separators = [' ', ','];
+        let option_sets = [
+            ParseOptions::default(),
+            ParseOptions {
+                single_quotes: true,
+                separators: Separators::Chars(&separators),
+                record_spans: true,
+                ..ParseOptions::default()
+            },
+            ParseOptions {
+                strict: true,
+                trim_line_endings: false,
+                ..ParseOptions::default()
+            },
+            ParseOptions::dialect(Dialect::GoLogfmt),
+        ];
+
+        // Every input of up to 4 characters
+        let mut inputs = Vec::from([String::new()]);
+        for _ in 0..4 {
+            inputs = inputs
+                .iter()
+                .flat_map(|input| {
+                    CHARS.iter().map(move |ch| {
+                        let mut input = input.clone();
+                        input.push(*ch);
+                        input
+                    })
+                })
+                .collect();
+            for input in &inputs {
+                check_invariants(input, &option_sets);
+            }
+        }
+
+        // Longer random inputs
+        let mut runner = TestRunner::new(Config::with_cases(2000));
+        let result = runner.run(&string_of(&CHARS, 24), |input| {
+            check_invariants(&input, &option_sets);
+            Ok(())
+        });
+        result.unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+//! ufmt support, enabled by the `ufmt` feature
+
+use ufmt::{Formatter, uDebug, uDisplay, uWrite};
+
+use crate::{EncodeError, InvalidKey, Log};
+
+impl Log<'_> {
+    /// Writes the log message as logfmt line, like [`Log::write_logfmt`], without allocating or
+    /// using `core::fmt`
+    pub fn write_logfmt_ufmt<W: uWrite + ?Sized>(&self, w: &mut W) -> Result<(), W::Error> {
+        self.logfmt_chunks(&mut |chunk| w.write_str(chunk))
+    }
+}
+
+impl uDisplay for Log<'_> {
+    /// Writes the log message as logfmt line, like [`Log::write_logfmt`]
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        self.logfmt_chunks(&mut |chunk| f.write_str(chunk))
+    }
+}
+
+impl uDebug for InvalidKey {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        f.debug_struct("InvalidKey")?
+            .field("key", &DebugStr(&self.key))?
+            .field("kind", &self.kind)?
+            .finish()
+    }
+}
+
+/// Formats a string quoted and escaped like its `core::fmt::Debug` implementation, which ufmt
+/// doesn't provide
+struct DebugStr<'a>(&'a str);
+
+impl uDebug for DebugStr<'_> {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        f.write_str("\"")?;
+        for ch in self.0.escape_debug() {
+            f.write_str(ch.encode_utf8(&mut [0; 4]))?;
+        }
+        f.write_str("\"")
+    }
+}
+
+impl uDebug for EncodeError {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        match self {
+            Self::InvalidKey(error) => f.debug_tuple("InvalidKey")?.field(error)?.finish(),
+            Self::ControlCharacter => f.write_str("ControlCharacter"),
+            Self::Write(_) => f.write_str("Write(Error)"),
+            Self::MessageNotFirst => f.write_str("MessageNotFirst"),
+            Self::ValueTooLong { key } => f
+                .debug_struct("ValueTooLong")?
+                .field("key", &DebugStr(key))?
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::ToString, vec::Vec};
+    use core::convert::Infallible;
+
+    use ufmt::{uWrite, uwrite};
+
+    use crate::{EncodeError, Log, ParseOptions};
+
+    /// Collects the written bytes
+    struct VecWriter(Vec<u8>);
+
+    impl uWrite for VecWriter {
+        type Error = Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+            self.0.extend_from_slice(s.as_bytes());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn display_parity() {
+        let line = "msg=\"disk full\" path=\"C:\\\\temp\" note=\"say \\\"hi\\\"\" a=1";
+        let mut log = Log::parse(line).unwrap();
+        log.insert("color", "\x1b[31mred").unwrap();
+
+        let mut writer = VecWriter(Vec::new());
+        uwrite!(writer, "{}", log).unwrap();
+        assert_eq!(writer.0, log.to_string().as_bytes());
+
+        let mut writer = VecWriter(Vec::new());
+        log.write_logfmt_ufmt(&mut writer).unwrap();
+        assert_eq!(writer.0, log.to_logfmt().as_bytes());
+    }
+
+    #[test]
+    fn debug_parity() {
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let parse_error = Log::parse_with("a=1 =2", &options).unwrap_err();
+        let key_error = Log::builder().attribute("a\"b", "1").build().unwrap_err();
+        let encode_error = EncodeError::InvalidKey(key_error.clone());
+
+        let mut writer = VecWriter(Vec::new());
+        uwrite!(
+            writer,
+            "{:?} {:?} {:?}",
+            parse_error,
+            key_error,
+            encode_error
+        )
+        .unwrap();
+        assert_eq!(
+            writer.0,
+            format!("{parse_error:?} {key_error:?} {encode_error:?}").as_bytes()
+        );
+    }
+}
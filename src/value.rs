@@ -0,0 +1,539 @@
+//! Typed values of attributes, like levels, numbers, timestamps, and durations
+
+use core::{
+    fmt::{self, Display},
+    hash::Hash,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::{UnknownLevel, encode::is_quoted, unquote};
+
+/// Which values are accepted as booleans by [`Log::get_bool_with`](crate::Log::get_bool_with)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoolStyle {
+    /// Only `true`, `false`, `1`, and `0`
+    #[default]
+    Strict,
+
+    /// Also `yes`, `no`, `on`, `off`, `t`, and `f`
+    Lenient,
+}
+
+impl BoolStyle {
+    /// Parses the value ignoring ASCII case
+    pub(crate) fn parse(self, value: &str) -> Option<bool> {
+        let (truthy, falsy): (&[&str], &[&str]) = match self {
+            Self::Strict => (&["true", "1"], &["false", "0"]),
+            Self::Lenient => (
+                &["true", "1", "yes", "on", "t"],
+                &["false", "0", "no", "off", "f"],
+            ),
+        };
+        let matches = |words: &[&str]| words.iter().any(|word| value.eq_ignore_ascii_case(word));
+        if matches(truthy) {
+            Some(true)
+        } else if matches(falsy) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// The unquoted values treated as null by [`Log::get_opt`](crate::Log::get_opt)
+pub const DEFAULT_NULL_VALUES: &[&str] = &["null", "nil", "-", ""];
+
+/// The severity of a log message, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Level {
+    /// Very detailed information for tracing the execution
+    Trace,
+
+    /// Information useful while debugging
+    Debug,
+
+    /// General information
+    Info,
+
+    /// Something unexpected that isn't an error
+    Warn,
+
+    /// An error
+    Error,
+
+    /// An error the program can't recover from
+    Fatal,
+}
+
+impl Level {
+    /// Parses the level from one of its common spellings, ignoring ASCII case
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        [
+            ("trace", Self::Trace),
+            ("trc", Self::Trace),
+            ("debug", Self::Debug),
+            ("dbg", Self::Debug),
+            ("info", Self::Info),
+            ("information", Self::Info),
+            ("notice", Self::Info),
+            ("warn", Self::Warn),
+            ("warning", Self::Warn),
+            ("error", Self::Error),
+            ("err", Self::Error),
+            ("fatal", Self::Fatal),
+            ("critical", Self::Fatal),
+            ("crit", Self::Fatal),
+            ("panic", Self::Fatal),
+        ]
+        .into_iter()
+        .find_map(|(name, level)| name.eq_ignore_ascii_case(s).then_some(level))
+    }
+
+    /// Return the lowercase name of the level
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+            Self::Fatal => "fatal",
+        }
+    }
+
+    /// Return the syslog severity of the level, with fatal errors as critical and trace as debug
+    pub const fn syslog_severity(self) -> u8 {
+        match self {
+            Self::Trace | Self::Debug => 7,
+            Self::Info => 6,
+            Self::Warn => 4,
+            Self::Error => 3,
+            Self::Fatal => 2,
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = UnknownLevel;
+
+    /// Parses the level from one of its common spellings, ignoring ASCII case.
+    /// `critical`, `crit`, and `panic` are fatal, `notice` is info.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or(UnknownLevel)
+    }
+}
+
+/// The value of an attribute, classified by its type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    /// An unquoted value that isn't of another type
+    Str(&'a str),
+
+    /// A quoted value, without the surrounding quotes
+    QuotedStr(&'a str),
+
+    /// An integer
+    Int(i64),
+
+    /// A floating point number
+    Float(f64),
+
+    /// `true` or `false`
+    Bool(bool),
+
+    /// `null` or `nil`
+    Null,
+}
+
+/// A classified attribute value, along with the value as it was stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypedValue<'a> {
+    raw: &'a str,
+    value: Value<'a>,
+}
+
+impl<'a> TypedValue<'a> {
+    /// Classify a raw value. Quoted values are always strings, unquoted values are booleans,
+    /// null, integers, or floating point numbers if they can be parsed as such.
+    pub fn new(raw: &'a str) -> Self {
+        let value = if is_quoted(raw) {
+            Value::QuotedStr(unquote(raw))
+        } else {
+            match raw {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" | "nil" => Value::Null,
+                _ => {
+                    if let Ok(int) = raw.parse() {
+                        Value::Int(int)
+                    } else if let Some(float) = raw
+                        .parse()
+                        .ok()
+                        .filter(|_| raw.bytes().any(|byte| byte.is_ascii_digit()))
+                    {
+                        // Words like `inf` and `NaN` are strings
+                        Value::Float(float)
+                    } else {
+                        Value::Str(raw)
+                    }
+                }
+            }
+        };
+        Self { raw, value }
+    }
+
+    /// Return the value as it was stored
+    pub const fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Return the classified value
+    pub const fn value(&self) -> Value<'a> {
+        self.value
+    }
+}
+
+/// A number parsed by [`Log::get_number`](crate::Log::get_number)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    /// An integer that fits in an `i64`
+    Int(i64),
+
+    /// A positive integer too large for an `i64` that fits in a `u64`
+    UInt(u64),
+
+    /// A finite floating point number, or an integer too large for a `u64`
+    Float(f64),
+}
+
+impl Number {
+    /// Parses the number, preferring integers over floating point numbers.
+    /// Scientific notation is a floating point number, infinite numbers and NaN aren't numbers.
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        if let Ok(int) = s.parse() {
+            Some(Self::Int(int))
+        } else if let Ok(uint) = s.parse() {
+            Some(Self::UInt(uint))
+        } else {
+            s.parse::<f64>()
+                .ok()
+                .filter(|float| float.is_finite())
+                .map(Self::Float)
+        }
+    }
+}
+
+/// A point in time, as seconds and nanoseconds since the Unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timestamp {
+    secs: i64,
+    nanos: u32,
+}
+
+impl Timestamp {
+    /// Create a timestamp from seconds and nanoseconds since the Unix epoch.
+    /// Nanoseconds past a second are carried into the seconds, which saturate at [`i64::MAX`].
+    pub const fn new(secs: i64, nanos: u32) -> Self {
+        Self {
+            secs: secs.saturating_add((nanos / 1_000_000_000) as i64),
+            nanos: nanos % 1_000_000_000,
+        }
+    }
+
+    /// Return the seconds since the Unix epoch
+    pub const fn secs(&self) -> i64 {
+        self.secs
+    }
+
+    /// Return the nanoseconds past the second
+    pub const fn nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Parses an RFC 3339 timestamp or an integer number of seconds, milliseconds, microseconds,
+    /// or nanoseconds since the Unix epoch, distinguished by the number of digits.
+    /// Up to 11 digits are seconds, 12 to 14 are milliseconds, 15 to 17 are microseconds, and 18
+    /// or 19 are nanoseconds.
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        if let Ok(number) = s.parse::<i64>() {
+            let digits = number.unsigned_abs().checked_ilog10().unwrap_or(0) + 1;
+            return match digits {
+                ..=11 => Some(Self::new(number, 0)),
+                12..=14 => Some(Self::from_fraction(number, 1000)),
+                15..=17 => Some(Self::from_fraction(number, 1_000_000)),
+                _ => Some(Self::from_fraction(number, 1_000_000_000)),
+            };
+        }
+        Self::parse_rfc3339(s)
+    }
+
+    /// Creates a timestamp from a number of fractions of a second since the Unix epoch
+    const fn from_fraction(count: i64, per_second: i64) -> Self {
+        Self {
+            secs: count.div_euclid(per_second),
+            nanos: (count.rem_euclid(per_second) * (1_000_000_000 / per_second)) as u32,
+        }
+    }
+
+    /// Parses an RFC 3339 timestamp like `2024-05-01T12:30:00.123+02:00`
+    fn parse_rfc3339(s: &str) -> Option<Self> {
+        // Parses a fixed number of digits at the start of the string
+        fn digits(s: &str, count: usize) -> Option<(i64, &str)> {
+            let digits = s.get(..count)?;
+            if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            Some((digits.parse().ok()?, &s[count..]))
+        }
+
+        // Parses the date
+        let (year, s) = digits(s, 4)?;
+        let (month, s) = digits(s.strip_prefix('-')?, 2)?;
+        let (day, s) = digits(s.strip_prefix('-')?, 2)?;
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        // Parses the time
+        let s = s.strip_prefix(['T', 't', ' '])?;
+        let (hour, s) = digits(s, 2)?;
+        let (minute, s) = digits(s.strip_prefix(':')?, 2)?;
+        let (second, mut s) = digits(s.strip_prefix(':')?, 2)?;
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        // Parses the fraction of a second, ignoring digits past nanoseconds
+        let mut nanos = 0;
+        if let Some(fraction) = s.strip_prefix('.') {
+            let length = fraction.bytes().take_while(u8::is_ascii_digit).count();
+            if length == 0 {
+                return None;
+            }
+            nanos = fraction[..length]
+                .bytes()
+                .chain(core::iter::repeat(b'0'))
+                .take(9)
+                .fold(0, |nanos, digit| nanos * 10 + u32::from(digit - b'0'));
+            s = &fraction[length..];
+        }
+
+        // Parses the offset from UTC
+        let offset = match s {
+            "Z" | "z" => 0,
+            _ => {
+                let sign = match s.get(..1)? {
+                    "+" => 1,
+                    "-" => -1,
+                    _ => return None,
+                };
+                let (hours, s) = digits(&s[1..], 2)?;
+                let (minutes, s) = digits(s.strip_prefix(':')?, 2)?;
+                if !s.is_empty() || hours > 23 || minutes > 59 {
+                    return None;
+                }
+                sign * (hours * 3600 + minutes * 60)
+            }
+        };
+
+        let secs =
+            days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+        Some(Self::new(secs, nanos))
+    }
+}
+
+/// Parses a Go duration like `1h2m3.5s`, a sequence of decimal numbers with a unit.
+/// Returns `None` for negative durations and durations that don't fit in 64 bits of nanoseconds.
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
+    let mut s = s.strip_prefix('+').unwrap_or(s);
+    if s == "0" {
+        return Some(Duration::ZERO);
+    }
+    if s.is_empty() {
+        return None;
+    }
+
+    let mut nanos = 0_u64;
+    while !s.is_empty() {
+        // Split the integer and fraction from the rest
+        let integer_len = s.bytes().take_while(u8::is_ascii_digit).count();
+        let (integer, rest) = s.split_at(integer_len);
+        let (fraction, rest) = match rest.strip_prefix('.') {
+            Some(rest) => rest.split_at(rest.bytes().take_while(u8::is_ascii_digit).count()),
+            None => ("", rest),
+        };
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+
+        // Find the number of nanoseconds in the unit
+        let unit_len = rest
+            .find(|ch: char| ch == '.' || ch.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (unit, rest) = rest.split_at(unit_len);
+        let scale: u64 = match unit {
+            "ns" => 1,
+            "us" | "\u{b5}s" | "\u{3bc}s" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            _ => return None,
+        };
+
+        // Add the whole units and the fraction of a unit, ignoring digits past nanoseconds
+        let whole = if integer.is_empty() {
+            0
+        } else {
+            integer.parse::<u64>().ok()?.checked_mul(scale)?
+        };
+        let (numerator, denominator) =
+            fraction
+                .bytes()
+                .take(18)
+                .fold((0_u128, 1_u128), |(numerator, denominator), digit| {
+                    (numerator * 10 + u128::from(digit - b'0'), denominator * 10)
+                });
+        let part = u64::try_from(numerator * u128::from(scale) / denominator).ok()?;
+        nanos = nanos.checked_add(whole)?.checked_add(part)?;
+        s = rest;
+    }
+    Some(Duration::from_nanos(nanos))
+}
+
+/// Parses a byte size like `10KB` or `1.5MiB`, with an optional decimal or binary unit ignoring
+/// case. Fractions of a byte are truncated and sizes that don't fit in 64 bits saturate.
+pub(crate) fn parse_bytes(s: &str) -> Option<u64> {
+    let integer_len = s.bytes().take_while(u8::is_ascii_digit).count();
+    let (integer, rest) = s.split_at(integer_len);
+    let (fraction, unit) = match rest.strip_prefix('.') {
+        Some(rest) => rest.split_at(rest.bytes().take_while(u8::is_ascii_digit).count()),
+        None => ("", rest),
+    };
+    if integer.is_empty() && fraction.is_empty() {
+        return None;
+    }
+
+    // Find the number of bytes in the unit
+    let multiplier: u128 = [
+        ("", 1),
+        ("b", 1),
+        ("kb", 1000),
+        ("mb", 1000_u128.pow(2)),
+        ("gb", 1000_u128.pow(3)),
+        ("tb", 1000_u128.pow(4)),
+        ("pb", 1000_u128.pow(5)),
+        ("eb", 1000_u128.pow(6)),
+        ("kib", 1 << 10),
+        ("mib", 1 << 20),
+        ("gib", 1 << 30),
+        ("tib", 1 << 40),
+        ("pib", 1 << 50),
+        ("eib", 1 << 60),
+    ]
+    .into_iter()
+    .find(|(name, _)| unit.eq_ignore_ascii_case(name))?
+    .1;
+
+    // Integers too large for 128 bits saturate as well
+    let whole = if integer.is_empty() {
+        0
+    } else {
+        integer
+            .parse::<u128>()
+            .map_or(u128::MAX, |integer| integer.saturating_mul(multiplier))
+    };
+    let (numerator, denominator) =
+        fraction
+            .bytes()
+            .take(18)
+            .fold((0_u128, 1_u128), |(numerator, denominator), digit| {
+                (numerator * 10 + u128::from(digit - b'0'), denominator * 10)
+            });
+    let bytes = whole.saturating_add(numerator * multiplier / denominator);
+    Some(u64::try_from(bytes).unwrap_or(u64::MAX))
+}
+
+/// Parses an unsigned integer with an optional `0x`, `0o`, or `0b` prefix, ignoring case
+pub(crate) fn parse_radix_u64(s: &str) -> Option<u64> {
+    let (digits, radix) = match s.as_bytes() {
+        [b'0', b'x' | b'X', ..] => (&s[2..], 16),
+        [b'0', b'o' | b'O', ..] => (&s[2..], 8),
+        [b'0', b'b' | b'B', ..] => (&s[2..], 2),
+        _ => (s, 10),
+    };
+
+    // Signs are handled by the caller
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+    u64::from_str_radix(digits, radix).ok()
+}
+
+/// Parses a signed integer with an optional sign followed by an optional `0x`, `0o`, or `0b`
+/// prefix, ignoring case
+pub(crate) fn parse_radix_i64(s: &str) -> Option<i64> {
+    match s.strip_prefix('-') {
+        Some(magnitude) => i64::try_from(-i128::from(parse_radix_u64(magnitude)?)).ok(),
+        None => i64::try_from(parse_radix_u64(s.strip_prefix('+').unwrap_or(s))?).ok(),
+    }
+}
+
+/// Checks whether the year is a leap year
+const fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Returns the number of days in the month of the year
+const fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days since the Unix epoch of a date in the proleptic Gregorian calendar
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the year, month, and day of a number of days since the Unix epoch in the proleptic
+/// Gregorian calendar
+pub(crate) const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// The keys checked by [`Log::level`], in order
+pub(crate) const LEVEL_KEYS: [&str; 3] = ["level", "lvl", "severity"];
+
+/// The keys checked by [`Log::timestamp`], in order
+pub(crate) const TIMESTAMP_KEYS: [&str; 3] = ["ts", "time", "timestamp"];
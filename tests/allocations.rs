@@ -0,0 +1,105 @@
+//! Counts the heap allocations of parsing and encoding, with a global allocator counting per thread
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use logfmt_nostd::{Encoder, KeyInterner, Log, OwnedLog};
+
+std::thread_local! {
+    /// The number of allocations by the global allocator on this thread
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting allocations per thread
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Returns the number of allocations made while parsing the line
+fn allocations(line: &str) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let log = Log::parse(line).unwrap();
+    let allocations = ALLOCATIONS.with(Cell::get) - before;
+    drop(log);
+    allocations
+}
+
+#[test]
+fn short_prose_lines() {
+    for line in [
+        "request done",
+        "GET / took 12ms",
+        "connection reset",
+        "msg=\"cache warmed\"",
+    ] {
+        assert_eq!(allocations(line), 0, "{line:?}");
+    }
+
+    // Longer messages spill to the heap
+    assert!(allocations("this message doesn't fit in the inline buffer") > 0);
+}
+
+#[test]
+fn interned_keys() {
+    let lines = [
+        "level=info method=GET status=200",
+        "level=warn method=POST status=500",
+    ];
+    let logs: Vec<_> = lines.iter().map(|line| Log::parse(line).unwrap()).collect();
+    let count = |convert: &mut dyn FnMut(&Log<'_>) -> OwnedLog| {
+        logs.iter()
+            .map(|log| {
+                let before = ALLOCATIONS.with(Cell::get);
+                let owned = convert(log);
+                let allocations = ALLOCATIONS.with(Cell::get) - before;
+                drop(owned);
+                allocations
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The keys of the second log are taken from the interner, instead of allocated with the
+    // list of a new interner
+    let copied = count(&mut |log| OwnedLog::from_log_interned(log, &mut KeyInterner::new()));
+    let mut interner = KeyInterner::new();
+    let interned = count(&mut |log| OwnedLog::from_log_interned(log, &mut interner));
+    assert_eq!(interned[0], copied[0]);
+    assert_eq!(interned[1] + 3, copied[1] - 1);
+}
+
+#[test]
+fn unbuffered_encoder() {
+    let mut line = String::with_capacity(256);
+    let before = ALLOCATIONS.with(Cell::get);
+    let mut encoder = Encoder::new(&mut line);
+    encoder
+        .message("request done")
+        .unwrap()
+        .push("method", "GET")
+        .unwrap()
+        .push("path", "/index.html")
+        .unwrap();
+    encoder.try_finish().unwrap();
+    assert_eq!(ALLOCATIONS.with(Cell::get) - before, 0);
+    assert_eq!(line, "msg=\"request done\" method=GET path=/index.html");
+
+    // Attributes too long for the stack are formatted on the heap
+    let long = "x".repeat(300);
+    let before = ALLOCATIONS.with(Cell::get);
+    Encoder::new(&mut line).push("long", &long).unwrap();
+    assert!(ALLOCATIONS.with(Cell::get) - before > 0);
+}
@@ -0,0 +1,135 @@
+//! Regression tests against real-world log lines.
+//! Every `tests/fixtures/*.txt` file holds lines of a single source, and the `.expected` file
+//! next to it describes how each non-empty line is parsed with the default options, in blocks
+//! separated by an empty line:
+//!
+//! ```text
+//! # Comments at the start of the file record why lines are parsed the way they are
+//! message: request done
+//! attr: status=200
+//! attr: path="/"
+//!
+//! fallback: State changed from starting to up
+//!
+//! error: err.unclosed_string
+//! ```
+//!
+//! `message` is the message, `fallback` a message that is the full line, `attr` an attribute
+//! with quotes kept, and `error` the id of the error parsing fails with.
+//! Run with `LOGFMT_BLESS=1` to write the `.expected` files from the current results, keeping
+//! their comments.
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use logfmt_nostd::{Log, ParseOptions};
+
+/// The directory of the fixtures
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Returns the block describing how the line is parsed
+fn describe(line: &str) -> String {
+    let log = match Log::parse_with(line, &ParseOptions::default()) {
+        Ok(log) => log,
+        Err(error) => return format!("error: {}\n", error.kind().message_id()),
+    };
+    let kind = if log.message_is_fallback() {
+        "fallback"
+    } else {
+        "message"
+    };
+    // Trailing whitespace is trimmed, as editors tend to strip it
+    let mut block = format!("{kind}: {}", log.message()).trim_end().to_owned();
+    block.push('\n');
+    for (key, value) in log.iter() {
+        writeln!(block, "attr: {key}={value}").unwrap();
+    }
+    block
+}
+
+/// Splits an `.expected` file in the comments at its start and its blocks
+fn parse_expected(expected: &str) -> (String, Vec<String>) {
+    let mut comments = String::new();
+    let mut lines = expected.lines().peekable();
+    while let Some(line) = lines.next_if(|line| line.starts_with('#')) {
+        comments.push_str(line);
+        comments.push('\n');
+    }
+    let mut blocks = Vec::new();
+    let mut block = String::new();
+    for line in lines {
+        if line.is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+            }
+            continue;
+        }
+        assert!(
+            ["message:", "fallback:", "attr: ", "error: "]
+                .iter()
+                .any(|prefix| line.starts_with(prefix)),
+            "unknown expectation {line:?}"
+        );
+        block.push_str(line);
+        block.push('\n');
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    (comments, blocks)
+}
+
+#[test]
+fn golden() {
+    let bless = env::var_os("LOGFMT_BLESS").is_some();
+    let mut inputs: Vec<_> = fs::read_dir(fixtures())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no fixtures found");
+
+    let mut failures = Vec::new();
+    for input in inputs {
+        let expected_path = input.with_extension("expected");
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        let (comments, expected) = parse_expected(&expected);
+        let actual: Vec<_> = fs::read_to_string(&input)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(describe)
+            .collect();
+
+        if bless {
+            let blocks = actual.join("\n");
+            let separator = if comments.is_empty() { "" } else { "\n" };
+            fs::write(&expected_path, format!("{comments}{separator}{blocks}")).unwrap();
+            continue;
+        }
+        let name = input.file_name().unwrap().to_string_lossy();
+        if actual.len() != expected.len() {
+            failures.push(format!(
+                "{name}: {} lines, but {} expected blocks",
+                actual.len(),
+                expected.len()
+            ));
+        }
+        for (number, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+            if actual != expected {
+                failures.push(format!(
+                    "{name} line {}:\nexpected:\n{expected}actual:\n{actual}",
+                    number + 1
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,168 @@
+//! Checks that the public items keep the paths downstream code imports them from: every item
+//! stays available at the crate root, and is the same item as in its module
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+use std::ops::ControlFlow;
+
+#[cfg(feature = "allocator_api")]
+use logfmt_nostd::AllocLog;
+#[cfg(feature = "std")]
+use logfmt_nostd::read_logs_std;
+use logfmt_nostd::{
+    Attribute, AttributeIter, AutoParseError, BoolStyle, BufferTooSmall, ColorScheme, ControlChars,
+    DEFAULT_MAX_ATTRIBUTES, DEFAULT_NULL_VALUES, DEFAULT_WRAP_COLUMN, Dialect, DuplicatePolicy,
+    EncodeError, EncodeOptions, EncodeStyle, Encoder, EncoderFactory, EqualsInValue, ErrorKind,
+    FlagWords, InvalidKey, Iter, JsonError, JsonErrorKind, JsonValues, KeyFilter, KeyInterner,
+    KeyStyle, Keys, LazyLog, Level, LineContinuation, ListStyle, Log, LogBuilder, LogVisitor,
+    MergePolicy, MessageRenderer, Number, OverlongValue, OwnedLog, ParseError, ParseLines,
+    ParseOptions, Separators, StreamParser, SyslogOptions, Template, TemplateError,
+    TemplateErrorKind, Timestamp, Token, TokenIter, TypedIter, TypedValue, UnclosedString,
+    UnescapeError, UnknownLevel, Value, Values, attributes, batch, const_logfmt,
+    default_key_validator, encode, error, log, loki, otel, parse, parse_attribute, parse_lines,
+    parse_visit, tokens, unescape, unquote, value,
+};
+
+/// Checks at compile time that each pair of paths names the same type
+macro_rules! same_types {
+    ($($root:ty => $module:ty),* $(,)?) => {
+        $(let _: fn($root) -> $module = |item| item;)*
+    };
+}
+
+/// Checks at compile time that both values have the same type
+fn same_item<T>(_: T, _: T) {}
+
+/// A visitor ignoring everything, to name [`parse_visit`] with
+struct Ignore;
+
+impl LogVisitor<'_> for Ignore {
+    fn word(&mut self, _: &str) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    fn attribute(&mut self, _: &str, _: &str) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    fn message_override(&mut self, _: &str) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+#[test]
+fn root_paths_match_modules() {
+    same_types!(
+        UnclosedString => error::UnclosedString,
+        ErrorKind => error::ErrorKind,
+        Box<dyn MessageRenderer> => Box<dyn error::MessageRenderer>,
+        ParseError => error::ParseError,
+        InvalidKey => error::InvalidKey,
+        BufferTooSmall => error::BufferTooSmall,
+        JsonErrorKind => error::JsonErrorKind,
+        JsonError => error::JsonError,
+        AutoParseError => error::AutoParseError,
+        UnescapeError => error::UnescapeError,
+        UnknownLevel => error::UnknownLevel,
+        EncodeError => error::EncodeError,
+        TemplateError => error::TemplateError,
+        TemplateErrorKind => error::TemplateErrorKind,
+        batch::HeaderError => error::HeaderError,
+        FlagWords<'static> => parse::FlagWords<'static>,
+        DuplicatePolicy => parse::DuplicatePolicy,
+        KeyFilter<'static> => parse::KeyFilter<'static>,
+        EqualsInValue => parse::EqualsInValue,
+        Dialect => parse::Dialect,
+        Separators<'static> => parse::Separators<'static>,
+        KeyStyle => parse::KeyStyle,
+        ControlChars => parse::ControlChars,
+        OverlongValue => parse::OverlongValue,
+        ParseOptions<'static> => parse::ParseOptions<'static>,
+        Token<'static> => parse::Token<'static>,
+        TokenIter<'static> => parse::TokenIter<'static>,
+        Box<dyn LogVisitor<'static>> => Box<dyn parse::LogVisitor<'static>>,
+        AttributeIter<'static> => parse::AttributeIter<'static>,
+        LineContinuation => parse::LineContinuation,
+        ParseLines<'static> => parse::ParseLines<'static>,
+        BoolStyle => value::BoolStyle,
+        Level => value::Level,
+        Value<'static> => value::Value<'static>,
+        TypedValue<'static> => value::TypedValue<'static>,
+        Number => value::Number,
+        Timestamp => value::Timestamp,
+        JsonValues => encode::JsonValues,
+        EncodeOptions => encode::EncodeOptions,
+        EncodeStyle => encode::EncodeStyle,
+        ListStyle => encode::ListStyle,
+        SyslogOptions<'static> => encode::SyslogOptions<'static>,
+        ColorScheme<'static> => encode::ColorScheme<'static>,
+        Encoder<String> => encode::Encoder<String>,
+        EncoderFactory => encode::EncoderFactory,
+        Log<'static> => log::Log<'static>,
+        Attribute<'static> => log::Attribute<'static>,
+        Iter<'static> => log::Iter<'static>,
+        Keys<'static> => log::Keys<'static>,
+        Values<'static> => log::Values<'static>,
+        TypedIter<'static> => log::TypedIter<'static>,
+        LogBuilder<'static> => log::LogBuilder<'static>,
+        LazyLog<'static> => log::LazyLog<'static>,
+        MergePolicy => log::MergePolicy,
+        OwnedLog => log::OwnedLog,
+    );
+
+    assert_eq!(DEFAULT_MAX_ATTRIBUTES, parse::DEFAULT_MAX_ATTRIBUTES);
+    assert_eq!(DEFAULT_NULL_VALUES, value::DEFAULT_NULL_VALUES);
+    assert_eq!(DEFAULT_WRAP_COLUMN, encode::DEFAULT_WRAP_COLUMN);
+
+    // Distinct functions have distinct types, so both paths have to name the same function
+    same_item(tokens, parse::tokens);
+    same_item(attributes, parse::attributes);
+    same_item(unquote, parse::unquote);
+    same_item(default_key_validator, parse::default_key_validator);
+    same_item(unescape, parse::unescape);
+    same_item(parse_lines, parse::parse_lines);
+    assert_eq!(
+        parse_visit("a=1", &mut Ignore),
+        parse::parse_visit("a=1", &mut Ignore)
+    );
+}
+
+#[test]
+fn root_items() {
+    // Items used from the root like downstream code uses them
+    let log = Log::parse("request done status=200").unwrap();
+    let _: &[Attribute<'_>] = log.attributes();
+    let _: Iter<'_> = log.iter();
+    let _: Keys<'_> = log.keys();
+    let _: Values<'_> = log.values();
+    let _: TypedIter<'_> = log.typed_iter();
+    let _: LogBuilder<'_> = Log::builder();
+    let _: LazyLog<'_> = Log::build().build_lazy().unwrap();
+    let _: MergePolicy = MergePolicy::KeepExisting;
+    assert_eq!(OwnedLog::from(log.clone()).message(), "request done");
+    assert_eq!(parse_attribute("status=200"), Some(("status", "200")));
+    const ATTRIBUTES: &[(&str, &str)] = const_logfmt!("at=info status=200");
+    assert_eq!(ATTRIBUTES, [("at", "info"), ("status", "200")]);
+
+    let _ = (
+        KeyInterner::new(),
+        StreamParser::new(1024),
+        Template::compile("{msg}"),
+    );
+    let _: Option<batch::HeaderError> = None;
+    let _: (Option<loki::LokiBatch>, [otel::Rename<'_>; 4]) = (None, otel::DEFAULT_RENAMES);
+    assert_eq!(batch::MAGIC, "LFMT");
+    #[cfg(feature = "allocator_api")]
+    let _: Option<AllocLog<'_, std::alloc::Global>> = None;
+    #[cfg(feature = "std")]
+    let _ = read_logs_std(std::io::Cursor::new("a=1\n"));
+}
+
+#[test]
+fn prelude() {
+    use logfmt_nostd::prelude::*;
+
+    let log: Log<'_> = Log::parse_with("request done", &ParseOptions::default()).unwrap();
+    let _: Result<Log<'_>, ParseError> = Log::parse_with("a=\"", &ParseOptions::default());
+    let _: fn(Encoder<String>) -> logfmt_nostd::Encoder<String> = |encoder| encoder;
+    assert_eq!(log.message(), "request done");
+}